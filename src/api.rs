@@ -0,0 +1,346 @@
+use hyper::{
+    body::{Bytes, HttpBody},
+    header,
+    service::{make_service_fn, service_fn},
+    Body, HeaderMap, Method, Request, Response, Server, StatusCode,
+};
+use log::{error, info};
+use poise::serenity_prelude::{GuildId, Http};
+use serde_json::{json, Value};
+use songbird::Songbird;
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+
+use crate::{
+    client_state::{ClientStateError, EventBus},
+    config::{Error, ServerState},
+};
+
+pub(crate) mod dashboard;
+pub(crate) mod endpoints;
+
+/// The largest request body accepted.
+const MAX_BODY: usize = 64 * 1024;
+
+/// An embedded HTTP server to inspect and control playback, operating on the same state
+/// as the slash commands. Every request must carry the configured token.
+#[derive(Clone)]
+pub(crate) struct Api {
+    pub(crate) data: ServerState,
+    pub(crate) http: Arc<Http>,
+    pub(crate) songbird: Arc<Songbird>,
+    /// Streamed to the dashboard.
+    pub(crate) events: EventBus,
+    pub(crate) token: Arc<str>,
+}
+
+/// An endpoint of the API.
+#[derive(Debug, PartialEq)]
+enum Route {
+    /// The dashboard page, which asks for the token itself.
+    Dashboard,
+    /// The dashboard's WebSocket, authenticated by its first message.
+    Events,
+    Guilds,
+    Queue(GuildId),
+    Enqueue(GuildId),
+    Move(GuildId),
+    Current(GuildId),
+    Skip(GuildId),
+    Pause(GuildId),
+    Resume(GuildId),
+    Seek(GuildId),
+}
+
+impl Route {
+    fn parse(method: &Method, path: &str) -> Option<Route> {
+        let segments = path.trim_matches('/').split('/').collect::<Vec<_>>();
+
+        let (guild_id, rest) = match segments[..] {
+            [""] if method == Method::GET => return Some(Route::Dashboard),
+            ["api", "events"] if method == Method::GET => return Some(Route::Events),
+            ["api", "guilds"] if method == Method::GET => return Some(Route::Guilds),
+            ["api", "guilds", id, ref rest @ ..] => (GuildId(id.parse().ok()?), rest),
+            _ => return None,
+        };
+
+        Some(match (method, rest) {
+            (&Method::GET, ["queue"]) => Route::Queue(guild_id),
+            (&Method::POST, ["queue"]) => Route::Enqueue(guild_id),
+            (&Method::POST, ["queue", "move"]) => Route::Move(guild_id),
+            (&Method::GET, ["current"]) => Route::Current(guild_id),
+            (&Method::POST, ["skip"]) => Route::Skip(guild_id),
+            (&Method::POST, ["pause"]) => Route::Pause(guild_id),
+            (&Method::POST, ["resume"]) => Route::Resume(guild_id),
+            (&Method::POST, ["seek"]) => Route::Seek(guild_id),
+            _ => return None,
+        })
+    }
+}
+
+/// A failed request, answered with its status and a JSON error message.
+#[derive(Debug)]
+pub(crate) struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    pub(crate) fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        ApiError {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+/// Reads a request body of at most [`MAX_BODY`] bytes. Larger bodies are refused by their
+/// announced length, or as soon as they grow past it, so they are never buffered whole.
+async fn read_body(headers: &HeaderMap, mut body: Body) -> Result<Bytes, ApiError> {
+    let too_large = || {
+        ApiError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "The request body is too large.",
+        )
+    };
+
+    let announced = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok())
+        .and_then(|length| length.parse::<usize>().ok());
+    if announced.is_some_and(|length| length > MAX_BODY) {
+        return Err(too_large());
+    }
+
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|_| {
+            ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "The request body could not be read.",
+            )
+        })?;
+        if bytes.len() + chunk.len() > MAX_BODY {
+            return Err(too_large());
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+
+    Ok(bytes.into())
+}
+
+impl From<Error> for ApiError {
+    fn from(err: Error) -> Self {
+        let status = match &err {
+            Error::State(ClientStateError::NonExistentClientID) => {
+                return ApiError::new(
+                    StatusCode::NOT_FOUND,
+                    "The bot is not in a voice channel in this guild.",
+                )
+            }
+            // Mostly requests that don't fit what the player is doing, like skipping silence.
+            Error::UserInput(_) => StatusCode::CONFLICT,
+            Error::SourceResolution(_) | Error::Unsupported(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Error::YouTubeApi(_) => StatusCode::BAD_GATEWAY,
+            Error::VoiceConnection(_) | Error::State(_) | Error::Discord(_) => {
+                error!("API request failed. Error: {err}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+
+        ApiError::new(status, err.user_message())
+    }
+}
+
+impl From<ClientStateError> for ApiError {
+    fn from(err: ClientStateError) -> Self {
+        Error::State(err).into()
+    }
+}
+
+/// Compares tokens in constant time, so their content can't be guessed from response times.
+fn token_matches(given: &str, token: &str) -> bool {
+    ring::constant_time::verify_slices_are_equal(given.as_bytes(), token.as_bytes()).is_ok()
+}
+
+/// Whether the request carries the token as `Authorization: Bearer <token>`.
+fn authorized(headers: &HeaderMap, token: &str) -> bool {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|given| token_matches(given, token))
+}
+
+fn respond(status: StatusCode, body: &Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap_or_default()
+}
+
+impl Api {
+    /// Serves the API on the given address until the bot shuts down.
+    pub(crate) fn serve(self, address: SocketAddr) {
+        let api = Arc::new(self);
+
+        let make_service = make_service_fn(move |_| {
+            let api = api.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let api = api.clone();
+                    async move { Ok::<_, Infallible>(Api::handle(api, request).await) }
+                }))
+            }
+        });
+
+        tokio::spawn(async move {
+            let server = match Server::try_bind(&address) {
+                Ok(server) => server,
+                Err(err) => {
+                    error!("Could not serve the API on {address}. Error: {err}");
+                    return;
+                }
+            };
+
+            info!("Serving the API on {address}.");
+            if let Err(err) = server.serve(make_service).await {
+                error!("The API stopped. Error: {err}");
+            }
+        });
+    }
+
+    async fn handle(self: Arc<Self>, request: Request<Body>) -> Response<Body> {
+        let route = Route::parse(request.method(), request.uri().path());
+
+        match route {
+            Some(Route::Dashboard) => return dashboard::page(),
+            Some(Route::Events) => return dashboard::connect(self, request),
+            _ => {}
+        }
+
+        if !authorized(request.headers(), &self.token) {
+            return respond(
+                StatusCode::UNAUTHORIZED,
+                &json!({ "error": "A valid bearer token is required." }),
+            );
+        }
+
+        let route = match route {
+            Some(route) => route,
+            None => return respond(StatusCode::NOT_FOUND, &json!({ "error": "Not found." })),
+        };
+
+        let (parts, body) = request.into_parts();
+        let body = match read_body(&parts.headers, body).await {
+            Ok(body) => body,
+            Err(err) => return respond(err.status, &json!({ "error": err.message })),
+        };
+
+        let api = self.as_ref();
+        let result = match route {
+            Route::Dashboard | Route::Events => unreachable!("served without a bearer token"),
+            Route::Guilds => endpoints::guilds(api).await,
+            Route::Queue(guild_id) => endpoints::queue(api, guild_id).await,
+            Route::Enqueue(guild_id) => endpoints::enqueue(api, guild_id, &body).await,
+            Route::Move(guild_id) => endpoints::move_element(api, guild_id, &body).await,
+            Route::Current(guild_id) => endpoints::current(api, guild_id).await,
+            Route::Skip(guild_id) => endpoints::skip(api, guild_id).await,
+            Route::Pause(guild_id) => endpoints::pause(api, guild_id).await,
+            Route::Resume(guild_id) => endpoints::resume(api, guild_id).await,
+            Route::Seek(guild_id) => endpoints::seek(api, guild_id, &body).await,
+        };
+
+        match result {
+            Ok(body) => respond(StatusCode::OK, &body),
+            Err(err) => respond(err.status, &json!({ "error": err.message })),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_are_parsed() {
+        for (method, path, route) in [
+            (Method::GET, "/", Route::Dashboard),
+            (Method::GET, "/api/events", Route::Events),
+            (Method::GET, "/api/guilds", Route::Guilds),
+            (
+                Method::GET,
+                "/api/guilds/42/queue",
+                Route::Queue(GuildId(42)),
+            ),
+            (
+                Method::POST,
+                "/api/guilds/42/queue",
+                Route::Enqueue(GuildId(42)),
+            ),
+            (
+                Method::POST,
+                "/api/guilds/42/queue/move/",
+                Route::Move(GuildId(42)),
+            ),
+            (
+                Method::GET,
+                "/api/guilds/42/current",
+                Route::Current(GuildId(42)),
+            ),
+            (
+                Method::POST,
+                "/api/guilds/42/seek",
+                Route::Seek(GuildId(42)),
+            ),
+        ] {
+            assert_eq!(Route::parse(&method, path), Some(route), "{method} {path}");
+        }
+    }
+
+    #[test]
+    fn unknown_routes_are_rejected() {
+        for (method, path) in [
+            (Method::POST, "/api/guilds"),
+            (Method::GET, "/api/guilds/42/skip"),
+            (Method::POST, "/api/guilds/abc/skip"),
+            (Method::GET, "/guilds"),
+            (Method::GET, "/api/guilds/42/volume"),
+            (Method::POST, "/"),
+        ] {
+            assert_eq!(Route::parse(&method, path), None, "{method} {path}");
+        }
+    }
+
+    #[test]
+    fn requests_need_the_token() {
+        let headers = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::AUTHORIZATION, value.parse().unwrap());
+            headers
+        };
+
+        assert!(authorized(&headers("Bearer secret"), "secret"));
+        assert!(!authorized(&headers("Bearer secrets"), "secret"));
+        assert!(!authorized(&headers("secret"), "secret"));
+        assert!(!authorized(&HeaderMap::new(), "secret"));
+    }
+
+    #[tokio::test]
+    async fn large_bodies_are_refused() {
+        let mut announced = HeaderMap::new();
+        announced.insert(header::CONTENT_LENGTH, (MAX_BODY + 1).into());
+
+        let small = read_body(&HeaderMap::new(), Body::from("{}")).await;
+        let lying = read_body(&announced, Body::from("{}")).await;
+        let (mut sender, body) = Body::channel();
+        tokio::spawn(
+            async move { while sender.send_data(vec![0u8; 1024].into()).await.is_ok() {} },
+        );
+        let streamed = read_body(&HeaderMap::new(), body).await;
+
+        assert_eq!(small.unwrap(), "{}");
+        assert_eq!(lying.unwrap_err().status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(streamed.unwrap_err().status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
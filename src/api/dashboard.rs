@@ -0,0 +1,195 @@
+use async_tungstenite::{
+    tokio::TokioAdapter,
+    tungstenite::{self, handshake::derive_accept_key, protocol::Role, Message},
+    WebSocketStream,
+};
+use futures::{SinkExt, StreamExt};
+use hyper::{header, upgrade::Upgraded, Body, HeaderMap, Request, Response, StatusCode};
+use log::debug;
+use poise::serenity_prelude::GuildId;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{sync::Arc, time::Duration};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{
+    api::{endpoints, respond, token_matches, Api},
+    client_state::{GuildEvent, StateEvent},
+};
+
+/// The dashboard, a single page that talks to the API and the event socket.
+const PAGE: &str = include_str!("dashboard/index.html");
+
+/// How long a socket may take to send the token before it is closed.
+const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+type Socket = WebSocketStream<TokioAdapter<Upgraded>>;
+
+/// The first message of a socket. Browsers can't set headers on WebSockets,
+/// so the token is sent once the connection is open.
+#[derive(Deserialize)]
+struct Hello {
+    token: String,
+}
+
+pub(crate) fn page() -> Response<Body> {
+    Response::builder()
+        .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+        .body(Body::from(PAGE))
+        .unwrap_or_default()
+}
+
+/// Returns the key of a WebSocket handshake, if the request is one.
+fn handshake_key(headers: &HeaderMap) -> Option<&str> {
+    let is = |name, expected: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.eq_ignore_ascii_case(expected))
+    };
+
+    if !is(header::UPGRADE, "websocket") || !is(header::SEC_WEBSOCKET_VERSION, "13") {
+        return None;
+    }
+
+    headers
+        .get(header::SEC_WEBSOCKET_KEY)
+        .and_then(|value| value.to_str().ok())
+}
+
+/// Accepts the dashboard's WebSocket. Once authenticated, it receives the state of every
+/// guild, then each guild again whenever it changes.
+pub(crate) fn connect(api: Arc<Api>, request: Request<Body>) -> Response<Body> {
+    let accept = match handshake_key(request.headers()) {
+        Some(key) => derive_accept_key(key.as_bytes()),
+        None => {
+            return respond(
+                StatusCode::BAD_REQUEST,
+                &json!({ "error": "Expected a WebSocket handshake." }),
+            )
+        }
+    };
+
+    tokio::spawn(async move {
+        let socket = match hyper::upgrade::on(request).await {
+            Ok(upgraded) => {
+                WebSocketStream::from_raw_socket(TokioAdapter::new(upgraded), Role::Server, None)
+                    .await
+            }
+            Err(err) => {
+                debug!("Could not upgrade to a WebSocket. Error: {err}");
+                return;
+            }
+        };
+
+        if let Err(err) = stream(&api, socket).await {
+            debug!("Dashboard socket closed. Error: {err}");
+        }
+    });
+
+    Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(header::CONNECTION, "upgrade")
+        .header(header::UPGRADE, "websocket")
+        .header(header::SEC_WEBSOCKET_ACCEPT, accept)
+        .body(Body::empty())
+        .unwrap_or_default()
+}
+
+fn text(message: Value) -> Message {
+    Message::Text(message.to_string())
+}
+
+/// The guild's player and full queue, or `None` if the bot left it.
+async fn guild(api: &Api, guild_id: u64) -> Option<Value> {
+    let client_state = api.data.client_state_map.get(&guild_id).await?;
+
+    let mut guild = endpoints::guild_json(guild_id, &client_state);
+    guild["queue"] = endpoints::queue_json(client_state.song_queue.as_deref().unwrap_or_default());
+
+    Some(guild)
+}
+
+async fn snapshot(api: &Api) -> Message {
+    let mut guilds = vec![];
+    for id in api.data.client_state_map.ids() {
+        guilds.extend(guild(api, id).await);
+    }
+
+    text(json!({ "type": "guilds", "guilds": guilds }))
+}
+
+async fn stream(api: &Api, mut socket: Socket) -> Result<(), tungstenite::Error> {
+    let authenticated = match tokio::time::timeout(AUTH_TIMEOUT, socket.next()).await {
+        Ok(Some(Ok(Message::Text(hello)))) => serde_json::from_str::<Hello>(&hello)
+            .is_ok_and(|hello| token_matches(&hello.token, &api.token)),
+        _ => false,
+    };
+
+    if !authenticated {
+        socket
+            .send(text(json!({ "type": "error", "error": "Invalid token." })))
+            .await?;
+        return socket.close(None).await;
+    }
+
+    // Subscribing first, so no change between the snapshot and the first event is missed.
+    let mut events = api.events.subscribe();
+    socket.send(snapshot(api).await).await?;
+
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(GuildEvent { guild_id, event: StateEvent::Left }) => {
+                    socket
+                        .send(text(json!({ "type": "left", "guild_id": guild_id.to_string() })))
+                        .await?;
+                }
+                Ok(GuildEvent { guild_id: GuildId(id), .. }) => {
+                    if let Some(guild) = guild(api, id).await {
+                        socket.send(text(json!({ "type": "guild", "guild": guild }))).await?;
+                    }
+                }
+                Err(RecvError::Lagged(_)) => socket.send(snapshot(api).await).await?,
+                Err(RecvError::Closed) => return socket.close(None).await,
+            },
+            message = socket.next() => match message {
+                // Pings are answered by tungstenite, and the dashboard sends nothing else.
+                Some(Ok(Message::Close(_))) | None => return Ok(()),
+                Some(Ok(_)) => {}
+                Some(Err(err)) => return Err(err),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_websocket_handshakes_are_accepted() {
+        let headers = |pairs: &[(header::HeaderName, &str)]| {
+            let mut headers = HeaderMap::new();
+            for (name, value) in pairs {
+                headers.insert(name, value.parse().unwrap());
+            }
+            headers
+        };
+        let handshake = [
+            (header::UPGRADE, "WebSocket"),
+            (header::SEC_WEBSOCKET_VERSION, "13"),
+            (header::SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ=="),
+        ];
+
+        assert_eq!(
+            handshake_key(&headers(&handshake)),
+            Some("dGhlIHNhbXBsZSBub25jZQ==")
+        );
+        assert_eq!(handshake_key(&headers(&handshake[1..])), None);
+        assert_eq!(
+            handshake_key(&headers(&[handshake[0].clone(), handshake[2].clone()])),
+            None
+        );
+    }
+}
@@ -0,0 +1,352 @@
+use hyper::StatusCode;
+use poise::serenity_prelude::{ChannelId, GuildId};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use crate::{
+    api::{Api, ApiError},
+    client_state::{ClientState, ClientStateError, QueueElement},
+    commands::{
+        play::{self, PlayStatus, PlaylistOptions},
+        track::{pause::pause_track, resume::resume_track, skip::skip_track},
+    },
+    config::Error,
+    utils::source_retriever::SourceType,
+};
+
+type ApiResult = Result<Value, ApiError>;
+
+fn parse<T: DeserializeOwned>(body: &[u8]) -> Result<T, ApiError> {
+    serde_json::from_slice(body)
+        .map_err(|err| ApiError::new(StatusCode::BAD_REQUEST, format!("Invalid body: {err}")))
+}
+
+pub(crate) fn queue_json(queue: &[QueueElement]) -> Value {
+    queue.iter().map(QueueElement::to_json).collect()
+}
+
+/// Describes a guild's player and queue length.
+pub(crate) fn guild_json(id: u64, client_state: &ClientState) -> Value {
+    json!({
+        "id": id.to_string(),
+        "channel_id": client_state.current_channel.map(|channel| channel.to_string()),
+        "playback": client_state.playback,
+        "now_playing": client_state.now_playing.as_ref().map(QueueElement::to_json),
+        "queue_length": client_state.song_queue.as_ref().map_or(0, Vec::len),
+    })
+}
+
+/// Lists the guilds the bot is in a voice channel of.
+pub(crate) async fn guilds(api: &Api) -> ApiResult {
+    let mut guilds = vec![];
+
+    for id in api.data.client_state_map.ids() {
+        if let Some(client_state) = api.data.client_state_map.get(&id).await {
+            guilds.push(guild_json(id, &client_state));
+        }
+    }
+
+    Ok(Value::Array(guilds))
+}
+
+pub(crate) async fn queue(api: &Api, guild_id: GuildId) -> ApiResult {
+    let queue = api
+        .data
+        .client_state_map
+        .with(guild_id.as_u64(), |client_state| {
+            queue_json(client_state.song_queue.as_deref().unwrap_or_default())
+        })
+        .await?;
+
+    Ok(queue)
+}
+
+/// The current track and how far into it playback is.
+pub(crate) async fn current(api: &Api, guild_id: GuildId) -> ApiResult {
+    let client_state = api
+        .data
+        .client_state_map
+        .get(guild_id.as_u64())
+        .await
+        .ok_or(ClientStateError::NonExistentClientID)?;
+
+    let position = match (
+        &client_state.current_track,
+        client_state.playback.has_track(),
+    ) {
+        (Some(track), true) => track
+            .get_info()
+            .await
+            .ok()
+            .map(|info| info.position.as_secs()),
+        _ => None,
+    };
+
+    Ok(json!({
+        "playback": client_state.playback,
+        "track": client_state.now_playing.as_ref().map(QueueElement::to_json),
+        "position": position,
+    }))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct Enqueue {
+    /// A URL or search query, as taken by `/play`.
+    query: String,
+    /// The playlist position to start from, like `/play`'s `from`.
+    from: Option<usize>,
+    /// The playlist position to stop at, like `/play`'s `to`.
+    to: Option<usize>,
+    #[serde(default)]
+    shuffle: bool,
+}
+
+/// Queues a video or playlist like `/play`, starting playback if nothing plays. The first page
+/// of a playlist is queued right away, the rest is imported in the background.
+/// The bot must already be in a voice channel of the guild.
+pub(crate) async fn enqueue(api: &Api, guild_id: GuildId, body: &[u8]) -> ApiResult {
+    let Enqueue {
+        query,
+        from,
+        to,
+        shuffle,
+    } = parse(body)?;
+
+    let client_state = api
+        .data
+        .client_state_map
+        .get(guild_id.as_u64())
+        .await
+        .ok_or(ClientStateError::NonExistentClientID)?;
+
+    let mut input = play::source_input(api.data.youtube_client.as_ref(), query).await?;
+
+    let room = play::queue_room(&api.data, guild_id).await;
+    if room == Some(0) {
+        return Err(ApiError::new(StatusCode::CONFLICT, "The queue is full."));
+    }
+
+    let unprocessable = |err| match err {
+        Error::UserInput(reason) => ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, reason),
+        err => err.into(),
+    };
+
+    let settings = api.data.settings.get(guild_id);
+    let mut import = None;
+    match &mut input {
+        SourceType::Single(element) => settings
+            .check_length(element.duration)
+            .map_err(|reason| ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, reason))?,
+        SourceType::Playlist(playlist) => {
+            // Import progress goes where announcements go.
+            let channel_id = settings
+                .announcements
+                .then(|| settings.announce_channel.or(client_state.text_channel))
+                .flatten()
+                .map(ChannelId);
+
+            import = play::prepare_playlist(
+                &api.data,
+                &api.http,
+                channel_id,
+                guild_id,
+                playlist,
+                PlaylistOptions { from, to, shuffle },
+                room,
+            )
+            .await
+            .map_err(unprocessable)?;
+        }
+    }
+
+    let status =
+        play::handle_play(&guild_id, &api.data, &api.http, &api.songbird, None, input).await?;
+
+    if let Some(import) = import {
+        play::start_import(&api.data, guild_id, import).await;
+    }
+
+    Ok(match status {
+        PlayStatus::Playing(element) => json!({ "playing": element.to_json(), "queued": [] }),
+        PlayStatus::Queued(SourceType::Single(element)) => {
+            json!({ "playing": null, "queued": [element.to_json()] })
+        }
+        PlayStatus::Queued(SourceType::Playlist(playlist)) => {
+            json!({ "playing": null, "queued": queue_json(&playlist.page.items) })
+        }
+        PlayStatus::PlayAndQueued(elements) => json!({
+            "playing": elements.first().map(QueueElement::to_json),
+            "queued": queue_json(elements.get(1..).unwrap_or_default()),
+        }),
+    })
+}
+
+#[derive(Deserialize)]
+pub(crate) struct Move {
+    /// The element's current zero-based position in the queue.
+    from: usize,
+    /// The id of the element expected at `from`.
+    id: String,
+    /// The position it is moved to.
+    to: usize,
+}
+
+/// Moves a queued element to another position. Answers 409 if the queue changed since the
+/// caller last saw it, so that a stale position doesn't move another element.
+fn move_in_queue(
+    queue: &mut Vec<QueueElement>,
+    Move { from, id, to }: Move,
+) -> Result<(), ApiError> {
+    if from >= queue.len() || to >= queue.len() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            format!("The queue has {} elements.", queue.len()),
+        ));
+    }
+    if queue[from].id != id {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "The queue changed, the element is no longer at that position.",
+        ));
+    }
+
+    let element = queue.remove(from);
+    queue.insert(to, element);
+    Ok(())
+}
+
+/// Moves a queued element to another position. Returns the reordered queue.
+pub(crate) async fn move_element(api: &Api, guild_id: GuildId, body: &[u8]) -> ApiResult {
+    let request = parse::<Move>(body)?;
+
+    api.data
+        .client_state_map
+        .with(guild_id.as_u64(), move |client_state| {
+            let queue = client_state.song_queue.get_or_insert_with(Vec::new);
+            move_in_queue(queue, request)?;
+
+            Ok(queue_json(queue))
+        })
+        .await?
+}
+
+/// Skips the current track. Returns the element that plays next, if any.
+pub(crate) async fn skip(api: &Api, guild_id: GuildId) -> ApiResult {
+    let next = skip_track(&api.data.client_state_map, guild_id).await?;
+
+    Ok(json!({ "next": next.as_ref().map(QueueElement::to_json) }))
+}
+
+pub(crate) async fn pause(api: &Api, guild_id: GuildId) -> ApiResult {
+    let message = pause_track(&api.data.client_state_map, guild_id).await?;
+
+    Ok(json!({ "message": message }))
+}
+
+pub(crate) async fn resume(api: &Api, guild_id: GuildId) -> ApiResult {
+    let message = resume_track(&api.data.client_state_map, guild_id).await?;
+
+    Ok(json!({ "message": message }))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct Seek {
+    /// The position to play from, in seconds.
+    position: u64,
+}
+
+/// Seeks the current track to an absolute position.
+pub(crate) async fn seek(api: &Api, guild_id: GuildId, body: &[u8]) -> ApiResult {
+    let Seek { position } = parse(body)?;
+
+    let client_state = api
+        .data
+        .client_state_map
+        .get(guild_id.as_u64())
+        .await
+        .ok_or(ClientStateError::NonExistentClientID)?;
+
+    let (track, element) = match (&client_state.current_track, &client_state.now_playing) {
+        (Some(track), Some(element)) if client_state.playback.has_track() => (track, element),
+        _ => {
+            return Err(ApiError::new(
+                StatusCode::CONFLICT,
+                "Nothing is currently playing.",
+            ))
+        }
+    };
+
+    if element.live {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "Livestreams can't be seeked.",
+        ));
+    }
+
+    let position = Duration::from_secs(position);
+    let length = track.metadata().duration.or(element.duration);
+    if length.is_some_and(|length| position > length) {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "The position is outside the track's duration.",
+        ));
+    }
+
+    track.seek_time(position).map_err(Error::from)?;
+
+    Ok(json!({ "position": position.as_secs() }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue() -> Vec<QueueElement> {
+        ["a", "b", "c"]
+            .into_iter()
+            .map(|id| QueueElement {
+                id: id.to_string(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn ids(queue: &[QueueElement]) -> Vec<&str> {
+        queue.iter().map(|element| element.id.as_str()).collect()
+    }
+
+    fn request(from: usize, id: &str, to: usize) -> Move {
+        Move {
+            from,
+            id: id.to_string(),
+            to,
+        }
+    }
+
+    #[test]
+    fn moves_the_element_at_the_position() {
+        let mut queue = queue();
+
+        move_in_queue(&mut queue, request(2, "c", 0)).unwrap();
+
+        assert_eq!(ids(&queue), ["c", "a", "b"]);
+    }
+
+    #[test]
+    fn stale_positions_conflict() {
+        let mut queue = queue();
+
+        let err = move_in_queue(&mut queue, request(1, "c", 0)).unwrap_err();
+
+        assert_eq!(err.status, StatusCode::CONFLICT);
+        assert_eq!(ids(&queue), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn positions_past_the_end_are_rejected() {
+        let err = move_in_queue(&mut queue(), request(3, "d", 0)).unwrap_err();
+
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,151 @@
+//! Inspects and controls a running bot over its control socket.
+
+#[path = "../control/protocol.rs"]
+mod protocol;
+
+use std::{
+    env,
+    io::{BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    process::ExitCode,
+};
+
+use protocol::{Request, Response};
+
+const USAGE: &str = "\
+Usage: luna-ctl [--socket <path>] <command>
+
+Commands:
+  guilds            List guilds with a client state and their voice connections.
+  state <guild id>  Dump a guild's client state.
+  leave <guild id>  Leave a guild's voice channel.
+  reload            Reload the guild settings file. Secrets.toml is only read
+                    at startup, changes to it need a restart.
+  shutdown          Leave every voice channel and stop the bot.
+
+The socket defaults to $LUNA_CONTROL_SOCKET, or luna.sock.";
+
+/// Parses the arguments into the socket path and request.
+fn parse(mut args: impl Iterator<Item = String>) -> Result<(String, Request), String> {
+    let mut socket = env::var("LUNA_CONTROL_SOCKET").ok();
+    let mut command = vec![];
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--socket" | "-s" => socket = Some(args.next().ok_or("--socket needs a path.")?),
+            "--help" | "-h" => return Err(USAGE.to_string()),
+            _ => command.push(arg),
+        }
+    }
+
+    let guild_id = |id: Option<&String>| {
+        id.ok_or("A guild id is required.".to_string())?
+            .parse::<u64>()
+            .map_err(|_| "Guild ids are numbers.".to_string())
+    };
+
+    let request = match command.first().map(String::as_str) {
+        Some("guilds") => Request::Guilds,
+        Some("state") => Request::State {
+            guild_id: guild_id(command.get(1))?,
+        },
+        Some("leave") => Request::Leave {
+            guild_id: guild_id(command.get(1))?,
+        },
+        Some("reload") => Request::Reload,
+        Some("shutdown") => Request::Shutdown,
+        Some(other) => return Err(format!("Unknown command {other}.\n\n{USAGE}")),
+        None => return Err(USAGE.to_string()),
+    };
+
+    Ok((
+        socket.unwrap_or_else(|| protocol::DEFAULT_SOCKET.to_string()),
+        request,
+    ))
+}
+
+fn send(socket: &str, request: &Request) -> Result<Response, String> {
+    let mut stream = UnixStream::connect(socket)
+        .map_err(|err| format!("Could not connect to {socket}. Is the bot running? {err}"))?;
+
+    let mut line = serde_json::to_string(request).map_err(|err| err.to_string())?;
+    line.push('\n');
+    stream
+        .write_all(line.as_bytes())
+        .map_err(|err| err.to_string())?;
+
+    let mut response = String::new();
+    BufReader::new(stream)
+        .read_line(&mut response)
+        .map_err(|err| err.to_string())?;
+
+    serde_json::from_str(&response).map_err(|err| format!("Invalid response. {err}"))
+}
+
+fn main() -> ExitCode {
+    let result = parse(env::args().skip(1)).and_then(|(socket, request)| send(&socket, &request));
+
+    match result {
+        Ok(Response::Guilds { guilds }) if guilds.is_empty() => {
+            println!("The bot is not in any voice channel.")
+        }
+        Ok(Response::Guilds { guilds }) => {
+            for guild in guilds {
+                println!(
+                    "{}  channel {}{}  {}  {} queued{}",
+                    guild.guild_id,
+                    guild
+                        .voice_channel
+                        .map_or("none".to_string(), |channel| channel.to_string()),
+                    if guild.connected {
+                        ""
+                    } else {
+                        " (disconnected)"
+                    },
+                    guild.playback,
+                    guild.queue_length,
+                    guild
+                        .now_playing
+                        .map(|title| format!("  {title}"))
+                        .unwrap_or_default(),
+                );
+            }
+        }
+        Ok(Response::State { state }) => println!("{state}"),
+        Ok(Response::Done { message }) => println!("{message}"),
+        Ok(Response::Error { message }) | Err(message) => {
+            eprintln!("{message}");
+            return ExitCode::FAILURE;
+        }
+    }
+
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(args: &[&str]) -> Result<(String, Request), String> {
+        parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn commands_are_parsed() {
+        assert_eq!(
+            parsed(&["--socket", "/run/luna.sock", "leave", "42"]),
+            Ok((
+                "/run/luna.sock".to_string(),
+                Request::Leave { guild_id: 42 }
+            ))
+        );
+        assert_eq!(
+            parsed(&["-s", "a.sock", "guilds"]).unwrap().1,
+            Request::Guilds
+        );
+        assert!(parsed(&["state"]).is_err());
+        assert!(parsed(&["state", "abc"]).is_err());
+        assert!(parsed(&["restart"]).is_err());
+        assert!(parsed(&[]).is_err());
+    }
+}
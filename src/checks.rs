@@ -1,9 +1,9 @@
 pub(crate) mod author_in_room_check;
-pub(crate) mod bot_in_room_check;
 pub(crate) mod bot_is_playing_check;
+pub(crate) mod dj_check;
 pub(crate) mod shared_room_check;
 
 pub(crate) use author_in_room_check::author_in_room_check;
-pub(crate) use bot_in_room_check::bot_in_room_check;
 pub(crate) use bot_is_playing_check::bot_is_playing_check;
+pub(crate) use dj_check::dj_check;
 pub(crate) use shared_room_check::shared_room_check;
@@ -1,11 +1,14 @@
-use crate::config::{Context, Error};
+use crate::{
+    config::{Context, Error},
+    utils,
+};
 
 /// Check if the command's author is connected to a voice channel.
 pub async fn author_in_room_check(ctx: Context<'_>) -> Result<bool, Error> {
-    let guild = ctx.guild().unwrap();
+    let guild = utils::guild(&ctx)?;
     let author = ctx.author();
 
-    if guild.voice_states.get(&author.id).is_none() {
+    if !guild.voice_states.contains_key(&author.id) {
         ctx.say("Whoops. Looks like you're not in a voice channel.")
             .await?;
         Ok(false)
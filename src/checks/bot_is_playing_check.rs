@@ -1,16 +1,23 @@
-use crate::config::{Context, Error};
+use crate::{
+    config::{Context, Error},
+    utils,
+};
 
 pub async fn bot_is_playing_check(ctx: Context<'_>) -> Result<bool, Error> {
-    let guild_id = ctx.guild_id().unwrap();
-    let client_map = ctx.data().client_state_map.read().await;
+    let guild_id = utils::guild_id(&ctx)?;
+    // this check is run after shared_room_check, so the client state should exist
+    let playback = ctx
+        .data()
+        .client_state_map
+        .with(guild_id.as_u64(), |client_state| client_state.playback)
+        .await?;
 
-    // this should not fail, as this check is run after shared_room_check
-    let client_state = client_map.get(guild_id.as_u64()).unwrap();
-
-    if !client_state.is_playing {
+    // A paused track can still be inspected and sought.
+    let has_track = playback.has_track();
+    if !has_track {
         ctx.say("Sorry but I can't do that. No tracks are currently playing.")
             .await?;
     }
 
-    Ok(client_state.is_playing)
+    Ok(has_track)
 }
@@ -0,0 +1,37 @@
+use poise::serenity_prelude::RoleId;
+
+use crate::{
+    config::{Context, Error},
+    utils,
+};
+
+/// Check if the command's author may control playback. If the guild set a DJ role,
+/// only members with that role or the Manage Server permission may.
+pub async fn dj_check(ctx: Context<'_>) -> Result<bool, Error> {
+    let guild_id = utils::guild_id(&ctx)?;
+
+    let dj_role = match ctx.data().settings.get(guild_id).dj_role {
+        Some(dj_role) => RoleId(dj_role),
+        None => return Ok(true),
+    };
+
+    let allowed = match ctx.author_member().await {
+        Some(member) => {
+            member.roles.contains(&dj_role)
+                || member
+                    .permissions
+                    .or_else(|| member.permissions(ctx).ok())
+                    .is_some_and(|permissions| permissions.manage_guild())
+        }
+        None => false,
+    };
+
+    if !allowed {
+        ctx.say(format!(
+            "Sorry but I can't do that. Controlling playback requires the <@&{dj_role}> role."
+        ))
+        .await?;
+    }
+
+    Ok(allowed)
+}
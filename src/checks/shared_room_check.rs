@@ -1,10 +1,12 @@
-use crate::config::{Context, Error};
+use crate::{
+    config::{Context, Error},
+    utils,
+};
 
 /// Check if the command's author is in the same voice channel as the bot.
 /// Implicitly carries out a check to ensure that the author is connected any voice channel.
 pub async fn shared_room_check(ctx: Context<'_>) -> Result<bool, Error> {
-    let guild = ctx.guild().unwrap();
-    let guild_id = ctx.guild_id().unwrap();
+    let guild = utils::guild(&ctx)?;
 
     let author = ctx.author();
 
@@ -21,20 +23,23 @@ pub async fn shared_room_check(ctx: Context<'_>) -> Result<bool, Error> {
         }
     };
 
-    let client_map = ctx.data().client_state_map.read().await;
-
-    let client_state = match client_map.get(guild_id.as_u64()) {
-        Some(client_state) => client_state,
-        None => {
+    let current_channel = match ctx
+        .data()
+        .client_state_map
+        .with(guild.id.as_u64(), |client_state| {
+            client_state.current_channel
+        })
+        .await
+    {
+        Ok(current_channel) => current_channel,
+        Err(_) => {
             ctx.say("I'm sorry but I can't do that. I am currently not in voice channel.")
                 .await?;
             return Ok(false);
         }
     };
 
-    if client_state.current_channel.is_some()
-        && client_state.current_channel.unwrap() == *auth_vc_id.as_u64()
-    {
+    if current_channel == Some(*auth_vc_id.as_u64()) {
         Ok(true)
     } else {
         ctx.say(
@@ -1,8 +1,15 @@
+#[allow(clippy::module_inception)]
 pub(crate) mod client_state;
 pub(crate) mod client_state_error;
 pub(crate) mod client_state_map;
+pub(crate) mod guild_actor;
+pub(crate) mod playback_state;
+pub(crate) mod state_events;
 
 pub(crate) use client_state::ClientState;
 pub(crate) use client_state::QueueElement;
 pub(crate) use client_state_error::ClientStateError;
 pub(crate) use client_state_map::ClientStateMap;
+pub(crate) use guild_actor::GuildActor;
+pub(crate) use playback_state::PlaybackState;
+pub(crate) use state_events::{EventBus, GuildEvent, StateEvent};
@@ -1,11 +1,53 @@
+use serde::{Deserialize, Serialize};
 use songbird::tracks::TrackHandle;
+use std::{sync::Arc, time::Duration};
+use tokio::task::AbortHandle;
+
+use crate::{
+    client_state::{ClientStateError, PlaybackState},
+    utils::chapters::Chapter,
+};
 
 #[derive(Default, Debug, Clone)]
 pub struct ClientState {
-    pub(crate) is_playing: bool,
+    pub(crate) playback: PlaybackState,
     pub(crate) current_channel: Option<u64>,
+    pub(crate) text_channel: Option<u64>,
     pub(crate) current_track: Option<TrackHandle>,
+    /// The queue element `current_track` was created from.
+    pub(crate) now_playing: Option<QueueElement>,
     pub(crate) song_queue: Option<Vec<QueueElement>>,
+    /// Playlist imports still streaming into the queue.
+    pub(crate) playlist_imports: Vec<Arc<AbortHandle>>,
+    /// Counts the loads begun, so a load only starts its track if it is still the latest.
+    pub(crate) load: u64,
+}
+
+impl ClientState {
+    /// Moves the player to the given state, if that is a valid transition.
+    pub(crate) fn transition(&mut self, next: PlaybackState) -> Result<(), ClientStateError> {
+        self.playback = self.playback.transition(next)?;
+        if next == PlaybackState::Loading {
+            self.load += 1;
+        }
+        Ok(())
+    }
+
+    /// Moves the player from loading to playing, unless playback was stopped
+    /// or another load began since the load numbered `load`.
+    pub(crate) fn finish_load(&mut self, load: u64) -> Result<(), ClientStateError> {
+        if self.load != load {
+            return Err(ClientStateError::SupersededLoad);
+        }
+        self.transition(PlaybackState::Playing)
+    }
+
+    /// Cancels playlist imports that are still streaming into the queue.
+    pub(crate) fn cancel_playlist_imports(&mut self) {
+        for import in self.playlist_imports.drain(..) {
+            import.abort();
+        }
+    }
 }
 
 impl PartialEq for ClientState {
@@ -16,10 +58,55 @@ impl PartialEq for ClientState {
 
 impl Eq for ClientState {}
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct QueueElement {
     pub(crate) title: String,
     pub(crate) channel_name: String,
     pub(crate) url: String,
     pub(crate) id: String,
+    /// The offset playback starts from.
+    pub(crate) start: Option<Duration>,
+    /// The length of the video, if known at resolution time.
+    #[serde(default)]
+    pub(crate) duration: Option<Duration>,
+    /// Whether the element is a livestream that is currently broadcasting.
+    #[serde(default)]
+    pub(crate) live: bool,
+    /// The chapters listed in the video description. `None` until the description is fetched.
+    #[serde(default)]
+    pub(crate) chapters: Option<Vec<Chapter>>,
+}
+
+impl QueueElement {
+    /// The element as JSON for webhooks and the API, with the duration in seconds.
+    pub(crate) fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "id": self.id,
+            "title": self.title,
+            "channel": self.channel_name,
+            "url": self.url,
+            "duration": self.duration.map(|duration| duration.as_secs()),
+            "live": self.live,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn superseded_loads_do_not_start() {
+        let mut client_state = ClientState::default();
+
+        client_state.transition(PlaybackState::Loading).unwrap();
+        let stopped = client_state.load;
+        // `/stop`, then `/play` while the first track still loads.
+        client_state.transition(PlaybackState::Idle).unwrap();
+        client_state.transition(PlaybackState::Loading).unwrap();
+
+        assert!(client_state.finish_load(stopped).is_err());
+        assert!(client_state.finish_load(client_state.load).is_ok());
+        assert_eq!(client_state.playback, PlaybackState::Playing);
+    }
 }
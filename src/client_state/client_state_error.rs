@@ -1,13 +1,29 @@
 use std::fmt::{Display, Formatter};
 
+use crate::client_state::PlaybackState;
+
 #[derive(Debug)]
 pub enum ClientStateError {
     ReservedClientID,
     NonExistentClientID,
+    /// The player cannot move from the first state to the second.
+    InvalidTransition(PlaybackState, PlaybackState),
+    /// A track finished loading after another load began, e.g. after `/stop` and `/play`.
+    SupersededLoad,
+    /// A job on the guild's state panicked. The state is kept as the job left it.
+    JobPanicked,
 }
 
 impl Display for ClientStateError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "(ClientStateError::{:?}", self)
+        match self {
+            ClientStateError::InvalidTransition(from, to) => {
+                write!(
+                    f,
+                    "(ClientStateError::InvalidTransition {from:?} -> {to:?})"
+                )
+            }
+            _ => write!(f, "(ClientStateError::{:?}", self),
+        }
     }
 }
@@ -1,61 +1,79 @@
-use std::collections::HashMap;
+use poise::serenity_prelude::GuildId;
+use std::{collections::HashMap, sync::Mutex};
 
-use crate::client_state::{ClientState, ClientStateError};
+use crate::client_state::{ClientState, ClientStateError, EventBus, GuildActor, StateEvent};
 
-#[derive(Clone)]
+/// The client states of all guilds, each owned by its own [`GuildActor`].
+/// The map itself is only locked to look up, add or remove actors.
 pub struct ClientStateMap {
-    map: HashMap<u64, ClientState>,
+    map: Mutex<HashMap<u64, GuildActor>>,
+    events: EventBus,
 }
 
 impl ClientStateMap {
-    pub fn new() -> Self {
+    pub(crate) fn new(events: EventBus) -> Self {
         ClientStateMap {
-            map: (HashMap::new()),
+            map: Mutex::new(HashMap::new()),
+            events,
         }
     }
 
-    pub fn get(&self, id: &u64) -> Option<&ClientState> {
-        self.map.get(id)
+    fn actor(&self, id: &u64) -> Option<GuildActor> {
+        self.map.lock().unwrap().get(id).cloned()
     }
 
-    pub fn contains_key(&self, id: &u64) -> bool {
-        self.map.contains_key(id)
+    /// Returns a snapshot of the guild's client state.
+    pub async fn get(&self, id: &u64) -> Option<ClientState> {
+        self.with(id, |client_state| client_state.clone())
+            .await
+            .ok()
     }
 
-    pub fn insert(
-        &mut self,
+    /// Runs `f` on the guild's client state inside its actor, returning the result.
+    pub async fn with<R: Send + 'static>(
+        &self,
         id: &u64,
-        client_state: &mut ClientState,
-    ) -> Result<(), ClientStateError> {
-        if self.map.contains_key(id) {
-            return Err(ClientStateError::ReservedClientID);
-        }
+        f: impl FnOnce(&mut ClientState) -> R + Send + 'static,
+    ) -> Result<R, ClientStateError> {
+        self.actor(id)
+            .ok_or(ClientStateError::NonExistentClientID)?
+            .run(f)
+            .await
+    }
 
-        self.map.insert(id.clone(), client_state.to_owned());
-        Ok(())
+    pub fn contains_key(&self, id: &u64) -> bool {
+        self.map.lock().unwrap().contains_key(id)
     }
 
-    pub fn update(
-        &mut self,
-        id: &u64,
-        client_state: &mut ClientState,
-    ) -> Result<(), ClientStateError> {
-        match self.map.contains_key(id) {
-            true => {
-                self.map.insert(id.clone(), client_state.to_owned());
-                Ok(())
-            }
-            false => Err(ClientStateError::NonExistentClientID),
+    /// The ids of all guilds with a client state.
+    pub fn ids(&self) -> Vec<u64> {
+        self.map.lock().unwrap().keys().copied().collect()
+    }
+
+    pub fn insert(&self, id: &u64, client_state: ClientState) -> Result<(), ClientStateError> {
+        let mut map = self.map.lock().unwrap();
+
+        if map.contains_key(id) {
+            return Err(ClientStateError::ReservedClientID);
         }
+
+        let channel = client_state.current_channel;
+        map.insert(
+            *id,
+            GuildActor::spawn(GuildId(*id), client_state, self.events.clone()),
+        );
+        self.events
+            .publish(GuildId(*id), StateEvent::Joined { channel });
+        Ok(())
     }
 
-    pub fn remove(&mut self, id: &u64) -> Result<(), ClientStateError> {
-        match self.map.contains_key(id) {
-            true => {
-                self.map.remove(id);
+    pub fn remove(&self, id: &u64) -> Result<(), ClientStateError> {
+        match self.map.lock().unwrap().remove(id) {
+            Some(_) => {
+                self.events.publish(GuildId(*id), StateEvent::Left);
                 Ok(())
             }
-            false => Err(ClientStateError::NonExistentClientID),
+            None => Err(ClientStateError::NonExistentClientID),
         }
     }
 }
@@ -0,0 +1,135 @@
+use log::error;
+use poise::serenity_prelude::GuildId;
+use std::panic::{self, AssertUnwindSafe};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::client_state::{
+    state_events::{EventBus, Snapshot},
+    ClientState, ClientStateError,
+};
+
+type Job = Box<dyn FnOnce(&mut ClientState) + Send>;
+
+/// A task owning one guild's client state. Jobs sent to it run one at a time, so each
+/// sees the changes of the previous ones and no guild waits on another.
+///
+/// Jobs run on the actor's task and must not block. Slow work such as resolving sources
+/// happens before or after, in the caller. The changes each job makes are published as events.
+#[derive(Clone)]
+pub(crate) struct GuildActor {
+    jobs: mpsc::UnboundedSender<Job>,
+}
+
+impl GuildActor {
+    /// Spawns an actor owning the given state. It runs until every handle to it is dropped.
+    pub(crate) fn spawn(
+        guild_id: GuildId,
+        mut client_state: ClientState,
+        events: EventBus,
+    ) -> Self {
+        let (jobs, mut receiver) = mpsc::unbounded_channel::<Job>();
+
+        tokio::spawn(async move {
+            while let Some(job) = receiver.recv().await {
+                let before = Snapshot::of(&client_state);
+                job(&mut client_state);
+
+                for event in before.changes(&Snapshot::of(&client_state)) {
+                    events.publish(guild_id, event);
+                }
+            }
+        });
+
+        GuildActor { jobs }
+    }
+
+    /// Runs `job` on the guild's state and returns its result. A panicking job is reported
+    /// as an error, and the actor keeps serving later jobs.
+    pub(crate) async fn run<R: Send + 'static>(
+        &self,
+        job: impl FnOnce(&mut ClientState) -> R + Send + 'static,
+    ) -> Result<R, ClientStateError> {
+        let (result, receiver) = oneshot::channel();
+
+        self.jobs
+            .send(Box::new(move |client_state| {
+                let outcome =
+                    panic::catch_unwind(AssertUnwindSafe(|| job(client_state))).map_err(|_| {
+                        error!("A job on a guild's client state panicked.");
+                        ClientStateError::JobPanicked
+                    });
+                let _ = result.send(outcome);
+            }))
+            .map_err(|_| ClientStateError::NonExistentClientID)?;
+
+        receiver
+            .await
+            .map_err(|_| ClientStateError::NonExistentClientID)?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client_state::StateEvent;
+
+    #[tokio::test]
+    async fn concurrent_jobs_see_each_others_changes() {
+        let actor = GuildActor::spawn(GuildId(1), ClientState::default(), EventBus::new());
+
+        futures::future::join_all((0..100).map(|_| {
+            actor.run(|client_state| {
+                client_state
+                    .song_queue
+                    .get_or_insert_with(Vec::new)
+                    .push(Default::default());
+            })
+        }))
+        .await;
+
+        let queued = actor
+            .run(|client_state| client_state.song_queue.as_ref().map_or(0, Vec::len))
+            .await
+            .unwrap();
+        assert_eq!(queued, 100);
+    }
+
+    #[tokio::test]
+    async fn changes_are_published() {
+        let events = EventBus::new();
+        let mut subscriber = events.subscribe();
+        let actor = GuildActor::spawn(GuildId(1), ClientState::default(), events);
+
+        actor
+            .run(|client_state| {
+                client_state.song_queue = Some(vec![Default::default()]);
+            })
+            .await
+            .unwrap();
+
+        let published = subscriber.recv().await.unwrap();
+        assert_eq!(published.guild_id, GuildId(1));
+        assert_eq!(published.event, StateEvent::QueueChanged { length: 1 });
+    }
+
+    #[tokio::test]
+    async fn actors_survive_panicking_jobs() {
+        let actor = GuildActor::spawn(GuildId(1), ClientState::default(), EventBus::new());
+
+        let panicked = actor.run(|_| panic!("job failed")).await;
+        let next = actor.run(|_| 42).await;
+
+        assert!(matches!(panicked, Err(ClientStateError::JobPanicked)));
+        assert_eq!(next.unwrap(), 42);
+    }
+
+    #[tokio::test]
+    async fn dropped_actors_reject_jobs() {
+        let actor = GuildActor::spawn(GuildId(1), ClientState::default(), EventBus::new());
+        let (jobs, _) = mpsc::unbounded_channel();
+        let closed = GuildActor { jobs };
+
+        assert!(actor.run(|_| ()).await.is_ok());
+        assert!(closed.run(|_| ()).await.is_err());
+    }
+}
@@ -0,0 +1,98 @@
+use serde::Serialize;
+
+use crate::client_state::ClientStateError;
+
+/// What a guild's player is doing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum PlaybackState {
+    /// Nothing is loaded.
+    #[default]
+    Idle,
+    /// The next element of the queue is being resolved.
+    Loading,
+    Playing,
+    Paused,
+    /// The current track was stopped to skip it, and its end has not been handled yet.
+    Stopping,
+}
+
+impl PlaybackState {
+    /// Whether the player may move from this state to `next`.
+    pub(crate) fn can_transition_to(self, next: PlaybackState) -> bool {
+        use PlaybackState::*;
+
+        matches!(
+            (self, next),
+            // Leaving, disconnecting and `/stop` reset the player from any state.
+            (_, Idle)
+                | (Idle, Loading)
+                // An element that fails to load is skipped for the next one.
+                | (Loading, Loading | Playing)
+                | (Playing, Paused | Stopping | Loading)
+                | (Paused, Playing | Stopping)
+                | (Stopping, Loading)
+        )
+    }
+
+    /// Returns `next` if the player may move to it.
+    pub(crate) fn transition(self, next: PlaybackState) -> Result<PlaybackState, ClientStateError> {
+        if self.can_transition_to(next) {
+            Ok(next)
+        } else {
+            Err(ClientStateError::InvalidTransition(self, next))
+        }
+    }
+
+    /// Whether a track is loaded or about to be, so that new requests are queued behind it.
+    pub(crate) fn is_active(self) -> bool {
+        self != PlaybackState::Idle
+    }
+
+    /// Whether there is a track that can be paused, sought or inspected.
+    pub(crate) fn has_track(self) -> bool {
+        matches!(self, PlaybackState::Playing | PlaybackState::Paused)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PlaybackState::*;
+
+    #[test]
+    fn tracks_are_paused_and_resumed() {
+        assert_eq!(Playing.transition(Paused).unwrap(), Paused);
+        assert_eq!(Paused.transition(Playing).unwrap(), Playing);
+        assert!(Paused.transition(Paused).is_err());
+        assert!(Idle.transition(Paused).is_err());
+        assert!(Loading.transition(Paused).is_err());
+        assert!(Stopping.transition(Playing).is_err());
+    }
+
+    #[test]
+    fn skipping_loads_the_next_track() {
+        let state = Paused
+            .transition(Stopping)
+            .and_then(|state| state.transition(Loading))
+            .and_then(|state| state.transition(Loading))
+            .and_then(|state| state.transition(Playing));
+
+        assert_eq!(state.unwrap(), Playing);
+    }
+
+    #[test]
+    fn a_track_stopped_while_loading_does_not_start() {
+        let state = Loading
+            .transition(Idle)
+            .and_then(|state| state.transition(Playing));
+
+        assert!(state.is_err());
+    }
+
+    #[test]
+    fn every_state_can_be_reset() {
+        for state in [Idle, Loading, Playing, Paused, Stopping] {
+            assert!(state.can_transition_to(Idle));
+        }
+    }
+}
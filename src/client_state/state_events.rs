@@ -0,0 +1,210 @@
+use log::debug;
+use poise::serenity_prelude::GuildId;
+use songbird::tracks::TrackHandle;
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::client_state::{ClientState, PlaybackState, QueueElement};
+
+/// How many events a subscriber may fall behind before it misses some.
+const CAPACITY: usize = 256;
+
+/// A change to a guild's client state.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum StateEvent {
+    /// The bot joined a voice channel.
+    Joined {
+        channel: Option<u64>,
+    },
+    /// The bot left the voice channel and the guild's state was dropped.
+    Left,
+    TrackStarted(QueueElement),
+    /// The track stopped, ran out or was replaced.
+    TrackEnded(QueueElement),
+    Paused,
+    Resumed,
+    /// Playback stopped with nothing left to play.
+    Idle,
+    /// Elements were added to, removed from or reordered in the queue.
+    QueueChanged {
+        length: usize,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct GuildEvent {
+    pub(crate) guild_id: GuildId,
+    pub(crate) event: StateEvent,
+}
+
+/// Publishes the state changes of all guilds to any number of subscribers.
+#[derive(Clone)]
+pub(crate) struct EventBus {
+    sender: broadcast::Sender<GuildEvent>,
+}
+
+impl EventBus {
+    pub(crate) fn new() -> Self {
+        EventBus {
+            sender: broadcast::channel(CAPACITY).0,
+        }
+    }
+
+    pub(crate) fn publish(&self, guild_id: GuildId, event: StateEvent) {
+        // Sending only fails when nobody is subscribed.
+        let _ = self.sender.send(GuildEvent { guild_id, event });
+    }
+
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<GuildEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Logs every event at debug level.
+    pub(crate) fn log(&self) {
+        let mut events = self.subscribe();
+
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(GuildEvent { guild_id, event }) => debug!("gid {guild_id}: {event:?}"),
+                    Err(RecvError::Lagged(missed)) => debug!("Missed {missed} state events."),
+                    Err(RecvError::Closed) => return,
+                }
+            }
+        });
+    }
+}
+
+/// The parts of a client state that events are derived from.
+pub(crate) struct Snapshot {
+    playback: PlaybackState,
+    current_track: Option<TrackHandle>,
+    now_playing: Option<QueueElement>,
+    queue_length: usize,
+    queue_hash: u64,
+}
+
+impl Snapshot {
+    pub(crate) fn of(client_state: &ClientState) -> Self {
+        let queue = client_state.song_queue.as_deref().unwrap_or_default();
+        let mut hasher = DefaultHasher::new();
+        for element in queue {
+            element.id.hash(&mut hasher);
+        }
+
+        Snapshot {
+            playback: client_state.playback,
+            current_track: client_state.current_track.clone(),
+            now_playing: client_state.now_playing.clone(),
+            queue_length: queue.len(),
+            queue_hash: hasher.finish(),
+        }
+    }
+
+    /// Lists the events that lead from this snapshot to `after`.
+    pub(crate) fn changes(&self, after: &Snapshot) -> Vec<StateEvent> {
+        let mut events = vec![];
+
+        let uuid = |snapshot: &Snapshot| snapshot.current_track.as_ref().map(TrackHandle::uuid);
+        if uuid(self) != uuid(after) {
+            if let (Some(_), Some(element)) = (&self.current_track, &self.now_playing) {
+                events.push(StateEvent::TrackEnded(element.clone()));
+            }
+            if let (Some(_), Some(element)) = (&after.current_track, &after.now_playing) {
+                events.push(StateEvent::TrackStarted(element.clone()));
+            }
+        }
+
+        match (self.playback, after.playback) {
+            (PlaybackState::Playing, PlaybackState::Paused) => events.push(StateEvent::Paused),
+            (PlaybackState::Paused, PlaybackState::Playing) => events.push(StateEvent::Resumed),
+            (before, PlaybackState::Idle) if before != PlaybackState::Idle => {
+                events.push(StateEvent::Idle)
+            }
+            _ => {}
+        }
+
+        if (self.queue_length, self.queue_hash) != (after.queue_length, after.queue_hash) {
+            events.push(StateEvent::QueueChanged {
+                length: after.queue_length,
+            });
+        }
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn element(id: &str) -> QueueElement {
+        QueueElement {
+            id: id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn changes(before: &ClientState, after: &ClientState) -> Vec<StateEvent> {
+        Snapshot::of(before).changes(&Snapshot::of(after))
+    }
+
+    #[test]
+    fn queue_edits_are_reported() {
+        let before = ClientState {
+            song_queue: Some(vec![element("a"), element("b")]),
+            ..Default::default()
+        };
+        let mut reversed = before.clone();
+        reversed.song_queue.as_mut().unwrap().reverse();
+        let mut cleared = before.clone();
+        cleared.song_queue = None;
+
+        assert_eq!(changes(&before, &before), vec![]);
+        assert_eq!(
+            changes(&before, &reversed),
+            vec![StateEvent::QueueChanged { length: 2 }]
+        );
+        assert_eq!(
+            changes(&before, &cleared),
+            vec![StateEvent::QueueChanged { length: 0 }]
+        );
+    }
+
+    #[test]
+    fn playback_transitions_are_reported() {
+        let state = |playback| ClientState {
+            playback,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            changes(
+                &state(PlaybackState::Playing),
+                &state(PlaybackState::Paused)
+            ),
+            vec![StateEvent::Paused]
+        );
+        assert_eq!(
+            changes(
+                &state(PlaybackState::Paused),
+                &state(PlaybackState::Playing)
+            ),
+            vec![StateEvent::Resumed]
+        );
+        assert_eq!(
+            changes(&state(PlaybackState::Loading), &state(PlaybackState::Idle)),
+            vec![StateEvent::Idle]
+        );
+        assert_eq!(
+            changes(
+                &state(PlaybackState::Playing),
+                &state(PlaybackState::Loading)
+            ),
+            vec![]
+        );
+    }
+}
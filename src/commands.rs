@@ -1,5 +1,9 @@
 pub(crate) mod leave;
 pub(crate) mod play;
 pub(crate) mod queue;
+pub(crate) mod quota;
+pub(crate) mod segments;
+pub(crate) mod settings;
 pub(crate) mod stop;
 pub(crate) mod track;
+pub(crate) mod webhooks;
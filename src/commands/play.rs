@@ -1,18 +1,28 @@
 use futures::join;
 use log::{debug, error, info, warn, Level};
+use rand::seq::SliceRandom;
 
-use serenity::model::id::GuildId;
-use songbird::{self, Event, TrackEvent};
-
-use url;
+use serenity::http::Http;
+use serenity::model::id::{ChannelId, GuildId};
+use serenity::prelude::Mutex;
+use songbird::{input::Input, tracks::create_player, Songbird};
+use std::{
+    collections::VecDeque,
+    sync::{atomic::AtomicUsize, Arc},
+};
 
 use crate::{
     checks::author_in_room_check,
-    client_state::{ClientState, QueueElement},
-    config::{Context, Error},
+    client_state::{ClientStateError, PlaybackState, QueueElement},
+    config::{Context, Error, ServerState},
     handlers::QueueHandler,
-    utils,
-    utils::{source_retriever, source_retriever::SourceType},
+    metrics, utils,
+    utils::{
+        announcer::Announcer,
+        playlist_import::PlaylistImport,
+        source_retriever,
+        source_retriever::{youtube::YouTubeClient, Playlist, PlaylistKind, SourceType},
+    },
 };
 
 #[derive(Debug)]
@@ -24,168 +34,335 @@ pub(crate) enum PlayStatus {
 
 /// Attempts to retrieve a video from YouTube using a given URL or search query.
 /// If successful, the function returns the video's audio and its metadata.
-async fn source_input(context: &Context<'_>, query: String) -> Option<SourceType> {
-    let server_state = context.data();
-
+pub(crate) async fn source_input(
+    youtube_client: &dyn YouTubeClient,
+    query: String,
+) -> Result<SourceType, Error> {
     // check that domain is a supported platform
     // if not a url, treat as search query instead
-    match url::Url::parse(query.as_str()) {
+    let source = match url::Url::parse(query.as_str()) {
         Ok(source) => {
-            let domain = source.domain().unwrap().to_lowercase();
+            let domain = source.domain().unwrap_or_default().to_lowercase();
             match domain {
-                _ if domain.contains("youtube.com") => {
-                    source_retriever::youtube::process(&source, server_state).await
+                _ if source_retriever::youtube::link::is_youtube_domain(&domain) => {
+                    source_retriever::youtube::process(&source, youtube_client).await?
+                }
+                _ if domain.contains("soundcloud.com") => {
+                    return Err(Error::SourceResolution(
+                        "SoundCloud links are not supported yet.".to_string(),
+                    ))
                 }
-                _ if domain.contains("soundcloud.com") => todo!(),
                 _ => None,
             }
         }
         // Search term, handle with youtube.
-        Err(_) => source_retriever::youtube::handle_search_query(query, server_state).await,
-    }
+        Err(_) => {
+            source_retriever::youtube::handle_search_query(query.clone(), youtube_client).await?
+        }
+    };
+
+    source.ok_or_else(|| {
+        warn!("Could not find the requested resource for `{query:?}`");
+        Error::SourceResolution(format!("Could not find the requested resource: {query}"))
+    })
 }
 
 /// This function handles playing or enqueuing the requested video.
 /// This includes updating the client state data and creating an
 /// event listnener to play queued videos.
-async fn handle_play(
+///
+/// Announcements go to `text_channel`, or where they went before if it is `None`.
+pub(crate) async fn handle_play(
     guild_id: &GuildId,
-    ctx: &Context<'_>,
+    data: &ServerState,
+    http: &Arc<Http>,
+    manager: &Songbird,
+    text_channel: Option<u64>,
     input: SourceType,
 ) -> Result<PlayStatus, Error> {
-    let manager = songbird::get(ctx.serenity_context()).await.unwrap().clone();
-    let client_map = &mut ctx.data().client_state_map.write().await;
-
-    let client_state = match client_map.get(guild_id.as_u64()) {
-        Some(client_state) => client_state,
-        None => {
-            error!(
-                "ClientState for gid: {} does not exist.",
-                guild_id.to_string()
-            );
-            return Err(Error::from(format!(
-                "ClientState for gid: {} does not exist.",
-                guild_id.to_string()
-            )));
-        }
+    // Anything loaded, even if paused or about to be replaced, keeps its place ahead of the request.
+    // Otherwise the first element is claimed for loading, so concurrent requests queue behind it.
+    let requested = input.clone();
+    let first = data
+        .client_state_map
+        .with(guild_id.as_u64(), move |client_state| -> Result<_, Error> {
+            if text_channel.is_some() {
+                client_state.text_channel = text_channel;
+            }
+
+            let items = match requested {
+                SourceType::Single(v) => vec![v],
+                SourceType::Playlist(p) => p.page.items,
+            };
+            let queue = client_state.song_queue.get_or_insert_with(Vec::new);
+
+            if client_state.playback.is_active() {
+                queue.extend(items);
+                return Ok(None);
+            }
+
+            let mut items = items.into_iter();
+            let first = items
+                .next()
+                .ok_or_else(|| Error::SourceResolution("The playlist is empty.".to_string()))?;
+            // The rest of a playlist goes ahead of what was already queued.
+            queue.splice(0..0, items);
+            client_state.transition(PlaybackState::Loading)?;
+
+            Ok(Some((first, client_state.load)))
+        })
+        .await??;
+
+    let (first, load) = match first {
+        Some(first) => first,
+        None => return Ok(PlayStatus::Queued(input)),
     };
 
-    let (play_status, mut updated_state) = if client_state.is_playing {
-        let updated_queue = client_state
-            .song_queue
-            .clone()
-            .unwrap()
-            .into_iter()
-            .chain(match input.clone() {
-                SourceType::Single(v) => vec![v].into_iter(),
-                SourceType::Playlist((_, p)) => p.into_iter(),
+    if let Err(err) = start(guild_id, data, http, manager, &first, load).await {
+        let _ = data
+            .client_state_map
+            .with(guild_id.as_u64(), move |client_state| {
+                if client_state.playback == PlaybackState::Loading && client_state.load == load {
+                    client_state.playback = PlaybackState::Idle;
+                }
             })
-            .collect();
+            .await;
 
-        (
-            PlayStatus::Queued(input),
-            ClientState {
-                song_queue: Some(updated_queue),
-                ..client_state.clone()
-            },
+        return Err(err);
+    }
+
+    Ok(match input {
+        SourceType::Single(v) => PlayStatus::Playing(v),
+        SourceType::Playlist(p) => PlayStatus::PlayAndQueued(p.page.items),
+    })
+}
+
+/// Resolves and starts the element claimed for loading by `handle_play` as load number `load`,
+/// and registers the queue handler that plays the elements after it.
+async fn start(
+    guild_id: &GuildId,
+    data: &ServerState,
+    http: &Arc<Http>,
+    manager: &Songbird,
+    first: &QueueElement,
+    load: u64,
+) -> Result<(), Error> {
+    let handler_lock = manager.get_or_insert(*guild_id.as_u64());
+
+    debug!("Initializing track.");
+    let t: Input = metrics::YTDLP_SECONDS
+        .time(
+            &["audio"],
+            songbird::input::Restartable::ytdl(first.url.clone(), true),
         )
-    } else {
-        let (play_status, updated_queue) = match &input {
-            SourceType::Single(v) => (
-                PlayStatus::Playing(v.to_owned()),
-                client_state.song_queue.to_owned(),
-            ),
-            SourceType::Playlist((_, p)) => {
-                if client_state.song_queue.is_some() {
-                    (
-                        PlayStatus::PlayAndQueued(p.clone()),
-                        Some(p.clone().into_iter().skip(1).collect()),
-                    )
-                } else {
-                    (
-                        PlayStatus::PlayAndQueued(p.clone()),
-                        Some(
-                            client_state
-                                .song_queue
-                                .clone()
-                                .unwrap()
-                                .into_iter()
-                                .chain(p.clone().into_iter().skip(1))
-                                .collect(),
-                        ),
-                    )
-                }
-            }
-        };
-
-        let handler_lock = manager.get_or_insert(*guild_id.as_u64());
-        let mut handler = handler_lock.lock().await;
-
-        let t_handle = match &input {
-            SourceType::Single(v) => {
-                debug!("Initializing track.");
-                let t = songbird::input::Restartable::ytdl(v.url.clone(), true).await?;
-                debug!("Track initialization complete.");
-                //handler.play_source(songbird::ytdl(v.url.clone()).await.unwrap())
-                handler.play_source(t.into())
-            }
-            SourceType::Playlist((_, p)) => {
-                debug!("Initializing track.");
-                let t = songbird::input::Restartable::ytdl(p.first().unwrap().url.clone(), true)
-                    .await?;
-                debug!("Track initialization complete.");
-                //let t = songbird::input::cached::Memory::new(songbird::ytdl(p.first().unwrap().url.clone()).await?).unwrap();
-                // songbird::ytdl(p.first().unwrap().url.clone())
-                //     .await
-                //     .unwrap(),
-                handler.play_source(t.into())
-            }
-        };
-        debug!("Play called");
-
-        if log::log_enabled!(Level::Debug) {
-            let metadata = t_handle.metadata().clone();
-            debug!(
-                "Adding event handler for {} - {}.",
-                metadata.title.map_or_else(|| "None".into(), |title| title),
-                metadata
-                    .channel
-                    .map_or_else(|| "None".into(), |channel| channel),
-            );
+        .await?
+        .into();
+    debug!("Track initialization complete.");
+
+    let settings = data.settings.get(*guild_id);
+    settings
+        .check_length(t.metadata.duration)
+        .map_err(Error::UserInput)?;
+
+    let (mut track, t_handle) = create_player(t);
+    track.set_volume(settings.gain());
+    if let Some(start) = first.start {
+        track.seek_time(start)?;
+    }
+
+    // Playback may have been stopped while the track was loading.
+    let (current_track, now_playing) = (t_handle.clone(), first.clone());
+    data.client_state_map
+        .with(guild_id.as_u64(), move |client_state| {
+            client_state.finish_load(load)?;
+            client_state.current_track = Some(current_track);
+            client_state.now_playing = Some(now_playing);
+            Ok::<_, ClientStateError>(())
+        })
+        .await?
+        .map_err(|_| {
+            Error::UserInput(format!(
+                "Playback was stopped before {} started.",
+                utils::decode_html_encoded_string(&first.title)
+            ))
+        })?;
+
+    handler_lock.lock().await.play(track);
+    metrics::TRACKS_PLAYED.inc(&[]);
+    debug!("Play called");
+
+    if log::log_enabled!(Level::Debug) {
+        let metadata = t_handle.metadata().clone();
+        debug!(
+            "Adding event handler for {} - {}.",
+            metadata.title.unwrap_or_else(|| "None".into()),
+            metadata.channel.unwrap_or_else(|| "None".into()),
+        );
+    }
+
+    QueueHandler {
+        client_state_map: data.client_state_map.clone(),
+        guild_id: *guild_id,
+        handler: handler_lock.clone(),
+        announcer: Announcer {
+            guild_id: *guild_id,
+            http: http.clone(),
+            client_state_map: data.client_state_map.clone(),
+            settings: data.settings.clone(),
+        },
+        preloaded: Arc::new(Mutex::new(None)),
+        failures: Arc::new(AtomicUsize::new(0)),
+        segment_provider: data.segment_provider.clone(),
+        settings: data.settings.clone(),
+        youtube_client: data.youtube_client.clone(),
+        history: Arc::new(std::sync::Mutex::new(VecDeque::new())),
+    }
+    .attach(&t_handle, first);
+
+    Ok(())
+}
+
+/// The number of uploads queued from a channel, unless a range is given.
+const CHANNEL_LATEST: usize = 25;
+
+/// The part of a playlist to queue, and in which order.
+#[derive(Debug, Default)]
+pub(crate) struct PlaylistOptions {
+    /// The position to start from. Defaults to the video the link points to, or the first.
+    pub(crate) from: Option<usize>,
+    /// The position to stop at.
+    pub(crate) to: Option<usize>,
+    pub(crate) shuffle: bool,
+}
+
+/// Applies the requested range and shuffling to a playlist's first page.
+/// Returns the background import for the following pages, if any are needed.
+/// Its progress is posted to `channel_id`, if given.
+pub(crate) async fn prepare_playlist(
+    data: &ServerState,
+    http: &Arc<Http>,
+    channel_id: Option<ChannelId>,
+    guild_id: GuildId,
+    playlist: &mut Playlist,
+    PlaylistOptions { from, to, shuffle }: PlaylistOptions,
+    room: Option<usize>,
+) -> Result<Option<PlaylistImport>, Error> {
+    let from = from.or(playlist.index).unwrap_or(1);
+    let to = match (to, playlist.kind) {
+        (None, PlaylistKind::Uploads) => Some(from + CHANNEL_LATEST - 1),
+        (to, _) => to,
+    };
+    let range_len = match to {
+        Some(to) if to < from => {
+            return Err(Error::UserInput(format!(
+                "The end of the range ({to}) comes before its start ({from})."
+            )))
         }
+        Some(to) => to - from + 1,
+        None => usize::MAX,
+    };
+    let limit = range_len
+        .min(data.max_playlist_import)
+        .min(room.unwrap_or(usize::MAX));
 
-        t_handle
-            .add_event(
-                Event::Track(TrackEvent::End),
-                QueueHandler {
-                    client_state_map: ctx.data().client_state_map.clone(),
-                    guild_id: guild_id.clone(),
-                    handler: handler_lock.clone(),
-                },
-            )
-            .or_else(|err| {
-                error!("Failed to add event listener for track end. Error: {err:?}");
-                Err(err)
-            })?;
-
-        (
-            play_status,
-            ClientState {
-                is_playing: true,
-                current_track: Some(t_handle),
-                song_queue: updated_queue,
-                ..client_state.clone()
-            },
+    if from > 1 {
+        let page = std::mem::take(&mut playlist.page);
+        playlist.page = source_retriever::youtube::skip_to(
+            &playlist.metadata.id,
+            page,
+            from,
+            data.youtube_client.as_ref(),
         )
-    };
+        .await?;
+    }
 
-    client_map
-        .update(guild_id.as_u64(), &mut updated_state)
-        .unwrap_or_else(|err| {
-            error!("Could not update the client state for {guild_id}. Error: {err:?}");
-        });
+    let page = &mut playlist.page;
+    if page.items.is_empty() {
+        return Err(Error::UserInput(format!(
+            "The playlist has fewer than {from} videos."
+        )));
+    }
+    let position = from - 1 + page.items.len();
+
+    let settings = data.settings.get(guild_id);
+    page.items
+        .retain(|item| settings.check_length(item.duration).is_ok());
+    if page.items.is_empty() {
+        return Err(Error::UserInput(
+            "The playlist's videos are longer than the maximum track length.".to_string(),
+        ));
+    }
+
+    page.items.truncate(limit);
+    if shuffle {
+        page.items.shuffle(&mut rand::thread_rng());
+    }
+
+    if page.items.len() >= limit {
+        return Ok(None);
+    }
 
-    Ok(play_status)
+    Ok(page
+        .next_page_token
+        .take()
+        .map(|next_page_token| PlaylistImport {
+            guild_id,
+            playlist: playlist.metadata.clone(),
+            next_page_token,
+            loaded: page.items.len(),
+            position,
+            limit,
+            shuffle_with: shuffle.then(|| page.items.iter().map(|item| item.id.clone()).collect()),
+            youtube_client: data.youtube_client.clone(),
+            client_state_map: data.client_state_map.clone(),
+            settings: data.settings.clone(),
+            http: http.clone(),
+            channel_id,
+        }))
+}
+
+/// Returns how many more tracks the guild's queue takes, counting the track that starts
+/// playing right away if nothing is playing. `None` if the queue size is unlimited.
+pub(crate) async fn queue_room(data: &ServerState, guild_id: GuildId) -> Option<usize> {
+    let max_queue_size = data.settings.get(guild_id).max_queue_size?;
+
+    let (queued, is_active) = data
+        .client_state_map
+        .with(guild_id.as_u64(), |client_state| {
+            (
+                client_state.song_queue.as_ref().map_or(0, Vec::len),
+                client_state.playback.is_active(),
+            )
+        })
+        .await
+        .unwrap_or_default();
+
+    Some(max_queue_size.saturating_sub(queued) + usize::from(!is_active))
+}
+
+/// Starts streaming the remaining pages of a playlist into the queue and registers the import,
+/// so it can be cancelled along with the queue.
+pub(crate) async fn start_import(data: &ServerState, guild_id: GuildId, import: PlaylistImport) {
+    let import = import.spawn();
+
+    let handle = Arc::new(import);
+    let registered = data
+        .client_state_map
+        .with(guild_id.as_u64(), {
+            let handle = handle.clone();
+            move |client_state| {
+                client_state
+                    .playlist_imports
+                    .retain(|import| !import.is_finished());
+                client_state.playlist_imports.push(handle);
+            }
+        })
+        .await;
+
+    if let Err(err) = registered {
+        debug!("Could not register the playlist import for {guild_id}. Error: {err:?}");
+        handle.abort();
+    }
 }
 
 /// Summon this bot to play a YouTube video as audio.
@@ -194,108 +371,143 @@ async fn handle_play(
 pub async fn play(
     context: Context<'_>,
     #[description = "URL or search query to the requested video."] query: Option<String>,
+    #[description = "Position in the playlist to start from."]
+    #[min = 1]
+    from: Option<u32>,
+    #[description = "Position in the playlist to stop at. Channels queue their latest 25 uploads by default."]
+    #[min = 1]
+    to: Option<u32>,
+    #[description = "Shuffle the playlist as it is queued."] shuffle: Option<bool>,
 ) -> Result<(), Error> {
     info!(
         "play::play() received query: {}.",
-        &query.clone().unwrap_or_else(|| "None".to_string())
+        query.as_deref().unwrap_or("None")
     );
 
-    if query.is_none() {
-        context
-            .say("Please provide a URL of video search query.")
-            .await?;
-        return Ok(());
-    }
+    let query = query.ok_or_else(|| {
+        Error::UserInput("Please provide a URL of video search query.".to_string())
+    })?;
 
-    let gid = {
-        match context.guild_id() {
-            Some(gid) => gid,
-            None => {
-                error!("play::play() could not find a valid guild id in context.");
-                context.say("An unexpected error occurred.").await?;
-                return Err(Error::from(
-                    "play::play() could not find a valid guild id in context.",
-                ));
-            }
-        }
-    };
+    let gid = utils::guild_id(&context)?;
 
     if let Err(err) = utils::summon(&context).await {
         error!("play::play() could not connect to voice channel for gid: {gid}. Error: {err:?}");
-        return Ok(());
+        return Err(err);
     }
 
-    return if let (Some(input), Ok(_)) = join!(
-        source_input(&context, query.clone().unwrap()),
+    let (input, deferred) = join!(
+        source_input(context.data().youtube_client.as_ref(), query),
         context.defer()
-    ) {
-        // respond before timeout.
-        if let SourceType::Playlist((p, p_items)) = &input {
-            context
-                .say(format!(
-                    "{} - {} containing {} videos found.",
-                    utils::decode_html_encoded_string(&p.title),
-                    utils::decode_html_encoded_string(&p.channel_name),
-                    p_items.len()
-                ))
-                .await?;
-        }
+    );
+    deferred?;
+    let mut input = input?;
 
-        match handle_play(&gid, &context, input).await {
-            Ok(play_status) => {
-                context
-                    .say(match play_status {
-                        PlayStatus::Playing(v) => {
-                            format!(
-                                "Playing: {} by {}.\n<{}>",
-                                utils::decode_html_encoded_string(&v.title),
-                                utils::decode_html_encoded_string(&v.channel_name),
-                                v.url
-                            )
-                        }
-                        PlayStatus::Queued(st) => match st {
-                            SourceType::Single(v) => {
-                                format!(
-                                    "Queued: {} by {}.\n<{}>",
-                                    utils::decode_html_encoded_string(&v.title),
-                                    utils::decode_html_encoded_string(&v.channel_name),
-                                    v.url
-                                )
-                            }
-                            SourceType::Playlist((_, p)) => format!("Queued {} videos.", p.len()),
-                        },
-                        PlayStatus::PlayAndQueued(p) => {
-                            let v = p.first().clone().unwrap();
-                            format!(
-                                "Queued {} videos.\nPlaying: {} by {}.\n<{}>",
-                                p.len(),
-                                utils::decode_html_encoded_string(&v.title),
-                                utils::decode_html_encoded_string(&v.channel_name),
-                                v.url
-                            )
-                        }
-                    })
-                    .await?;
-
-                Ok(())
-            }
-            Err(err) => {
-                error!("Could not play the requested resource. Error: {err:?}");
-                context
-                    .say("Could not play the requested resource. Resetting connection...")
-                    .await?;
-                utils::banish(&context).await?;
-                Ok(())
-            }
-        }
-    } else {
-        warn!("Could not find the requested resource for `{query:?}`");
+    let room = queue_room(context.data(), gid).await;
+    if room == Some(0) {
+        return Err(Error::UserInput(
+            "The queue is full. Please wait for some tracks to play before adding more."
+                .to_string(),
+        ));
+    }
+
+    if let SourceType::Single(element) = &input {
+        context
+            .data()
+            .settings
+            .get(gid)
+            .check_length(element.duration)
+            .map_err(Error::UserInput)?;
+    }
+
+    // Only the first page of a playlist is queued up front, the rest is imported in the background.
+    let mut import = None;
+
+    if let SourceType::Playlist(playlist) = &mut input {
+        import = prepare_playlist(
+            context.data(),
+            &context.serenity_context().http,
+            Some(context.channel_id()),
+            gid,
+            playlist,
+            PlaylistOptions {
+                from: from.map(|from| from as usize),
+                to: to.map(|to| to as usize),
+                shuffle: shuffle.unwrap_or_default(),
+            },
+            room,
+        )
+        .await?;
+
+        // respond before timeout.
         context
             .say(format!(
-                "Could not find the requested resource: {}",
-                &query.unwrap()
+                "Found {} - {}.",
+                utils::decode_html_encoded_string(&playlist.metadata.title),
+                utils::decode_html_encoded_string(&playlist.metadata.channel_name),
             ))
             .await?;
-        Ok(())
+    }
+
+    let manager = songbird::get(context.serenity_context())
+        .await
+        .ok_or_else(|| Error::VoiceConnection("Songbird is not initialized.".to_string()))?;
+    let play_status = match handle_play(
+        &gid,
+        context.data(),
+        &context.serenity_context().http,
+        &manager,
+        Some(*context.channel_id().as_u64()),
+        input,
+    )
+    .await
+    {
+        Ok(play_status) => play_status,
+        // Nothing went wrong with the connection, so the bot stays for the next request.
+        Err(err @ Error::UserInput(_)) => return Err(err),
+        Err(err) => {
+            error!("Could not play the requested resource. Error: {err:?}");
+            utils::banish(&context).await?;
+            return Err(err);
+        }
     };
+
+    context
+        .say(match play_status {
+            PlayStatus::Playing(v) => {
+                format!(
+                    "Playing: {} by {}.\n<{}>",
+                    utils::decode_html_encoded_string(&v.title),
+                    utils::decode_html_encoded_string(&v.channel_name),
+                    v.url
+                )
+            }
+            PlayStatus::Queued(st) => match st {
+                SourceType::Single(v) => {
+                    format!(
+                        "Queued: {} by {}.\n<{}>",
+                        utils::decode_html_encoded_string(&v.title),
+                        utils::decode_html_encoded_string(&v.channel_name),
+                        v.url
+                    )
+                }
+                SourceType::Playlist(p) => format!("Queued {} videos.", p.page.items.len()),
+            },
+            PlayStatus::PlayAndQueued(p) => match p.first() {
+                Some(v) => format!(
+                    "Queued {} videos.\nPlaying: {} by {}.\n<{}>",
+                    p.len(),
+                    utils::decode_html_encoded_string(&v.title),
+                    utils::decode_html_encoded_string(&v.channel_name),
+                    v.url
+                ),
+                None => format!("Queued {} videos.", p.len()),
+            },
+        })
+        .await?;
+
+    if let Some(import) = import {
+        start_import(context.data(), gid, import).await;
+    }
+
+    Ok(())
 }
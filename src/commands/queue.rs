@@ -1,10 +1,12 @@
 use crate::{
-    checks::shared_room_check,
-    client_state::{ClientState, QueueElement},
+    checks::{dj_check, shared_room_check},
+    client_state::QueueElement,
     config::{Context, Error},
+    utils,
 };
 
 use rand::seq::SliceRandom;
+use std::time::Duration;
 
 /// Commands to interact with and manipulate the queue.
 #[poise::command(
@@ -22,12 +24,15 @@ pub async fn show(
     context: Context<'_>,
     #[description = "Number of queue items to show."] count: Option<u8>,
 ) -> Result<(), Error> {
-    let client_map = context.data().client_state_map.read().await;
-    let client_state = client_map.get(context.guild_id().unwrap().as_u64());
+    let client_state = context
+        .data()
+        .client_state_map
+        .get(utils::guild_id(&context)?.as_u64())
+        .await;
 
     if let Some(state) = client_state {
         match &state.song_queue {
-            Some(v) if v.len() > 0 => {
+            Some(v) if !v.is_empty() => {
                 let out = v
                     .iter()
                     .take(
@@ -39,16 +44,17 @@ pub async fn show(
                     .enumerate()
                     .fold(String::new(), |accum, (i, curr)| {
                         format!(
-                            "{}{}. {} by {}. <{}>\n",
+                            "{}{}. {} by {}{}. <{}>\n",
                             accum,
                             i + 1,
                             curr.title,
                             curr.channel_name,
+                            if curr.live { " 🔴 LIVE" } else { "" },
                             curr.url
                         )
                     });
                 context
-                    .say(format!("{} items are queued.\n{}", v.len(), out))
+                    .say(format!("{} items are queued{}.\n{}", v.len(), eta(v), out))
                     .await?;
             }
             _ => {
@@ -60,109 +66,113 @@ pub async fn show(
     Ok(())
 }
 
+/// Describes the total playback time of the queue. Livestreams have no length and are left out.
+fn eta(queue: &[QueueElement]) -> String {
+    let total = queue
+        .iter()
+        .filter_map(|element| element.duration)
+        .sum::<Duration>();
+    let live = queue.iter().filter(|element| element.live).count();
+    let unknown = queue
+        .iter()
+        .filter(|element| !element.live && element.duration.is_none())
+        .count();
+
+    if total.is_zero() {
+        return String::new();
+    }
+
+    let mut eta = format!(", {} in total", utils::format_duration(total));
+    if live > 0 {
+        eta += &format!(" plus {live} livestream(s)");
+    }
+    if unknown > 0 {
+        eta += &format!(" and {unknown} track(s) of unknown length");
+    }
+
+    eta
+}
+
 /// Clear all elements in the queue.
-#[poise::command(slash_command, check = "shared_room_check")]
+#[poise::command(slash_command, check = "shared_room_check", check = "dj_check")]
 pub async fn clear(
     context: Context<'_>,
     #[description = "Number of items to remove from the queue."] count: Option<u8>,
 ) -> Result<(), Error> {
-    let guild_id = context.guild_id().unwrap();
-    let mut client_map = context.data().client_state_map.write().await;
+    let guild_id = utils::guild_id(&context)?;
+
+    let cleared = context
+        .data()
+        .client_state_map
+        .with(guild_id.as_u64(), move |client_state| {
+            let queue = client_state.song_queue.as_mut()?;
+
+            match count {
+                Some(count) => {
+                    queue.drain(..queue.len().min(count.into()));
+                }
+                None => {
+                    queue.clear();
+                    client_state.cancel_playlist_imports();
+                }
+            }
 
-    if let Some(client_state) = client_map.get(guild_id.as_u64()).cloned() {
-        if let Some(queue) = client_state.song_queue {
-            let updated_queue = if let Some(count) = count {
-                queue
-                    .iter()
-                    .skip(count.into())
-                    .map(|elem| elem.to_owned())
-                    .collect::<Vec<QueueElement>>()
-            } else {
-                vec![]
-            };
-
-            client_map
-                .update(
-                    guild_id.as_u64(),
-                    &mut ClientState {
-                        song_queue: Some(updated_queue),
-                        ..client_state
-                    },
-                )
-                .unwrap();
-
-            context.say("The queue has been updated.").await?;
-        } else {
-            context.say("The queue is empty.").await?;
-        }
-    }
+            Some(())
+        })
+        .await?;
+
+    match cleared {
+        Some(_) => context.say("The queue has been updated.").await?,
+        None => context.say("The queue is empty.").await?,
+    };
 
     Ok(())
 }
 
 /// Shuffle the items in the queue.
-#[poise::command(slash_command, check = "shared_room_check")]
+#[poise::command(slash_command, check = "shared_room_check", check = "dj_check")]
 pub async fn shuffle(context: Context<'_>) -> Result<(), Error> {
-    let guild_id = context.guild_id().unwrap();
-    let mut client_map = context.data().client_state_map.write().await;
-
-    if let Some(client_state) = client_map.get(guild_id.as_u64()).cloned() {
-        if let Some(queue) = client_state.song_queue {
-            let mut updated_queue = queue
-                .iter()
-                .map(|elem| elem.to_owned())
-                .collect::<Vec<QueueElement>>();
-
-            updated_queue.shuffle(&mut rand::thread_rng());
-
-            client_map
-                .update(
-                    guild_id.as_u64(),
-                    &mut ClientState {
-                        song_queue: Some(updated_queue.to_vec()),
-                        ..client_state
-                    },
-                )
-                .unwrap();
-
-            context.say("Queue has been shuffled.").await?;
-        } else {
-            context.say("The queue is empty.").await?;
-        }
-    }
+    let guild_id = utils::guild_id(&context)?;
+
+    let shuffled = context
+        .data()
+        .client_state_map
+        .with(guild_id.as_u64(), |client_state| {
+            client_state
+                .song_queue
+                .as_mut()
+                .map(|queue| queue.shuffle(&mut rand::thread_rng()))
+        })
+        .await?;
+
+    match shuffled {
+        Some(_) => context.say("Queue has been shuffled.").await?,
+        None => context.say("The queue is empty.").await?,
+    };
 
     Ok(())
 }
 
 /// Reverse the order of queue elements.
-#[poise::command(slash_command, check = "shared_room_check")]
+#[poise::command(slash_command, check = "shared_room_check", check = "dj_check")]
 pub async fn reverse(context: Context<'_>) -> Result<(), Error> {
-    let guild_id = context.guild_id().unwrap();
-    let mut client_map = context.data().client_state_map.write().await;
-
-    if let Some(client_state) = client_map.get(guild_id.as_u64()).cloned() {
-        if let Some(queue) = client_state.song_queue {
-            let updated_queue = queue
-                .iter()
-                .rev()
-                .map(|elem| elem.to_owned())
-                .collect::<Vec<QueueElement>>();
-
-            client_map
-                .update(
-                    guild_id.as_u64(),
-                    &mut ClientState {
-                        song_queue: Some(updated_queue),
-                        ..client_state
-                    },
-                )
-                .unwrap();
-
-            context.say("Queue has been reversed.").await?;
-        } else {
-            context.say("The queue is empty.").await?;
-        }
-    }
+    let guild_id = utils::guild_id(&context)?;
+
+    let reversed = context
+        .data()
+        .client_state_map
+        .with(guild_id.as_u64(), |client_state| {
+            client_state
+                .song_queue
+                .as_mut()
+                .map(|queue| queue.reverse())
+        })
+        .await?;
+
+    match reversed {
+        Some(_) => context.say("Queue has been reversed.").await?,
+        None => context.say("The queue is empty.").await?,
+    };
 
     Ok(())
 }
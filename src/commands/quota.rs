@@ -0,0 +1,29 @@
+use crate::config::{Context, Error};
+
+/// Show how much of today's YouTube API quota is left.
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_GUILD",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn quota(ctx: Context<'_>) -> Result<(), Error> {
+    match &ctx.data().youtube_quota {
+        Some(quota) => {
+            ctx.say(format!(
+                "{} of {} YouTube API units remaining today ({} used). The quota resets <t:{}:R>.",
+                quota.remaining(),
+                quota.daily_limit(),
+                quota.used(),
+                quota.resets_at().timestamp()
+            ))
+            .await?;
+        }
+        None => {
+            ctx.say("The YouTube API is not in use, metadata is looked up with yt-dlp.")
+                .await?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use crate::{
+    checks::{dj_check, shared_room_check},
+    config::{Context, Error},
+    utils::{
+        self,
+        segment_skip::{Category, SegmentSkip},
+    },
+};
+
+/// Commands to configure skipping of sponsor reads, intros and other non-music segments.
+#[poise::command(
+    slash_command,
+    check = "shared_room_check",
+    subcommands("show", "toggle", "category")
+)]
+pub async fn segments(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Show whether segments are skipped, and which categories.
+#[poise::command(slash_command, check = "shared_room_check")]
+pub async fn show(context: Context<'_>) -> Result<(), Error> {
+    let guild_id = utils::guild_id(&context)?;
+
+    if context.data().segment_provider.is_none() {
+        context.say("Segment skipping is not configured.").await?;
+        return Ok(());
+    }
+
+    let segment_skip = context.data().settings.get(guild_id).segment_skip;
+
+    context.say(describe(&segment_skip)).await?;
+
+    Ok(())
+}
+
+/// Turn segment skipping on or off.
+#[poise::command(slash_command, check = "shared_room_check", check = "dj_check")]
+pub async fn toggle(
+    context: Context<'_>,
+    #[description = "Whether segments are skipped."] enabled: bool,
+) -> Result<(), Error> {
+    update(context, |segment_skip| segment_skip.enabled = enabled).await
+}
+
+/// Choose whether a category of segments is skipped.
+#[poise::command(slash_command, check = "shared_room_check", check = "dj_check")]
+pub async fn category(
+    context: Context<'_>,
+    #[description = "The category of segments."] category: Category,
+    #[description = "Whether segments of this category are skipped."] skip: bool,
+) -> Result<(), Error> {
+    update(context, |segment_skip| {
+        segment_skip.categories.retain(|c| *c != category);
+        if skip {
+            segment_skip.categories.push(category);
+        }
+    })
+    .await
+}
+
+async fn update(context: Context<'_>, change: impl FnOnce(&mut SegmentSkip)) -> Result<(), Error> {
+    let guild_id = utils::guild_id(&context)?;
+
+    if context.data().segment_provider.is_none() {
+        context.say("Segment skipping is not configured.").await?;
+        return Ok(());
+    }
+
+    let settings = context
+        .data()
+        .settings
+        .update(guild_id, |settings| change(&mut settings.segment_skip))
+        .await;
+
+    context.say(describe(&settings.segment_skip)).await?;
+
+    Ok(())
+}
+
+fn describe(segment_skip: &SegmentSkip) -> String {
+    if !segment_skip.enabled {
+        return "Segment skipping is disabled.".to_string();
+    }
+
+    let categories = Category::ALL
+        .iter()
+        .filter(|category| segment_skip.categories.contains(category))
+        .map(|category| category.to_string())
+        .collect::<Vec<_>>();
+
+    match categories.len() {
+        0 => "Segment skipping is enabled, but no categories are selected.".to_string(),
+        _ => format!("Skipping segments: {}.", categories.join(", ")),
+    }
+}
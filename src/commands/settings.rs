@@ -0,0 +1,355 @@
+use poise::serenity_prelude::{GuildChannel, Role};
+use std::time::Duration;
+
+use crate::{
+    config::{Context, Error},
+    settings::GuildSettings,
+    utils,
+};
+
+/// View and change how the bot behaves in this server.
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_GUILD",
+    required_permissions = "MANAGE_GUILD",
+    subcommands(
+        "show",
+        "volume",
+        "idle_timeout",
+        "announcements",
+        "announce_channel",
+        "dj_role",
+        "max_queue_size",
+        "max_track_length",
+        "autoplay",
+        "crossfade"
+    )
+)]
+pub async fn settings(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Show the current settings.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+pub async fn show(context: Context<'_>) -> Result<(), Error> {
+    let settings = context.data().settings.get(utils::guild_id(&context)?);
+
+    context
+        .say(format!(
+            "Volume: {}%\n\
+            Idle timeout: {}\n\
+            Announcements: {}\n\
+            Announce channel: {}\n\
+            DJ role: {}\n\
+            Max queue size: {}\n\
+            Max track length: {}\n\
+            Autoplay: {}\n\
+            Crossfade: {}",
+            settings.volume,
+            describe_idle_timeout(&settings),
+            if settings.announcements { "on" } else { "off" },
+            settings
+                .announce_channel
+                .map_or("where `/play` was last used".to_string(), |id| format!(
+                    "<#{id}>"
+                )),
+            settings
+                .dj_role
+                .map_or("none".to_string(), |id| format!("<@&{id}>")),
+            settings
+                .max_queue_size
+                .map_or("unlimited".to_string(), |size| size.to_string()),
+            settings
+                .max_track_length
+                .map_or("unlimited".to_string(), utils::format_duration),
+            if settings.autoplay { "on" } else { "off" },
+            settings.crossfade.map_or("off".to_string(), |fade| format!(
+                "{} seconds",
+                fade.as_secs()
+            )),
+        ))
+        .await?;
+
+    Ok(())
+}
+
+fn describe_idle_timeout(settings: &GuildSettings) -> String {
+    match settings.idle_timeout.as_secs() / 60 {
+        0 => "never leave".to_string(),
+        1 => "1 minute".to_string(),
+        minutes => format!("{minutes} minutes"),
+    }
+}
+
+/// Set the volume tracks play at.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+pub async fn volume(
+    context: Context<'_>,
+    #[description = "Volume in percent."]
+    #[max = 200]
+    percent: u8,
+) -> Result<(), Error> {
+    let guild_id = utils::guild_id(&context)?;
+    let settings = context
+        .data()
+        .settings
+        .update(guild_id, |settings| settings.volume = percent)
+        .await;
+
+    // Apply the new volume to the current track as well.
+    let current_track = context
+        .data()
+        .client_state_map
+        .get(guild_id.as_u64())
+        .await
+        .and_then(|client_state| client_state.current_track.clone());
+
+    if let Some(current_track) = current_track {
+        let _ = current_track.set_volume(settings.gain());
+    }
+
+    context.say(format!("Volume set to {percent}%.")).await?;
+
+    Ok(())
+}
+
+/// Set how long the bot stays in a voice channel with nothing to play.
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "idle-timeout",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn idle_timeout(
+    context: Context<'_>,
+    #[description = "Minutes to wait before leaving. 0 never leaves."]
+    #[max = 1440]
+    minutes: u16,
+) -> Result<(), Error> {
+    let settings = context
+        .data()
+        .settings
+        .update(utils::guild_id(&context)?, |settings| {
+            settings.idle_timeout = Duration::from_secs(u64::from(minutes) * 60)
+        })
+        .await;
+
+    context
+        .say(format!(
+            "Idle timeout set to {}.",
+            describe_idle_timeout(&settings)
+        ))
+        .await?;
+
+    Ok(())
+}
+
+/// Turn announcements of track transitions, disconnects and playback errors on or off.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+pub async fn announcements(
+    context: Context<'_>,
+    #[description = "Whether announcements are posted."] enabled: bool,
+) -> Result<(), Error> {
+    context
+        .data()
+        .settings
+        .update(utils::guild_id(&context)?, |settings| {
+            settings.announcements = enabled
+        })
+        .await;
+
+    context
+        .say(match enabled {
+            true => "Track transitions and playback errors will be announced.",
+            false => "Announcements are off.",
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Set the channel track transitions and errors are posted to.
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "announce-channel",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn announce_channel(
+    context: Context<'_>,
+    #[description = "Leave empty to announce where `/play` was last used."]
+    #[channel_types("Text")]
+    channel: Option<GuildChannel>,
+) -> Result<(), Error> {
+    let channel_id = channel.map(|channel| *channel.id.as_u64());
+
+    context
+        .data()
+        .settings
+        .update(utils::guild_id(&context)?, |settings| {
+            settings.announce_channel = channel_id
+        })
+        .await;
+
+    context
+        .say(match channel_id {
+            Some(id) => format!("Announcements will be posted in <#{id}>."),
+            None => "Announcements will be posted where `/play` was last used.".to_string(),
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Restrict controlling playback to members with a role.
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "dj-role",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn dj_role(
+    context: Context<'_>,
+    #[description = "Leave empty to let everyone control playback."] role: Option<Role>,
+) -> Result<(), Error> {
+    let role_id = role.map(|role| *role.id.as_u64());
+
+    context
+        .data()
+        .settings
+        .update(utils::guild_id(&context)?, |settings| {
+            settings.dj_role = role_id
+        })
+        .await;
+
+    context
+        .say(match role_id {
+            Some(id) => format!("Controlling playback now requires the <@&{id}> role."),
+            None => "Everyone can control playback.".to_string(),
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Limit how many tracks can be queued.
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "max-queue-size",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn max_queue_size(
+    context: Context<'_>,
+    #[description = "Leave empty for no limit."]
+    #[min = 1]
+    tracks: Option<u32>,
+) -> Result<(), Error> {
+    let size = tracks.map(|tracks| tracks as usize);
+
+    context
+        .data()
+        .settings
+        .update(utils::guild_id(&context)?, |settings| {
+            settings.max_queue_size = size
+        })
+        .await;
+
+    context
+        .say(match size {
+            Some(size) => format!("The queue now holds at most {size} tracks."),
+            None => "The queue size is now unlimited.".to_string(),
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Limit how long queued tracks may be.
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "max-track-length",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn max_track_length(
+    context: Context<'_>,
+    #[description = "Length in minutes. Leave empty for no limit."]
+    #[min = 1]
+    minutes: Option<u32>,
+) -> Result<(), Error> {
+    let length = minutes.map(|minutes| Duration::from_secs(u64::from(minutes) * 60));
+
+    context
+        .data()
+        .settings
+        .update(utils::guild_id(&context)?, |settings| {
+            settings.max_track_length = length
+        })
+        .await;
+
+    context
+        .say(match length {
+            Some(length) => format!(
+                "Tracks may now be at most {} long.",
+                utils::format_duration(length)
+            ),
+            None => "Track length is now unlimited.".to_string(),
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Queue related tracks once the queue runs out.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+pub async fn autoplay(
+    context: Context<'_>,
+    #[description = "Whether related tracks are queued."] enabled: bool,
+) -> Result<(), Error> {
+    context
+        .data()
+        .settings
+        .update(utils::guild_id(&context)?, |settings| {
+            settings.autoplay = enabled
+        })
+        .await;
+
+    context
+        .say(match enabled {
+            true => "Related tracks will be queued once the queue runs out.",
+            false => "Autoplay is off.",
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Crossfade between queued tracks.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+pub async fn crossfade(
+    context: Context<'_>,
+    #[description = "Crossfade duration in seconds. 0 disables crossfading."]
+    #[max = 12]
+    seconds: u8,
+) -> Result<(), Error> {
+    let crossfade = (seconds > 0).then(|| Duration::from_secs(seconds.into()));
+
+    context
+        .data()
+        .settings
+        .update(utils::guild_id(&context)?, |settings| {
+            settings.crossfade = crossfade
+        })
+        .await;
+
+    context
+        .say(match crossfade {
+            Some(_) => format!("Tracks will crossfade for {seconds} seconds."),
+            None => "Crossfading has been disabled.".to_string(),
+        })
+        .await?;
+
+    Ok(())
+}
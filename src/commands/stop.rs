@@ -1,17 +1,31 @@
 use log::warn;
 
 use crate::{
-    checks::shared_room_check,
-    client_state::ClientState,
+    checks::{dj_check, shared_room_check},
+    client_state::PlaybackState,
     config::{Context, Error},
+    utils,
 };
 
 /// Stop the current track and empty the queue.
-#[poise::command(slash_command, check = "shared_room_check")]
+#[poise::command(slash_command, check = "shared_room_check", check = "dj_check")]
 pub(crate) async fn stop(ctx: Context<'_>) -> Result<(), Error> {
-    let gid = ctx.guild_id().unwrap();
+    let gid = utils::guild_id(&ctx)?;
 
-    if let Some(manager) = songbird::get(&ctx.serenity_context()).await {
+    // Reset the state first, so the end of the stopped track doesn't start the next one.
+    let update_res = ctx
+        .data()
+        .client_state_map
+        .with(gid.as_u64(), |client_state| {
+            client_state.cancel_playlist_imports();
+            client_state.song_queue = Some(vec![]);
+            client_state.playback = PlaybackState::Idle;
+            client_state.current_track = None;
+            client_state.now_playing = None;
+        })
+        .await;
+
+    if let Some(manager) = songbird::get(ctx.serenity_context()).await {
         match manager.get(gid) {
             Some(handler) => {
                 handler.lock().await.stop();
@@ -22,29 +36,12 @@ pub(crate) async fn stop(ctx: Context<'_>) -> Result<(), Error> {
         }
     }
 
-    let mut client_map = ctx.data().client_state_map.write().await;
-    let current_state = client_map.get(gid.as_u64()).unwrap().clone();
-
-    let update_res = client_map.update(
-        gid.as_u64(),
-        &mut ClientState {
-            song_queue: Some(vec![]),
-            is_playing: false,
-            current_track: None,
-            ..current_state
-        },
-    );
-
     match update_res {
         Ok(_) => {
             ctx.say("Stopping audio and clearing queue.").await?;
             Ok(())
         }
         Err(client_error) => {
-            ctx.say(
-                "I have encountered some difficulties. Future queries may not behave as intended.",
-            )
-            .await?;
             warn!("stop::stop() encountered error: {:?}", client_error);
             Err(client_error.into())
         }
@@ -1,19 +1,37 @@
 use crate::config::{Context, Error};
 
+pub(crate) mod chapters;
 pub(crate) mod info;
+pub(crate) mod next_chapter;
 pub(crate) mod pause;
+pub(crate) mod prev_chapter;
 pub(crate) mod resume;
 pub(crate) mod seek;
 pub(crate) mod skip;
 
+use chapters::chapters;
 use info::info;
+use next_chapter::next_chapter;
 use pause::pause;
+use prev_chapter::prev_chapter;
 use resume::resume;
 use seek::seek;
 use skip::skip;
 
 /// Commands that allow interacting with and manipulating the current track.
-#[poise::command(slash_command, subcommands("pause", "resume", "info", "seek", "skip"))]
+#[poise::command(
+    slash_command,
+    subcommands(
+        "pause",
+        "resume",
+        "info",
+        "seek",
+        "skip",
+        "chapters",
+        "next_chapter",
+        "prev_chapter"
+    )
+)]
 pub async fn track(_: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
@@ -0,0 +1,61 @@
+use crate::{
+    checks::{bot_is_playing_check, shared_room_check},
+    client_state::ClientStateError,
+    config::{Context, Error},
+    utils::{self, chapters},
+};
+
+/// Discord's message length limit, minus room for the trailing note.
+const MAX_MESSAGE_LENGTH: usize = 1900;
+
+/// List the chapters of the current track.
+#[poise::command(
+    slash_command,
+    check = "shared_room_check",
+    check = "bot_is_playing_check"
+)]
+pub async fn chapters(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = utils::guild_id(&ctx)?;
+    let chapters = chapters::of_current_track(&ctx, guild_id).await?;
+
+    if chapters.is_empty() {
+        ctx.say("The current track has no chapters.").await?;
+        return Ok(());
+    }
+
+    let current_track = ctx
+        .data()
+        .client_state_map
+        .get(guild_id.as_u64())
+        .await
+        .ok_or(ClientStateError::NonExistentClientID)?
+        .current_track
+        .clone();
+
+    let current = match current_track {
+        Some(track) => chapters::current(&chapters, track.get_info().await?.position),
+        None => None,
+    };
+
+    let mut list = format!("The current track has {} chapters:", chapters.len());
+    for (i, chapter) in chapters.iter().enumerate() {
+        let line = format!(
+            "\n{}{}. {} - {}",
+            if current == Some(i) { "▶ " } else { "" },
+            i + 1,
+            utils::format_duration(chapter.start),
+            chapter.title
+        );
+
+        if list.len() + line.len() > MAX_MESSAGE_LENGTH {
+            list.push_str(&format!("\n... and {} more.", chapters.len() - i));
+            break;
+        }
+
+        list.push_str(&line);
+    }
+
+    ctx.say(list).await?;
+
+    Ok(())
+}
@@ -1,53 +1,84 @@
 use crate::{
     checks::shared_room_check,
+    client_state::ClientStateError,
     config::{Context, Error},
-    utils,
+    utils::{self, chapters},
 };
 
 /// See the current track's metadata.
 #[poise::command(slash_command, check = "shared_room_check")]
 pub async fn info(context: Context<'_>) -> Result<(), Error> {
-    let guild_id = context.guild_id().unwrap();
+    let guild_id = utils::guild_id(&context)?;
+    let chapters = chapters::of_current_track(&context, guild_id)
+        .await
+        .unwrap_or_default();
 
-    let client_map = context.data().client_state_map.read().await;
+    let client_state = context
+        .data()
+        .client_state_map
+        .get(guild_id.as_u64())
+        .await
+        .ok_or(ClientStateError::NonExistentClientID)?;
 
-    if let Some(client_state) = client_map.get(guild_id.as_u64()) {
-        if let Some(curr_track) = &client_state.current_track {
-            let metadata = &curr_track.metadata();
-            let play_status = curr_track.get_info().await.unwrap();
+    if let Some(curr_track) = &client_state.current_track {
+        let metadata = &curr_track.metadata();
+        let play_status = curr_track.get_info().await?;
 
-            let (elapsed_m, elapsed_s) = (
-                play_status.play_time.as_secs() / 60,
-                play_status.play_time.as_secs() % 60,
-            );
+        let (elapsed_m, elapsed_s) = (
+            play_status.play_time.as_secs() / 60,
+            play_status.play_time.as_secs() % 60,
+        );
 
-            let (total_m, total_s) = (
-                metadata.duration.unwrap().as_secs() / 60,
-                metadata.duration.unwrap().as_secs() % 60,
-            );
+        let live = client_state
+            .now_playing
+            .as_ref()
+            .is_some_and(|element| element.live);
 
-            let title = metadata.title.as_ref().unwrap();
-            let channel = metadata.channel.as_ref().unwrap();
-
-            context
-                .say(format!(
-                    "Now Playing: {} - {} [{:02}:{:02}/{:02}:{:02}]\n{}",
-                    utils::decode_html_encoded_string(&title),
-                    utils::decode_html_encoded_string(&channel),
-                    elapsed_m,
-                    elapsed_s,
-                    total_m,
-                    total_s,
-                    metadata.source_url.as_ref().unwrap()
-                ))
-                .await?;
+        let progress = if live {
+            format!("🔴 LIVE [{elapsed_m:02}:{elapsed_s:02} elapsed]")
         } else {
-            context.say("Nothing is currently playing.").await?;
-        }
+            let total = metadata.duration.map_or_else(
+                || "--:--".to_string(),
+                |duration| {
+                    format!(
+                        "{:02}:{:02}",
+                        duration.as_secs() / 60,
+                        duration.as_secs() % 60
+                    )
+                },
+            );
+
+            format!("[{elapsed_m:02}:{elapsed_s:02}/{total}]")
+        };
 
-        Ok(())
+        let chapter = chapters::current(&chapters, play_status.position)
+            .map(|i| {
+                format!(
+                    "\nChapter {}/{}: {}",
+                    i + 1,
+                    chapters.len(),
+                    chapters[i].title
+                )
+            })
+            .unwrap_or_default();
+
+        let unknown = String::from("Unknown");
+        let title = metadata.title.as_ref().unwrap_or(&unknown);
+        let channel = metadata.channel.as_ref().unwrap_or(&unknown);
+
+        context
+            .say(format!(
+                "Now Playing: {} - {} {}{}\n{}",
+                utils::decode_html_encoded_string(title),
+                utils::decode_html_encoded_string(channel),
+                progress,
+                chapter,
+                metadata.source_url.as_deref().unwrap_or_default()
+            ))
+            .await?;
     } else {
-        context.say("Sorry. Something went wrong.").await?;
-        Ok(())
+        context.say("Nothing is currently playing.").await?;
     }
+
+    Ok(())
 }
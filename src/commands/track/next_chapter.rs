@@ -0,0 +1,26 @@
+use crate::{
+    checks::{bot_is_playing_check, dj_check, shared_room_check},
+    config::{Context, Error},
+    utils::chapters,
+};
+
+/// Skip to the next chapter of the current track.
+#[poise::command(
+    slash_command,
+    rename = "next-chapter",
+    check = "shared_room_check",
+    check = "dj_check",
+    check = "bot_is_playing_check"
+)]
+pub async fn next_chapter(ctx: Context<'_>) -> Result<(), Error> {
+    chapters::jump(
+        &ctx,
+        |chapters, position| {
+            chapters::current(chapters, position)
+                .map(|i| i + 1)
+                .filter(|i| *i < chapters.len())
+        },
+        "This is the last chapter.",
+    )
+    .await
+}
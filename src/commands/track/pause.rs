@@ -1,44 +1,44 @@
+use poise::serenity_prelude::GuildId;
+
 use crate::{
-    checks::shared_room_check,
-    client_state::ClientState,
+    checks::{dj_check, shared_room_check},
+    client_state::{ClientStateMap, PlaybackState},
     config::{Context, Error},
+    utils,
 };
 
 /// Pause the current track.
-#[poise::command(slash_command, check = "shared_room_check")]
+#[poise::command(slash_command, check = "shared_room_check", check = "dj_check")]
 pub async fn pause(context: Context<'_>) -> Result<(), Error> {
-    let guild_id = context.guild_id().unwrap();
+    let guild_id = utils::guild_id(&context)?;
+    let paused = pause_track(&context.data().client_state_map, guild_id).await?;
 
-    let mut client_map = context.data().client_state_map.write().await;
+    context.say(paused).await?;
 
-    if let Some(client_state) = client_map.get(guild_id.as_u64()).cloned() {
-        match (client_state.is_playing, &client_state.current_track) {
-            (true, Some(track)) => {
-                track.pause()?;
-                client_map
-                    .update(
-                        guild_id.as_u64(),
-                        &mut ClientState {
-                            is_playing: false,
-                            ..client_state
-                        },
-                    )
-                    .unwrap();
+    Ok(())
+}
 
-                context.say("Track paused.").await?;
-            }
-            (false, Some(_)) => {
-                context.say("The track is already paused.").await?;
+/// Pauses the guild's current track. Returns a message describing the outcome.
+pub(crate) async fn pause_track(
+    client_state_map: &ClientStateMap,
+    guild_id: GuildId,
+) -> Result<&'static str, Error> {
+    client_state_map
+        .with(guild_id.as_u64(), |client_state| {
+            match (client_state.playback, &client_state.current_track) {
+                (PlaybackState::Playing, Some(track)) => {
+                    track.pause()?;
+                    client_state.transition(PlaybackState::Paused)?;
+                    Ok("Track paused.")
+                }
+                (PlaybackState::Paused, _) => Ok("The track is already paused."),
+                (PlaybackState::Loading | PlaybackState::Stopping, _) => Err(Error::UserInput(
+                    "The next track is still loading. Try again in a moment.".to_string(),
+                )),
+                _ => Err(Error::UserInput(
+                    "No tracks in the buffer. A track must be queried first".to_string(),
+                )),
             }
-            (_, None) => {
-                context
-                    .say("No tracks in the buffer. A track must be queried first")
-                    .await?;
-            }
-        };
-    } else {
-        context.say("Sorry. Something has gone wrong.").await?;
-    }
-
-    Ok(())
+        })
+        .await?
 }
@@ -0,0 +1,17 @@
+use crate::{
+    checks::{bot_is_playing_check, dj_check, shared_room_check},
+    config::{Context, Error},
+    utils::chapters,
+};
+
+/// Go back to the start of the current or previous chapter.
+#[poise::command(
+    slash_command,
+    rename = "prev-chapter",
+    check = "shared_room_check",
+    check = "dj_check",
+    check = "bot_is_playing_check"
+)]
+pub async fn prev_chapter(ctx: Context<'_>) -> Result<(), Error> {
+    chapters::jump(&ctx, chapters::previous, "This is the first chapter.").await
+}
@@ -1,45 +1,43 @@
+use poise::serenity_prelude::GuildId;
+
 use crate::{
-    checks::shared_room_check,
-    client_state::ClientState,
+    checks::{dj_check, shared_room_check},
+    client_state::{ClientStateMap, PlaybackState},
     config::{Context, Error},
+    utils,
 };
 
 /// Resume a paused track.
-#[poise::command(slash_command, check = "shared_room_check")]
+#[poise::command(slash_command, check = "shared_room_check", check = "dj_check")]
 pub async fn resume(context: Context<'_>) -> Result<(), Error> {
-    let guild_id = context.guild_id().unwrap();
-
-    let mut client_map = context.data().client_state_map.write().await;
+    let guild_id = utils::guild_id(&context)?;
+    let resumed = resume_track(&context.data().client_state_map, guild_id).await?;
 
-    if let Some(client_state) = client_map.get(guild_id.as_u64()).cloned() {
-        match (client_state.is_playing, &client_state.current_track) {
-            (true, Some(_)) => {
-                context.say("The track is not paused.").await?;
-            }
-            (false, Some(track)) => {
-                track.play()?;
+    context.say(resumed).await?;
 
-                client_map
-                    .update(
-                        guild_id.as_u64(),
-                        &mut ClientState {
-                            is_playing: true,
-                            ..client_state
-                        },
-                    )
-                    .unwrap();
+    Ok(())
+}
 
-                context.say("Track resumed.").await?;
-            }
-            (_, None) => {
-                context
-                    .say("No tracks in the buffer. A track must be queried first")
-                    .await?;
+/// Resumes the guild's paused track. Returns a message describing the outcome.
+pub(crate) async fn resume_track(
+    client_state_map: &ClientStateMap,
+    guild_id: GuildId,
+) -> Result<&'static str, Error> {
+    client_state_map
+        .with(guild_id.as_u64(), |client_state| {
+            match (client_state.playback, &client_state.current_track) {
+                (PlaybackState::Paused, Some(track)) => {
+                    track.play()?;
+                    client_state.transition(PlaybackState::Playing)?;
+                    Ok("Track resumed.")
+                }
+                (PlaybackState::Playing | PlaybackState::Loading | PlaybackState::Stopping, _) => {
+                    Ok("The track is not paused.")
+                }
+                _ => Err(Error::UserInput(
+                    "No tracks in the buffer. A track must be queried first".to_string(),
+                )),
             }
-        };
-    } else {
-        context.say("Sorry. Something has gone wrong.").await?;
-    }
-
-    Ok(())
+        })
+        .await?
 }
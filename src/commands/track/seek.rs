@@ -2,187 +2,259 @@ use log::{debug, error};
 use std::time::Duration;
 
 use crate::{
-    checks::{bot_is_playing_check, shared_room_check},
+    checks::{bot_is_playing_check, dj_check, shared_room_check},
+    client_state::ClientStateError,
     config::{Context, Error},
-    utils,
+    utils::{self, chapters},
 };
 
+#[derive(Debug, PartialEq)]
 enum SeekType {
     Relative(i64),
-    Absolute(u64),
+    Absolute(Duration),
+    /// A percentage of the track's length.
+    Percent(f64),
+    /// A 1-based chapter number.
+    ChapterNumber(usize),
+    ChapterTitle(String),
+}
+
+const FORMAT_HINT: &str =
+    "Invalid value given. For absolute timestamps, please use one of the following formats: \
+    `ss`, `mm:ss`, `hh:mm:ss`, `1h2m3s` or a percentage like `50%`. \
+    For relative timestamps, prefix any valid timestamp with a + or -. \
+    To jump to a chapter, give its title or its number like `#3`.";
+
+fn parse(timestamp: &str) -> Result<SeekType, Error> {
+    let invalid = || Error::UserInput(FORMAT_HINT.to_string());
+    let timestamp = timestamp.trim();
+
+    if let Some(percent) = timestamp.strip_suffix('%') {
+        return percent
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|percent| (0.0..=100.0).contains(percent))
+            .map(SeekType::Percent)
+            .ok_or_else(invalid);
+    }
+
+    if let Some(number) = timestamp
+        .strip_prefix('#')
+        .or_else(|| timestamp.strip_prefix("chapter "))
+    {
+        return number
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .filter(|number| *number > 0)
+            .map(SeekType::ChapterNumber)
+            .ok_or_else(invalid);
+    }
+
+    if let Some(offset) = timestamp.strip_prefix(['+', '-']) {
+        let secs = utils::parse_timestamp(offset)
+            .ok_or_else(invalid)?
+            .as_secs();
+        let secs = i64::try_from(secs).map_err(|_| {
+            Error::UserInput("Invalid timestamp. The value is too large.".to_string())
+        })?;
+
+        return Ok(SeekType::Relative(if timestamp.starts_with('-') {
+            -secs
+        } else {
+            secs
+        }));
+    }
+
+    match utils::parse_timestamp(timestamp) {
+        Some(instant) => Ok(SeekType::Absolute(instant)),
+        None if timestamp.chars().any(char::is_alphabetic) => {
+            Ok(SeekType::ChapterTitle(timestamp.to_string()))
+        }
+        None => Err(invalid()),
+    }
 }
 
 /// Seek a specific or relative moment in the current track.
 #[poise::command(
     slash_command,
     check = "shared_room_check",
+    check = "dj_check",
     check = "bot_is_playing_check"
 )]
 pub async fn seek(
     ctx: Context<'_>,
-    #[description = "Seek an absolute or relative timestamp in the current track."]
+    #[description = "A timestamp like 1:30 or 1m30s, an offset like +30, a percentage, or a chapter."]
     timestamp: String,
 ) -> Result<(), Error> {
-    let instant = timestamp.trim();
+    let timestamp = parse(&timestamp)?;
 
-    if instant.len() == 0 {
-        ctx.say("Invalid value given. For absolute timestamps, please use the following formats: `mm:ss` or `ss`.\
-                For relative timestamps, prefix any valid format with a + or -."
-        ).await?;
-        return Ok(());
-    }
+    debug!("track::seek(): Parsed parameter: {timestamp:?}.");
 
-    let (operator, instant) = match instant.chars().next() {
-        Some(op) if (op == '+' || op == '-') && instant.len() > 1 => (Some(op), &instant[1..]),
-        Some(op) if (op == '+' || op == '-') => return Ok(()),
-        _ => (None, instant),
-    };
+    let guild_id = utils::guild_id(&ctx)?;
 
-    let (m, s) = if instant.contains(':') {
-        let args = instant.split(':').collect::<Vec<_>>();
+    let chapter = match &timestamp {
+        SeekType::ChapterNumber(_) | SeekType::ChapterTitle(_) => {
+            let chapters = chapters::of_current_track(&ctx, guild_id).await?;
 
-        match args.len() {
-            2 => (args[0].parse::<u64>().ok(), args[1].parse::<u64>().ok()),
-            _ => {
-                ctx.say("Invalid value given, please use the following format: `mm:ss`, to specify a timestamp with minutes and seconds. ").await?;
-                return Ok(());
+            if chapters.is_empty() {
+                return Err(Error::UserInput(
+                    "The current track has no chapters.".to_string(),
+                ));
             }
-        }
-    } else {
-        (
-            None,
-            instant.chars().collect::<String>().parse::<u64>().ok(),
-        )
-    };
 
-    let secs = match m {
-        Some(_) => utils::to_seconds(m, s),
-        None => utils::to_seconds(None, s),
-    };
+            let chapter = match &timestamp {
+                SeekType::ChapterNumber(number) => chapters.get(number - 1),
+                SeekType::ChapterTitle(title) => chapters::find(&chapters, title),
+                _ => None,
+            };
 
-    debug!("track::seek(): Parsed parameters - Minutes: {m:?}, Seconds: {s:?}, Total Seconds: {secs:?}.");
-
-    let timestamp = if let Some(operator) = operator {
-        match secs {
-            Some(v) => {
-                let v = match i64::try_from(v) {
-                    Ok(v) => v,
-                    Err(_) => {
-                        ctx.say("Invalid timestamp. The value is too large.")
-                            .await?;
-                        return Ok(());
-                    }
-                };
-
-                if operator == '-' {
-                    Some(SeekType::Relative(v * -1))
-                } else {
-                    Some(SeekType::Relative(v))
-                }
-            }
-            None => None,
-        }
-    } else {
-        match secs {
-            Some(v) => Some(SeekType::Absolute(v)),
-            None => None,
+            Some(chapter.cloned().ok_or_else(|| {
+                Error::UserInput(format!(
+                    "No such chapter. The current track has {} chapters, see `/track chapters`.",
+                    chapters.len()
+                ))
+            })?)
         }
+        _ => None,
     };
 
-    if timestamp.is_none() {
-        ctx.say("Invalid value given. For absolute timestamps, please use the following formats: `mm:ss` or `ss`.\
-                For relative timestamps, prefix any valid format with a + or -."
-               ).await?;
-        return Ok(());
+    let client_state = ctx
+        .data()
+        .client_state_map
+        .get(guild_id.as_u64())
+        .await
+        .ok_or(ClientStateError::NonExistentClientID)?;
+
+    if client_state
+        .now_playing
+        .as_ref()
+        .is_some_and(|element| element.live)
+    {
+        return Err(Error::UserInput(
+            "Livestreams can't be seeked, playback always follows the live broadcast.".to_string(),
+        ));
     }
 
-    let client_map = ctx.data().client_state_map.write().await;
+    let current_track = client_state
+        .current_track
+        .as_ref()
+        .ok_or_else(|| Error::UserInput("Nothing is currently playing.".to_string()))?;
 
-    let client_state = if let Some(v) = client_map.get(ctx.guild_id().unwrap().as_u64()) {
-        v
-    } else {
-        ctx.say("An unexpected error occured. Please try again.")
-            .await?;
-        return Ok(());
-    };
+    let metadata = current_track.metadata();
+    let track_length = metadata.duration.or_else(|| {
+        client_state
+            .now_playing
+            .as_ref()
+            .and_then(|element| element.duration)
+    });
 
-    let metadata = client_state.current_track.as_ref().unwrap().metadata();
-    let track_length = metadata.duration;
+    let out_of_bounds =
+        || Error::UserInput("The timestamp is outside the track's duration.".to_string());
 
-    let dur = match timestamp.unwrap() {
+    let dur = match timestamp {
         SeekType::Relative(v) => {
-            let curr_pos = client_state
-                .current_track
-                .as_ref()
-                .unwrap()
-                .get_info()
-                .await
-                .as_ref()
-                .unwrap()
-                .position;
-
-            match operator.unwrap() {
-                '-' if curr_pos.as_secs() as i64 + v.abs() < 0 => {
-                    ctx.say("The timestamp is outside the track's duration.")
-                        .await?;
-                    return Ok(());
-                }
-                '+' if curr_pos.as_secs() + (v as u64) >= track_length.unwrap().as_secs() => {
-                    ctx.say("The timestamp is outside the track's duration.")
-                        .await?;
-                    return Ok(());
-                }
-                _ => (),
-            }
+            let curr_pos = current_track.get_info().await?.position.as_secs();
 
-            let dur = Duration::from_secs(if v < 0 {
-                curr_pos.as_secs() - (v.abs() as u64)
-            } else {
-                curr_pos.as_secs() + (v.abs() as u64)
-            });
+            let target = curr_pos.checked_add_signed(v).ok_or_else(out_of_bounds)?;
 
-            dur
+            if track_length.is_some_and(|length| target >= length.as_secs()) {
+                return Err(out_of_bounds());
+            }
+
+            Duration::from_secs(target)
         }
-        SeekType::Absolute(v) => {
-            let dur = Duration::from_secs(v);
-            if track_length.is_some() && &dur > track_length.as_ref().unwrap() {
-                ctx.say("The timestamp is outside the track's duration.")
-                    .await?;
-                return Ok(());
+        SeekType::Absolute(dur) => {
+            if track_length.is_some_and(|length| dur > length) {
+                return Err(out_of_bounds());
             }
 
             dur
         }
+        SeekType::Percent(percent) => {
+            let length = track_length.ok_or_else(|| {
+                Error::UserInput(
+                    "The length of the current track is unknown, please seek a timestamp instead."
+                        .to_string(),
+                )
+            })?;
+
+            Duration::from_secs(length.mul_f64(percent / 100.0).as_secs())
+        }
+        SeekType::ChapterNumber(_) | SeekType::ChapterTitle(_) => chapter
+            .as_ref()
+            .map(|chapter| chapter.start)
+            .unwrap_or_default(),
     };
 
-    let min = dur.as_secs() / 60;
-    let sec = dur.as_secs() - (min * 60);
+    let position = utils::format_duration(dur);
 
-    if let Some(_) = client_state
-        .current_track
-        .as_ref()
-        .unwrap()
+    if current_track
         .seek_time(dur)
-        .map_err(|e| {
-            error!("Seek failed: {e}");
-            e
-        })
-        .ok()
+        .inspect_err(|e| error!("Seek failed: {e}"))
+        .is_ok()
     {
-        let title = metadata.title.as_ref().unwrap();
-        let channel = metadata.channel.as_ref().unwrap();
+        let unknown = String::from("Unknown");
+        let title = metadata.title.as_ref().unwrap_or(&unknown);
+        let channel = metadata.channel.as_ref().unwrap_or(&unknown);
+        let chapter = chapter
+            .map(|chapter| format!(" ({})", chapter.title))
+            .unwrap_or_default();
+
         ctx.say(format!(
-            "Playing {} - {} from {:0>2}:{:0>2}.",
-            utils::decode_html_encoded_string(&title),
-            utils::decode_html_encoded_string(&channel),
-            min,
-            sec
+            "Playing {} - {} from {position}{chapter}.",
+            utils::decode_html_encoded_string(title),
+            utils::decode_html_encoded_string(channel),
         ))
         .await?;
     } else {
-        ctx.say(format!("Seeking {min:0>2}:{sec:0>2} failed."))
-            .await?;
+        ctx.say(format!("Seeking {position} failed.")).await?;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(timestamp: &str) -> Option<SeekType> {
+        parse(timestamp).ok()
+    }
+
+    #[test]
+    fn timestamps_are_parsed() {
+        assert_eq!(
+            parsed("1:02:03"),
+            Some(SeekType::Absolute(Duration::from_secs(3723)))
+        );
+        assert_eq!(
+            parsed(" 1m30s "),
+            Some(SeekType::Absolute(Duration::from_secs(90)))
+        );
+        assert_eq!(parsed("+1m"), Some(SeekType::Relative(60)));
+        assert_eq!(parsed("-0:15"), Some(SeekType::Relative(-15)));
+        assert_eq!(parsed("50%"), Some(SeekType::Percent(50.0)));
+        assert_eq!(parsed("12.5 %"), Some(SeekType::Percent(12.5)));
+    }
+
+    #[test]
+    fn chapters_are_parsed() {
+        assert_eq!(parsed("#3"), Some(SeekType::ChapterNumber(3)));
+        assert_eq!(parsed("chapter 2"), Some(SeekType::ChapterNumber(2)));
+        assert_eq!(
+            parsed("the long road"),
+            Some(SeekType::ChapterTitle("the long road".to_string()))
+        );
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        for timestamp in [
+            "", "+", "-", "150%", "-5%", "#0", "#x", "1:75", "+intro", "12:",
+        ] {
+            assert_eq!(parsed(timestamp), None, "{timestamp}");
+        }
+    }
+}
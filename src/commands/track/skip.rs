@@ -1,38 +1,40 @@
 use log::error;
+use poise::serenity_prelude::GuildId;
 
 use crate::{
-    checks::shared_room_check,
+    checks::{dj_check, shared_room_check},
+    client_state::{ClientStateMap, PlaybackState, QueueElement},
     config::{Context, Error},
+    utils,
 };
 
 /// Skip the current track.
-#[poise::command(slash_command, check = "shared_room_check")]
+#[poise::command(slash_command, check = "shared_room_check", check = "dj_check")]
 pub async fn skip(context: Context<'_>) -> Result<(), Error> {
-    let guild_id = context.guild_id().unwrap();
+    let guild_id = utils::guild_id(&context)?;
 
-    let client_map = context.data().client_state_map.write().await;
-    let client_state = client_map.get(guild_id.as_u64()).unwrap();
-
-    let t_handle = match &client_state.current_track {
-        Some(t_handle) => t_handle,
-        None => {
-            context.say("I can't skip silence.").await?;
+    let next = match skip_track(&context.data().client_state_map, guild_id).await {
+        Ok(next) => next,
+        Err(err @ Error::VoiceConnection(_)) => {
+            error!("An error occured stopping a track. Error: {err:?}");
+            context
+                .say("Sorry something went wrong. Could not skip the current track.")
+                .await?;
             return Ok(());
         }
+        Err(err) => return Err(err),
     };
 
-    if let Err(err) = t_handle.stop() {
-        error!("An error occured stopping a track. Error: {err:?}");
-        context
-            .say("Sorry something went wrong. Could not skip the current track.")
-            .await?;
-    };
-
-    if let Some(v) = client_state.song_queue.as_ref().unwrap_or(&vec![]).first() {
+    // The next track is announced once it starts.
+    if context.data().settings.get(guild_id).announcements {
+        context.say("Skipped.").await?;
+    } else if let Some(v) = next {
         context
             .say(format!(
                 "Playing: {} by {}.\n{}",
-                v.title, v.channel_name, v.url
+                utils::decode_html_encoded_string(&v.title),
+                utils::decode_html_encoded_string(&v.channel_name),
+                v.url
             ))
             .await?;
     } else {
@@ -41,3 +43,35 @@ pub async fn skip(context: Context<'_>) -> Result<(), Error> {
 
     Ok(())
 }
+
+/// Stops the guild's current track so the queue handler moves on. Returns the element
+/// that plays next, if any.
+pub(crate) async fn skip_track(
+    client_state_map: &ClientStateMap,
+    guild_id: GuildId,
+) -> Result<Option<QueueElement>, Error> {
+    client_state_map
+        .with(guild_id.as_u64(), |client_state| -> Result<_, Error> {
+            match (client_state.playback, client_state.current_track.clone()) {
+                (previous @ (PlaybackState::Playing | PlaybackState::Paused), Some(t_handle)) => {
+                    // The queue handler starts the next track once the stopped one has ended.
+                    client_state.transition(PlaybackState::Stopping)?;
+                    if let Err(err) = t_handle.stop() {
+                        // The track keeps playing, so there is no end to wait for.
+                        client_state.playback = previous;
+                        return Err(err.into());
+                    }
+
+                    Ok(client_state
+                        .song_queue
+                        .as_ref()
+                        .and_then(|queue| queue.first().cloned()))
+                }
+                (PlaybackState::Loading | PlaybackState::Stopping, _) => Err(Error::UserInput(
+                    "The next track is still loading. Try again in a moment.".to_string(),
+                )),
+                _ => Err(Error::UserInput("I can't skip silence.".to_string())),
+            }
+        })
+        .await?
+}
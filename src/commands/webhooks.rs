@@ -0,0 +1,159 @@
+use crate::{
+    config::{Context, Error},
+    utils::{
+        self,
+        webhooks::{self, Webhook, MAX_WEBHOOKS},
+    },
+};
+
+/// Post track, queue and voice channel events of this server to HTTP endpoints.
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_GUILD",
+    required_permissions = "MANAGE_GUILD",
+    subcommands("add", "remove", "list")
+)]
+pub async fn webhooks(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Add an endpoint that events are posted to.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+pub async fn add(
+    context: Context<'_>,
+    #[description = "The http or https URL to post to."] url: String,
+    #[description = "Signs each payload with HMAC-SHA256 in the X-Luna-Signature header."]
+    secret: Option<String>,
+) -> Result<(), Error> {
+    let url = parse(&url, context.data().webhook_allow_private).await?;
+    let mut outcome = Ok(());
+
+    context
+        .data()
+        .settings
+        .update(utils::guild_id(&context)?, |settings| {
+            outcome = if settings.webhooks.iter().any(|webhook| webhook.url == url) {
+                Err(format!("<{url}> is already a webhook."))
+            } else if settings.webhooks.len() >= MAX_WEBHOOKS {
+                Err(format!(
+                    "A server may have at most {MAX_WEBHOOKS} webhooks."
+                ))
+            } else {
+                settings.webhooks.push(Webhook {
+                    url: url.clone(),
+                    secret,
+                });
+                Ok(())
+            };
+        })
+        .await;
+    outcome.map_err(Error::UserInput)?;
+
+    reply(context, format!("Events will be posted to <{url}>.")).await
+}
+
+/// Stop posting events to an endpoint.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+pub async fn remove(
+    context: Context<'_>,
+    #[description = "The URL of the webhook."] url: String,
+) -> Result<(), Error> {
+    let mut removed = false;
+
+    context
+        .data()
+        .settings
+        .update(utils::guild_id(&context)?, |settings| {
+            let before = settings.webhooks.len();
+            settings
+                .webhooks
+                .retain(|webhook| webhook.url != url.trim());
+            removed = settings.webhooks.len() < before;
+        })
+        .await;
+
+    if !removed {
+        return Err(Error::UserInput(format!(
+            "<{}> is not a webhook.",
+            url.trim()
+        )));
+    }
+
+    reply(context, format!("Removed <{}>.", url.trim())).await
+}
+
+/// List the endpoints events are posted to.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+pub async fn list(context: Context<'_>) -> Result<(), Error> {
+    let webhooks = context
+        .data()
+        .settings
+        .get(utils::guild_id(&context)?)
+        .webhooks;
+
+    let message = if webhooks.is_empty() {
+        "No webhooks are set up.".to_string()
+    } else {
+        webhooks
+            .iter()
+            .map(|webhook| {
+                format!(
+                    "<{}>{}",
+                    webhook.url,
+                    if webhook.secret.is_some() {
+                        " (signed)"
+                    } else {
+                        ""
+                    }
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    reply(context, message).await
+}
+
+/// Webhook URLs may carry tokens, so replies are only shown to the admin.
+async fn reply(context: Context<'_>, message: String) -> Result<(), Error> {
+    context
+        .send(|reply| reply.content(message).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
+
+/// Returns the URL if it is an absolute http or https URL that doesn't point to
+/// a private or local address.
+async fn parse(url: &str, allow_private: bool) -> Result<String, Error> {
+    let url = url.trim();
+    webhooks::check_target(url, allow_private)
+        .await
+        .map_err(Error::UserInput)?;
+
+    Ok(url.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn only_public_http_urls_are_accepted() {
+        assert!(parse("https://93.184.216.34/hook", false).await.is_ok());
+        assert!(parse(" http://127.0.0.1:8080 ", false).await.is_err());
+        assert!(parse("http://169.254.169.254/", false).await.is_err());
+        assert!(parse("ftp://example.com", false).await.is_err());
+        assert!(parse("example.com/hook", false).await.is_err());
+        assert!(parse("file:///etc/passwd", false).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn local_urls_are_accepted_with_the_opt_in() {
+        assert_eq!(
+            parse(" http://127.0.0.1:8080 ", true).await.unwrap(),
+            "http://127.0.0.1:8080"
+        );
+    }
+}
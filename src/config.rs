@@ -1,27 +1,30 @@
-use crate::client_state::{client_state_map::ClientStateMap, ClientStateError};
-
-use google_youtube3::YouTube;
-use hyper::client::connect::HttpConnector;
-use hyper_rustls::HttpsConnector;
+use crate::{
+    client_state::client_state_map::ClientStateMap,
+    settings::SettingsStore,
+    utils::{
+        segment_skip::SegmentProvider,
+        source_retriever::youtube::{QuotaTracker, YouTubeClient},
+    },
+};
 
 use std::sync::Arc;
-use tokio::sync::RwLock;
 
 //use derive_more::AsMut;
 
-pub type Error = Box<dyn std::error::Error + Send + Sync>;
+pub use crate::error::Error;
 pub type Context<'a> = poise::Context<'a, ServerState, Error>;
 
 #[derive(Clone)]
 pub struct ServerState {
-    pub youtube_client: YouTube<HttpsConnector<HttpConnector>>,
-    pub youtube_api_key: String,
-    pub client_state_map: Arc<RwLock<ClientStateMap>>,
-    pub discord_id: u64,
-}
-
-impl From<ClientStateError> for Error {
-    fn from(val: ClientStateError) -> Self {
-        Error::from(val.to_string())
-    }
+    pub youtube_client: Arc<dyn YouTubeClient>,
+    /// Tracks the Data API quota. `None` when the Data API is not in use.
+    pub youtube_quota: Option<Arc<QuotaTracker>>,
+    pub client_state_map: Arc<ClientStateMap>,
+    pub settings: Arc<SettingsStore>,
+    /// The maximum number of videos imported from a single playlist.
+    pub max_playlist_import: usize,
+    /// Looks up skippable segments. `None` when segment skipping is turned off.
+    pub segment_provider: Option<Arc<SegmentProvider>>,
+    /// Lets webhooks post to loopback, private and link-local addresses.
+    pub webhook_allow_private: bool,
 }
@@ -0,0 +1,275 @@
+use log::{debug, error, info};
+use poise::serenity_prelude::{GuildId, ShardManager};
+use songbird::Songbird;
+use std::{
+    io::ErrorKind,
+    os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    sync::Mutex,
+};
+
+use crate::config::ServerState;
+
+pub(crate) mod protocol;
+
+use protocol::{GuildSummary, Request, Response};
+
+/// Removes a socket left by a previous run. Anything else at the path, including a socket
+/// another instance still listens on, is left alone.
+fn remove_stale_socket(path: &Path) -> Result<(), String> {
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(format!("Error: {err}")),
+    };
+
+    if !metadata.file_type().is_socket() {
+        return Err("The path exists and is not a socket.".to_string());
+    }
+    if std::os::unix::net::UnixStream::connect(path).is_ok() {
+        return Err("Another instance is already listening on it.".to_string());
+    }
+
+    std::fs::remove_file(path).map_err(|err| format!("Error: {err}"))
+}
+
+/// Binds the socket in a directory only the bot's user can enter, restricts it to that user
+/// and only then moves it to its path, so nobody else can ever connect.
+fn bind_private(path: &Path) -> std::io::Result<UnixListener> {
+    let mut staging = path.as_os_str().to_owned();
+    staging.push(format!(".{}.d", std::process::id()));
+    let staging = PathBuf::from(staging);
+    std::fs::DirBuilder::new().mode(0o700).create(&staging)?;
+
+    let bound = staging.join("socket");
+    let listener = UnixListener::bind(&bound).and_then(|listener| {
+        std::fs::set_permissions(&bound, std::fs::Permissions::from_mode(0o600))?;
+        std::fs::rename(&bound, path)?;
+        Ok(listener)
+    });
+
+    let _ = std::fs::remove_file(&bound);
+    let _ = std::fs::remove_dir(&staging);
+    listener
+}
+
+/// Answers `luna-ctl` on a Unix domain socket. Only the user running the bot may connect.
+pub(crate) struct Control {
+    pub(crate) data: ServerState,
+    pub(crate) songbird: Arc<Songbird>,
+    pub(crate) shard_manager: Arc<Mutex<ShardManager>>,
+    pub(crate) path: PathBuf,
+}
+
+impl Control {
+    /// Listens on the socket until the bot shuts down, replacing a socket left by a previous run.
+    pub(crate) fn listen(self) {
+        if let Err(reason) = remove_stale_socket(&self.path) {
+            error!("Could not listen on {:?}. {reason}", self.path);
+            return;
+        }
+
+        let listener = match bind_private(&self.path) {
+            Ok(listener) => listener,
+            Err(err) => {
+                error!("Could not listen on {:?}. Error: {err}", self.path);
+                return;
+            }
+        };
+
+        info!("Listening for luna-ctl on {:?}.", self.path);
+        let control = Arc::new(self);
+
+        tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        let control = control.clone();
+                        tokio::spawn(async move {
+                            if let Err(err) = control.serve(stream).await {
+                                debug!("Control connection closed. Error: {err}");
+                            }
+                        });
+                    }
+                    Err(err) => error!("Could not accept a control connection. Error: {err}"),
+                }
+            }
+        });
+    }
+
+    /// Answers each request line with a response line.
+    async fn serve(&self, stream: UnixStream) -> std::io::Result<()> {
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+
+        while let Some(line) = lines.next_line().await? {
+            let request = serde_json::from_str::<Request>(&line);
+            let response = match &request {
+                Ok(request) => self.handle(request).await,
+                Err(err) => Response::Error {
+                    message: format!("Invalid request. Error: {err}"),
+                },
+            };
+
+            let mut response = serde_json::to_vec(&response)?;
+            response.push(b'\n');
+            writer.write_all(&response).await?;
+
+            // Answered first, so the caller learns the shutdown started.
+            if let Ok(Request::Shutdown) = request {
+                self.shutdown().await;
+                return Ok(());
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn handle(&self, request: &Request) -> Response {
+        match *request {
+            Request::Guilds => Response::Guilds {
+                guilds: self.guilds().await,
+            },
+            Request::State { guild_id } => match self.data.client_state_map.get(&guild_id).await {
+                Some(client_state) => Response::State {
+                    state: format!("{client_state:#?}"),
+                },
+                None => Response::Error {
+                    message: format!("There is no client state for {guild_id}."),
+                },
+            },
+            Request::Leave { guild_id } => match self.leave(GuildId(guild_id)).await {
+                true => Response::Done {
+                    message: format!("Left {guild_id}."),
+                },
+                false => Response::Error {
+                    message: format!("The bot is not in a voice channel of {guild_id}."),
+                },
+            },
+            Request::Reload => match self.data.settings.reload() {
+                Ok(guilds) => Response::Done {
+                    message: format!("Reloaded the settings of {guilds} guilds."),
+                },
+                Err(message) => Response::Error { message },
+            },
+            Request::Shutdown => Response::Done {
+                message: "Shutting down.".to_string(),
+            },
+        }
+    }
+
+    async fn guilds(&self) -> Vec<GuildSummary> {
+        let mut guilds = vec![];
+
+        for guild_id in self.data.client_state_map.ids() {
+            let client_state = match self.data.client_state_map.get(&guild_id).await {
+                Some(client_state) => client_state,
+                None => continue,
+            };
+
+            let (voice_channel, connected) = match self.songbird.get(guild_id) {
+                Some(call) => {
+                    let call = call.lock().await;
+                    (
+                        call.current_channel().map(|channel| channel.0),
+                        call.current_connection().is_some(),
+                    )
+                }
+                None => (None, false),
+            };
+
+            guilds.push(GuildSummary {
+                guild_id,
+                voice_channel,
+                connected,
+                playback: format!("{:?}", client_state.playback).to_lowercase(),
+                now_playing: client_state.now_playing.map(|element| element.title),
+                queue_length: client_state.song_queue.map_or(0, |queue| queue.len()),
+            });
+        }
+
+        guilds.sort_by_key(|guild| guild.guild_id);
+        guilds
+    }
+
+    /// Leaves the guild's voice channel and drops its client state, like `/leave`.
+    /// Returns whether the bot was in the guild.
+    async fn leave(&self, guild_id: GuildId) -> bool {
+        let _ = self
+            .data
+            .client_state_map
+            .with(guild_id.as_u64(), |client_state| {
+                client_state.cancel_playlist_imports()
+            })
+            .await;
+
+        let left_call = self.songbird.remove(guild_id).await.is_ok();
+        let had_state = self.data.client_state_map.remove(guild_id.as_u64()).is_ok();
+
+        left_call || had_state
+    }
+
+    /// Leaves every voice channel and disconnects all shards, which ends the bot's main loop.
+    async fn shutdown(&self) {
+        info!("Shutting down on request of luna-ctl.");
+
+        for guild_id in self.data.client_state_map.ids() {
+            self.leave(GuildId(guild_id)).await;
+        }
+
+        let _ = std::fs::remove_file(&self.path);
+        self.shard_manager.lock().await.shutdown_all().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_stale_sockets_are_removed() {
+        let dir = std::env::temp_dir().join(format!("luna-control-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let missing = dir.join("missing.sock");
+        assert!(remove_stale_socket(&missing).is_ok());
+
+        let file = dir.join("file.sock");
+        std::fs::write(&file, "settings").unwrap();
+        assert!(remove_stale_socket(&file).is_err());
+        assert!(file.exists());
+
+        let live = dir.join("live.sock");
+        let listener = std::os::unix::net::UnixListener::bind(&live).unwrap();
+        assert!(remove_stale_socket(&live).is_err());
+        assert!(live.exists());
+
+        drop(listener);
+        assert!(remove_stale_socket(&live).is_ok());
+        assert!(!live.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn sockets_are_private_from_the_start() {
+        let dir = std::env::temp_dir().join(format!("luna-bind-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("luna.sock");
+
+        let _listener = bind_private(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        let leftovers = std::fs::read_dir(&dir).unwrap().count();
+        let connected = std::os::unix::net::UnixStream::connect(&path).is_ok();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(leftovers, 1);
+        assert!(connected);
+    }
+}
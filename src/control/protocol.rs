@@ -0,0 +1,60 @@
+//! Messages exchanged over the control socket, one JSON object per line.
+//! Shared with the `luna-ctl` binary, so it only depends on serde.
+
+use serde::{Deserialize, Serialize};
+
+/// The default path of the control socket, relative to the bot's working directory.
+pub(crate) const DEFAULT_SOCKET: &str = "luna.sock";
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub(crate) enum Request {
+    /// List the guilds with a client state and their voice connections.
+    Guilds,
+    /// Dump a guild's client state.
+    State { guild_id: u64 },
+    /// Leave a guild's voice channel and drop its client state.
+    Leave { guild_id: u64 },
+    /// Reload the guild settings file.
+    Reload,
+    /// Leave every voice channel and disconnect from Discord.
+    Shutdown,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub(crate) enum Response {
+    Guilds { guilds: Vec<GuildSummary> },
+    State { state: String },
+    Done { message: String },
+    Error { message: String },
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct GuildSummary {
+    pub(crate) guild_id: u64,
+    /// The voice channel songbird is connected to, if any.
+    pub(crate) voice_channel: Option<u64>,
+    /// Whether the voice connection is established.
+    pub(crate) connected: bool,
+    pub(crate) playback: String,
+    pub(crate) now_playing: Option<String>,
+    pub(crate) queue_length: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_are_tagged_by_command() {
+        assert_eq!(
+            serde_json::to_string(&Request::Leave { guild_id: 42 }).unwrap(),
+            r#"{"command":"leave","guild_id":42}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<Request>(r#"{"command":"shutdown"}"#).unwrap(),
+            Request::Shutdown
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use log::error;
+use std::fmt::{Display, Formatter};
+
+use crate::{
+    client_state::ClientStateError,
+    config::{Context, ServerState},
+    metrics,
+};
+
+/// Errors raised while handling a command or an event.
+#[derive(Debug)]
+pub enum Error {
+    /// The requested resource could not be found or turned into playable audio.
+    SourceResolution(String),
+    /// A request to the YouTube Data API failed.
+    YouTubeApi(Box<google_youtube3::Error>),
+    /// The YouTube backend can't perform this lookup, e.g. the Data API can't read mixes.
+    Unsupported(String),
+    /// Joining, leaving or driving a voice channel failed.
+    VoiceConnection(String),
+    /// The client state did not match what the command expected.
+    State(ClientStateError),
+    /// The command was invoked with unusable arguments or from an unusable place.
+    UserInput(String),
+    /// A Discord API request failed.
+    Discord(Box<serenity::Error>),
+}
+
+impl Error {
+    /// A message suitable to be shown to the user who triggered the error.
+    pub fn user_message(&self) -> String {
+        match self {
+            Error::SourceResolution(reason) => format!("I couldn't play that. {reason}"),
+            Error::YouTubeApi(_) => {
+                "I couldn't reach YouTube right now. Please try again in a moment.".to_string()
+            }
+            Error::Unsupported(reason) => format!("I can't look that up. {reason}"),
+            Error::VoiceConnection(_) => {
+                "I ran into trouble with the voice connection. Please try again.".to_string()
+            }
+            Error::State(_) => {
+                "Something went wrong on my end. Try `/leave` and summoning me again.".to_string()
+            }
+            Error::UserInput(reason) => reason.clone(),
+            Error::Discord(_) => "Something went wrong talking to Discord.".to_string(),
+        }
+    }
+}
+
+impl Error {
+    /// Whether the YouTube Data API rejected the request because the daily quota is spent.
+    pub fn is_quota_exceeded(&self) -> bool {
+        const QUOTA_REASONS: [&str; 3] =
+            ["quotaExceeded", "dailyLimitExceeded", "rateLimitExceeded"];
+
+        match self {
+            Error::YouTubeApi(err) => match err.as_ref() {
+                google_youtube3::Error::BadRequest(body) => body["error"]["errors"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|error| error["reason"].as_str())
+                    .any(|reason| QUOTA_REASONS.contains(&reason)),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::SourceResolution(reason) => write!(f, "(Error::SourceResolution {reason})"),
+            Error::YouTubeApi(err) => write!(f, "(Error::YouTubeApi {err})"),
+            Error::Unsupported(reason) => write!(f, "(Error::Unsupported {reason})"),
+            Error::VoiceConnection(reason) => write!(f, "(Error::VoiceConnection {reason})"),
+            Error::State(err) => write!(f, "(Error::State {err})"),
+            Error::UserInput(reason) => write!(f, "(Error::UserInput {reason})"),
+            Error::Discord(err) => write!(f, "(Error::Discord {err})"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ClientStateError> for Error {
+    fn from(val: ClientStateError) -> Self {
+        Error::State(val)
+    }
+}
+
+impl From<google_youtube3::Error> for Error {
+    fn from(val: google_youtube3::Error) -> Self {
+        Error::YouTubeApi(Box::new(val))
+    }
+}
+
+impl From<serenity::Error> for Error {
+    fn from(val: serenity::Error) -> Self {
+        Error::Discord(Box::new(val))
+    }
+}
+
+impl From<songbird::input::error::Error> for Error {
+    fn from(val: songbird::input::error::Error) -> Self {
+        Error::SourceResolution(val.to_string())
+    }
+}
+
+impl From<songbird::error::JoinError> for Error {
+    fn from(val: songbird::error::JoinError) -> Self {
+        Error::VoiceConnection(val.to_string())
+    }
+}
+
+impl From<songbird::tracks::TrackError> for Error {
+    fn from(val: songbird::tracks::TrackError) -> Self {
+        Error::VoiceConnection(val.to_string())
+    }
+}
+
+/// Renders command errors to the invoking user. Other framework errors
+/// are delegated to poise's default handler.
+pub async fn on_error(error: poise::FrameworkError<'_, ServerState, Error>) {
+    match error {
+        poise::FrameworkError::Command { error, ctx } => {
+            error!("/{} failed. Error: {error}", ctx.command().qualified_name);
+            metrics::COMMAND_FAILURES.inc(&[&ctx.command().qualified_name]);
+            reply(ctx, &error).await;
+        }
+        poise::FrameworkError::CommandCheckFailed {
+            error: Some(error),
+            ctx,
+        } => {
+            error!(
+                "A check for /{} failed. Error: {error}",
+                ctx.command().qualified_name
+            );
+            metrics::COMMAND_FAILURES.inc(&[&ctx.command().qualified_name]);
+            reply(ctx, &error).await;
+        }
+        error => {
+            // Checks that reject without an error are still counted, poise answers them.
+            if let poise::FrameworkError::CommandCheckFailed { ctx, .. } = &error {
+                metrics::COMMAND_FAILURES.inc(&[&ctx.command().qualified_name]);
+            }
+
+            if let Err(err) = poise::builtins::on_error(error).await {
+                error!("Could not handle framework error. Error: {err:?}");
+            }
+        }
+    }
+}
+
+async fn reply(ctx: Context<'_>, error: &Error) {
+    if let Err(err) = ctx.say(error.user_message()).await {
+        error!("Could not report error to the user. Error: {err:?}");
+    }
+}
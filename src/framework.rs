@@ -1,5 +1,3 @@
-use google_youtube3::client::auth::NoToken;
-
 use poise::{
     framework::{Framework, FrameworkBuilder},
     serenity_prelude as serenity,
@@ -7,39 +5,59 @@ use poise::{
 };
 
 use crate::{
-    client_state::ClientStateMap,
+    client_state::{ClientStateMap, EventBus},
     commands,
     config::{Error, ServerState},
+    control::{self, Control},
+    error,
+    metrics::{self, Metrics},
+    settings::SettingsStore,
+    utils::playlist_import,
+    utils::presence::Presence,
+    utils::segment_skip::{self, SegmentProvider},
+    utils::source_retriever::youtube::{
+        cache, quota, CachedClient, DataApiClient, FallbackClient, QuotaTracker, YouTubeClient,
+        YtDlpClient,
+    },
+    utils::webhooks::WebhookDispatcher,
 };
+#[cfg(feature = "api")]
+use log::warn;
+use log::{error, info};
 use songbird::SerenityInit;
 
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::{path::PathBuf, sync::Arc, time::Duration};
 
 pub(crate) async fn build_client(
     secrets: ::config::Config,
     intents: GatewayIntents,
 ) -> FrameworkBuilder<ServerState, Error> {
-    let https = hyper_rustls::HttpsConnectorBuilder::new()
-        .with_native_roots()
-        .https_or_http()
-        .enable_http1()
-        .build();
-
-    let yt = google_youtube3::YouTube::new(hyper::Client::builder().build(https), NoToken);
-
     Framework::builder()
         .options(poise::FrameworkOptions {
             commands: vec![
                 commands::play::play(),
                 commands::leave::leave(),
                 commands::queue::queue(),
+                commands::quota::quota(),
+                commands::segments::segments(),
+                commands::settings::settings(),
                 commands::stop::stop(),
                 commands::track::track(),
+                commands::webhooks::webhooks(),
             ],
+            on_error: |err| Box::pin(error::on_error(err)),
+            pre_command: |ctx| {
+                Box::pin(async move {
+                    metrics::COMMANDS.inc(&[&ctx.command().qualified_name]);
+                })
+            },
             ..Default::default()
         })
-        .token(secrets.get::<String>("DISCORD_TOKEN").unwrap())
+        .token(
+            secrets
+                .get::<String>("DISCORD_TOKEN")
+                .expect("DISCORD_TOKEN is missing from the secrets file."),
+        )
         .intents(intents)
         .client_settings(|cb| cb.register_songbird())
         .setup(|context, _, framework| {
@@ -64,12 +82,233 @@ pub(crate) async fn build_client(
                     }
                 }?;
 
-                Ok(ServerState {
-                    youtube_client: yt,
-                    youtube_api_key: secrets.get("YOUTUBE_API_KEY").unwrap(),
-                    client_state_map: Arc::new(RwLock::new(ClientStateMap::new())),
-                    discord_id: context.cache.current_user_id().as_u64().clone(),
-                })
+                let (backend, youtube_quota) = youtube_backend(&secrets);
+                let events = EventBus::new();
+                events.log();
+                let client_state_map = Arc::new(ClientStateMap::new(events.clone()));
+
+                // PRIMARY_GUILD_ID pins the activity to that guild's track while it plays.
+                let presence = Arc::new(Presence::new(
+                    context.shard.clone(),
+                    client_state_map.clone(),
+                    secrets
+                        .get("PRIMARY_GUILD_ID")
+                        .ok()
+                        .and_then(|v: String| v.parse::<u64>().ok())
+                        .map(serenity::GuildId),
+                ));
+                presence.start(events.subscribe());
+
+                let settings = Arc::new(SettingsStore::new(settings_path(&secrets)));
+                let webhook_allow_private = secrets.get("WEBHOOK_ALLOW_PRIVATE").unwrap_or(false);
+                WebhookDispatcher::new(settings.clone(), webhook_allow_private)
+                    .start(events.subscribe());
+
+                let server_state = ServerState {
+                    youtube_client: cached(&secrets, backend),
+                    youtube_quota,
+                    client_state_map,
+                    settings,
+                    max_playlist_import: secrets
+                        .get("MAX_PLAYLIST_IMPORT")
+                        .unwrap_or(playlist_import::DEFAULT_MAX_IMPORT),
+                    segment_provider: segment_provider(&secrets),
+                    webhook_allow_private,
+                };
+
+                listen_for_control(&secrets, context, framework, &server_state).await;
+                serve_metrics(&secrets, context, &server_state).await;
+
+                #[cfg(feature = "api")]
+                serve_api(&secrets, context, &server_state, &events).await;
+
+                Ok(server_state)
             })
         })
 }
+
+/// Answers `luna-ctl` on the Unix socket at `CONTROL_SOCKET`, `luna.sock` by default.
+/// An empty path turns it off.
+async fn listen_for_control(
+    secrets: &::config::Config,
+    context: &serenity::Context,
+    framework: &Framework<ServerState, Error>,
+    server_state: &ServerState,
+) {
+    let path = secrets
+        .get::<String>("CONTROL_SOCKET")
+        .unwrap_or_else(|_| control::protocol::DEFAULT_SOCKET.to_string());
+    if path.trim().is_empty() {
+        return;
+    }
+
+    match songbird::get(context).await {
+        Some(songbird) => Control {
+            data: server_state.clone(),
+            songbird,
+            shard_manager: framework.shard_manager().clone(),
+            path: PathBuf::from(path),
+        }
+        .listen(),
+        None => error!("Songbird is not initialized, luna-ctl can't connect."),
+    }
+}
+
+/// Serves Prometheus metrics on `METRICS_ADDRESS`, e.g. `127.0.0.1:9090`. Off unless set.
+async fn serve_metrics(
+    secrets: &::config::Config,
+    context: &serenity::Context,
+    server_state: &ServerState,
+) {
+    let address = match secrets.get::<String>("METRICS_ADDRESS") {
+        Ok(address) if !address.trim().is_empty() => address,
+        _ => return,
+    };
+    let address = match address.parse() {
+        Ok(address) => address,
+        Err(err) => {
+            error!("Invalid METRICS_ADDRESS {address:?}. Error: {err}");
+            return;
+        }
+    };
+
+    match songbird::get(context).await {
+        Some(songbird) => Metrics {
+            client_state_map: server_state.client_state_map.clone(),
+            songbird,
+        }
+        .serve(address),
+        None => error!("Songbird is not initialized, metrics are off."),
+    }
+}
+
+/// Serves the REST API and dashboard on `API_ADDRESS`, `127.0.0.1:8080` by default.
+/// They stay off unless `API_TOKEN` is set, since every request must carry that token.
+#[cfg(feature = "api")]
+async fn serve_api(
+    secrets: &::config::Config,
+    context: &serenity::Context,
+    server_state: &ServerState,
+    events: &EventBus,
+) {
+    use crate::api::Api;
+
+    let token = match secrets.get::<String>("API_TOKEN") {
+        Ok(token) if !token.trim().is_empty() => token,
+        _ => {
+            warn!("API_TOKEN is not set, the API is off.");
+            return;
+        }
+    };
+
+    let address = secrets
+        .get::<String>("API_ADDRESS")
+        .unwrap_or_else(|_| "127.0.0.1:8080".to_string());
+    let address = match address.parse() {
+        Ok(address) => address,
+        Err(err) => {
+            error!("Invalid API_ADDRESS {address:?}. Error: {err}");
+            return;
+        }
+    };
+
+    let songbird = match songbird::get(context).await {
+        Some(songbird) => songbird,
+        None => {
+            error!("Songbird is not initialized, the API is off.");
+            return;
+        }
+    };
+
+    Api {
+        data: server_state.clone(),
+        http: context.http.clone(),
+        songbird,
+        events: events.clone(),
+        token: token.into(),
+    }
+    .serve(address);
+}
+
+/// Guild settings are saved to `SETTINGS_PATH`, `guild-settings.json` by default.
+/// An empty path keeps them in memory only.
+fn settings_path(secrets: &::config::Config) -> Option<PathBuf> {
+    let path = secrets
+        .get::<String>("SETTINGS_PATH")
+        .unwrap_or_else(|_| "guild-settings.json".to_string());
+
+    (!path.trim().is_empty()).then(|| PathBuf::from(path))
+}
+
+/// Creates the segment provider from `SEGMENT_PROVIDER_URL`, which defaults to the public
+/// SponsorBlock instance. Setting it to an empty string turns segment skipping off.
+fn segment_provider(secrets: &::config::Config) -> Option<Arc<SegmentProvider>> {
+    let url = secrets
+        .get::<String>("SEGMENT_PROVIDER_URL")
+        .unwrap_or_else(|_| segment_skip::DEFAULT_PROVIDER_URL.to_string());
+
+    (!url.trim().is_empty()).then(|| Arc::new(SegmentProvider::new(url)))
+}
+
+/// Picks the metadata backend. `YOUTUBE_BACKEND` may be set to `api` or `yt-dlp` to use only
+/// that backend. By default the Data API is used when an API key is configured, falling back
+/// to yt-dlp when its quota runs out and for lookups it can't do, like mixes.
+/// Returns the quota tracker when the Data API is in use.
+fn youtube_backend(
+    secrets: &::config::Config,
+) -> (Box<dyn YouTubeClient>, Option<Arc<QuotaTracker>>) {
+    let backend = secrets.get::<String>("YOUTUBE_BACKEND").ok();
+    let api_key = secrets.get::<String>("YOUTUBE_API_KEY").ok();
+
+    match (backend.as_deref(), api_key) {
+        (Some("yt-dlp"), _) => (Box::new(YtDlpClient), None),
+        (backend @ (None | Some("api")), Some(api_key)) => {
+            let quota = Arc::new(QuotaTracker::new(
+                secrets
+                    .get("YOUTUBE_DAILY_QUOTA")
+                    .unwrap_or(quota::DEFAULT_DAILY_QUOTA),
+            ));
+            let api = Box::new(DataApiClient::new(api_key, quota.clone()));
+
+            match backend {
+                Some(_) => (api, Some(quota)),
+                None => (
+                    Box::new(FallbackClient::new(api, Box::new(YtDlpClient))),
+                    Some(quota),
+                ),
+            }
+        }
+        (Some("api"), None) => {
+            panic!("YOUTUBE_BACKEND is set to api, but YOUTUBE_API_KEY is missing from the secrets file.")
+        }
+        (None, None) => {
+            info!("No YOUTUBE_API_KEY configured, resolving YouTube metadata with yt-dlp.");
+            (Box::new(YtDlpClient), None)
+        }
+        (Some(backend), _) => {
+            panic!("Unknown YOUTUBE_BACKEND {backend:?}. Expected \"api\" or \"yt-dlp\".")
+        }
+    }
+}
+
+/// Puts the metadata cache in front of the backend. The cache is configured with
+/// `YOUTUBE_CACHE_TTL` (seconds), `YOUTUBE_CACHE_SIZE` and `YOUTUBE_CACHE_PATH`.
+fn cached(secrets: &::config::Config, backend: Box<dyn YouTubeClient>) -> Arc<dyn YouTubeClient> {
+    let client = Arc::new(CachedClient::new(
+        backend,
+        secrets
+            .get("YOUTUBE_CACHE_TTL")
+            .map(Duration::from_secs)
+            .unwrap_or(cache::DEFAULT_TTL),
+        secrets
+            .get("YOUTUBE_CACHE_SIZE")
+            .unwrap_or(cache::DEFAULT_CAPACITY),
+        secrets
+            .get::<String>("YOUTUBE_CACHE_PATH")
+            .ok()
+            .map(PathBuf::from),
+    ));
+    client.flush_periodically();
+
+    client
+}
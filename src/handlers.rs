@@ -1,9 +1,15 @@
 pub(crate) mod disconnect_handler;
+pub(crate) mod idle_handler;
 pub(crate) mod inactivity_handler;
+pub(crate) mod preload_handler;
 pub(crate) mod queue_handler;
 pub(crate) mod reconnect_handler;
+pub(crate) mod segment_skip_handler;
 
 pub(crate) use disconnect_handler::DisconnectHandler;
+pub(crate) use idle_handler::IdleHandler;
 pub(crate) use inactivity_handler::InactivityHandler;
+pub(crate) use preload_handler::PreloadHandler;
 pub(crate) use queue_handler::QueueHandler;
 pub(crate) use reconnect_handler::ReconnectHandler;
+pub(crate) use segment_skip_handler::SegmentSkipHandler;
@@ -6,16 +6,16 @@ use songbird::{
 };
 
 use std::sync::Arc;
-use tokio::sync::RwLock;
 
-use log::debug;
+use log::{debug, error};
 
-use crate::client_state::ClientStateMap;
+use crate::{client_state::ClientStateMap, utils::announcer::Announcer};
 
 pub(crate) struct DisconnectHandler {
-    pub(crate) client_state_map: Arc<RwLock<ClientStateMap>>,
+    pub(crate) client_state_map: Arc<ClientStateMap>,
     pub(crate) manager: Arc<Songbird>,
     pub(crate) guild: Guild,
+    pub(crate) announcer: Announcer,
 }
 
 #[async_trait]
@@ -23,11 +23,34 @@ impl EventHandler for DisconnectHandler {
     async fn act(&self, _: &EventContext<'_>) -> Option<Event> {
         debug!("Disconnect Handler fired.");
 
-        let mut client_map = self.client_state_map.write().await;
-
-        if client_map.get(self.guild.id.as_u64()).is_some() {
-            self.manager.remove(self.guild.id).await.unwrap();
-            client_map.remove(self.guild.id.as_u64()).unwrap();
+        if let Some(client_state) = self.client_state_map.get(self.guild.id.as_u64()).await {
+            let channel = self.announcer.channel(Some(&client_state));
+
+            self.manager
+                .remove(self.guild.id)
+                .await
+                .unwrap_or_else(|err| {
+                    error!(
+                        "Could not leave the channel for gid: {}. Error: {err:?}",
+                        self.guild.id
+                    );
+                });
+            self.client_state_map
+                .remove(self.guild.id.as_u64())
+                .unwrap_or_else(|err| {
+                    error!(
+                        "Could not remove the client state for gid: {}. Error: {err:?}",
+                        self.guild.id
+                    );
+                });
+
+            self.announcer
+                .send(
+                    channel,
+                    "Lost the connection to the voice channel. Use `/play` to start again."
+                        .to_string(),
+                )
+                .await;
         }
 
         None
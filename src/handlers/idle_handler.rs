@@ -0,0 +1,105 @@
+use poise::serenity_prelude::GuildId;
+use serenity::async_trait;
+use songbird::{
+    events::{Event, EventContext, EventHandler},
+    Songbird,
+};
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use log::{debug, error};
+
+use crate::{
+    client_state::{ClientStateMap, PlaybackState},
+    settings::SettingsStore,
+    utils::announcer::Announcer,
+};
+
+/// Leaves the voice channel once nothing has played for the guild's idle timeout.
+pub(crate) struct IdleHandler {
+    pub(crate) client_state_map: Arc<ClientStateMap>,
+    pub(crate) manager: Arc<Songbird>,
+    pub(crate) settings: Arc<SettingsStore>,
+    pub(crate) guild_id: GuildId,
+    pub(crate) idle_since: Mutex<Option<Instant>>,
+    pub(crate) announcer: Announcer,
+}
+
+impl IdleHandler {
+    pub(crate) const PERIOD: Duration = Duration::from_secs(15);
+}
+
+#[async_trait]
+impl EventHandler for IdleHandler {
+    async fn act(&self, _: &EventContext<'_>) -> Option<Event> {
+        // A paused track counts as idle, so the bot doesn't hold the channel forever.
+        let is_playing = match self
+            .client_state_map
+            .with(self.guild_id.as_u64(), |client_state| client_state.playback)
+            .await
+        {
+            Ok(playback) => !matches!(playback, PlaybackState::Idle | PlaybackState::Paused),
+            Err(_) => return Some(Event::Cancel),
+        };
+
+        let idle_for = {
+            let mut idle_since = self.idle_since.lock().unwrap();
+            if is_playing {
+                *idle_since = None;
+                return None;
+            }
+
+            idle_since.get_or_insert_with(Instant::now).elapsed()
+        };
+
+        let timeout = self.settings.get(self.guild_id).idle_timeout;
+        if timeout.is_zero() || idle_for < timeout {
+            return None;
+        }
+
+        debug!(
+            "Leaving gid: {} after {idle_for:?} without playback.",
+            self.guild_id
+        );
+
+        let mut client_state = self.client_state_map.get(self.guild_id.as_u64()).await;
+        let channel = self.announcer.channel(client_state.as_ref());
+        if let Some(client_state) = &mut client_state {
+            client_state.cancel_playlist_imports();
+        }
+
+        self.manager
+            .remove(self.guild_id)
+            .await
+            .unwrap_or_else(|err| {
+                error!(
+                    "Could not leave the channel for gid: {}. Error: {err:?}",
+                    self.guild_id
+                );
+            });
+
+        self.client_state_map
+            .remove(self.guild_id.as_u64())
+            .unwrap_or_else(|err| {
+                error!(
+                    "Could not remove the client state for gid: {}. Error: {err:?}",
+                    self.guild_id
+                );
+            });
+
+        self.announcer
+            .send(
+                channel,
+                format!(
+                    "Left the voice channel after {} minutes without playback.",
+                    timeout.as_secs() / 60
+                ),
+            )
+            .await;
+
+        Some(Event::Cancel)
+    }
+}
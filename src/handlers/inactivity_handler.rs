@@ -6,29 +6,27 @@ use songbird::{
 };
 
 use std::sync::Arc;
-use tokio::sync::RwLock;
 
-use crate::client_state::ClientStateMap;
+use crate::{client_state::ClientStateMap, utils::announcer::Announcer};
 
 use log::{debug, error};
 
 pub(crate) struct InactivityHandler {
     pub(crate) cache: Arc<Cache>,
-    pub(crate) client_state_map: Arc<RwLock<ClientStateMap>>,
+    pub(crate) client_state_map: Arc<ClientStateMap>,
     pub(crate) guild: Guild,
     pub(crate) manager: Arc<Songbird>,
+    pub(crate) announcer: Announcer,
 }
 
 #[async_trait]
 impl EventHandler for InactivityHandler {
     async fn act(&self, _: &EventContext<'_>) -> Option<Event> {
         let guild_id = self.guild.id;
-        let mut client_map = self.client_state_map.write().await;
-
         debug!("Inactivity hander acting");
 
         if let (Some(client_state), Some(guild)) = (
-            client_map.get(guild_id.as_u64()),
+            self.client_state_map.get(guild_id.as_u64()).await,
             self.cache.guild(guild_id),
         ) {
             if let Some(channel_id) = client_state.current_channel {
@@ -44,13 +42,22 @@ impl EventHandler for InactivityHandler {
                     .count();
 
                 if member_count == 0 {
+                    let channel = self.announcer.channel(Some(&client_state));
+
                     self.manager.remove(guild_id).await.unwrap_or_else(|err| {
                         error!("Could not leave the channel for gid: {guild_id}. Error: {err:?}");
                     });
 
-                    client_map.remove(guild_id.as_u64()).unwrap_or_else(|err| {
+                    self.client_state_map.remove(guild_id.as_u64()).unwrap_or_else(|err| {
                         error!("Could not update the client state map after gid {guild_id} removed. Error: {err:?}");
                     });
+
+                    self.announcer
+                        .send(
+                            channel,
+                            "Everyone left the voice channel, so I did too.".to_string(),
+                        )
+                        .await;
                 }
             }
         }
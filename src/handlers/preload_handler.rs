@@ -0,0 +1,157 @@
+use log::{debug, error};
+use serenity::{async_trait, prelude::Mutex};
+use songbird::{
+    events::{Event, EventContext, EventHandler},
+    input::{Input, Restartable},
+    tracks::TrackHandle,
+};
+
+use std::{sync::Arc, time::Duration};
+use tokio::task::JoinHandle;
+
+use crate::{handlers::QueueHandler, metrics};
+
+/// A queue element whose audio source is resolved ahead of its turn.
+#[derive(Debug)]
+pub(crate) struct PreloadedTrack {
+    pub(crate) id: String,
+    pub(crate) input: JoinHandle<Option<Input>>,
+}
+
+pub(crate) type PreloadSlot = Arc<Mutex<Option<PreloadedTrack>>>;
+
+/// Watches the current track's position. Shortly before the track ends, the head
+/// of the queue is resolved in the background. If the guild has crossfading enabled,
+/// the next track is started early and the two tracks are faded into each other.
+pub(crate) struct PreloadHandler {
+    pub(crate) queue_handler: QueueHandler,
+}
+
+impl PreloadHandler {
+    pub(crate) const PERIOD: Duration = Duration::from_millis(500);
+
+    /// How long before the end of a track the next queue element starts resolving.
+    const LEAD: Duration = Duration::from_secs(15);
+
+    const FADE_STEP: Duration = Duration::from_millis(100);
+
+    async fn preload(&self) {
+        let mut preloaded = self.queue_handler.preloaded.lock().await;
+        if preloaded.is_some() {
+            return;
+        }
+
+        let next = self
+            .queue_handler
+            .client_state_map
+            .with(self.queue_handler.guild_id.as_u64(), |client_state| {
+                client_state
+                    .song_queue
+                    .as_ref()
+                    .and_then(|song_queue| song_queue.first().cloned())
+            })
+            .await
+            .ok()
+            .flatten();
+
+        if let Some(next) = next {
+            debug!("Preloading {} - {}.", next.title, next.url);
+
+            let url = next.url.clone();
+            *preloaded = Some(PreloadedTrack {
+                id: next.id,
+                input: tokio::spawn(async move {
+                    metrics::YTDLP_SECONDS
+                        .time(&["audio"], Restartable::ytdl(url.clone(), false))
+                        .await
+                        .map(Input::from)
+                        .inspect_err(|err| {
+                            error!("Could not preload source for {url}. Error: {err:?}");
+                        })
+                        .ok()
+                }),
+            });
+        }
+    }
+
+    async fn crossfade(&self, outgoing: TrackHandle, fade: Duration) {
+        // Fading in a source that is still resolving would leave a gap regardless.
+        let ready = self
+            .queue_handler
+            .preloaded
+            .lock()
+            .await
+            .as_ref()
+            .is_some_and(|preloaded| preloaded.input.is_finished());
+
+        if !ready {
+            debug!("Next track is not ready, skipping crossfade.");
+            return;
+        }
+
+        let gain = self
+            .queue_handler
+            .settings
+            .get(self.queue_handler.guild_id)
+            .gain();
+
+        if let Some(incoming) = self.queue_handler.play_next(0.0).await {
+            debug!("Crossfading into {:?}.", incoming.metadata().title);
+
+            tokio::spawn(async move {
+                let steps = (fade.as_millis() / Self::FADE_STEP.as_millis()).max(1) as u32;
+
+                for step in 1..=steps {
+                    let ratio = step as f32 / steps as f32;
+
+                    if outgoing.set_volume(gain * (1.0 - ratio)).is_err()
+                        || incoming.set_volume(gain * ratio).is_err()
+                    {
+                        break;
+                    }
+
+                    tokio::time::sleep(fade / steps).await;
+                }
+
+                let _ = incoming.set_volume(gain);
+                let _ = outgoing.stop();
+            });
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for PreloadHandler {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        let (state, t_handle) = match ctx {
+            EventContext::Track(&[(state, t_handle)]) => (state, t_handle),
+            _ => return None,
+        };
+
+        // Livestreams have no known end to prepare for.
+        let duration = match t_handle.metadata().duration {
+            Some(duration) => duration,
+            None => return Some(Event::Cancel),
+        };
+
+        let crossfade = self
+            .queue_handler
+            .settings
+            .get(self.queue_handler.guild_id)
+            .crossfade;
+
+        let remaining = duration.saturating_sub(state.position);
+
+        if remaining <= Self::LEAD + crossfade.unwrap_or_default() {
+            self.preload().await;
+        }
+
+        match crossfade {
+            Some(fade) if remaining <= fade => {
+                self.crossfade(t_handle.clone(), fade).await;
+                Some(Event::Cancel)
+            }
+            _ => None,
+        }
+    }
+}
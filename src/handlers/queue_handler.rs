@@ -1,80 +1,443 @@
-use log::{debug, error};
-use songbird::{events::Event, events::EventContext, events::EventHandler, Call, TrackEvent};
-
-use serenity::{
-    async_trait,
-    prelude::{Mutex, RwLock},
+use log::{debug, error, warn};
+use songbird::{
+    events::Event,
+    events::EventContext,
+    events::EventHandler,
+    input::{Input, Restartable},
+    tracks::{create_player, PlayMode, TrackHandle},
+    Call, TrackEvent,
 };
 
+use serenity::{async_trait, prelude::Mutex};
+
 use poise::serenity_prelude::GuildId;
-use std::sync::Arc;
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
-use crate::client_state::{ClientState, ClientStateMap};
+use crate::{
+    client_state::{ClientStateError, ClientStateMap, PlaybackState, QueueElement},
+    handlers::{preload_handler::PreloadSlot, PreloadHandler, SegmentSkipHandler},
+    metrics,
+    settings::SettingsStore,
+    utils::{
+        self, announcer::Announcer, segment_skip::SegmentProvider,
+        source_retriever::youtube::YouTubeClient,
+    },
+};
 
+#[derive(Clone)]
 pub(crate) struct QueueHandler {
     pub(crate) guild_id: GuildId,
     pub(crate) handler: Arc<Mutex<Call>>,
-    pub(crate) client_state_map: Arc<RwLock<ClientStateMap>>,
+    pub(crate) announcer: Announcer,
+    pub(crate) client_state_map: Arc<ClientStateMap>,
+    pub(crate) preloaded: PreloadSlot,
+    pub(crate) failures: Arc<AtomicUsize>,
+    /// `None` when segment skipping is disabled for the whole bot.
+    pub(crate) segment_provider: Option<Arc<SegmentProvider>>,
+    pub(crate) settings: Arc<SettingsStore>,
+    pub(crate) youtube_client: Arc<dyn YouTubeClient>,
+    /// The ids of recently started tracks, which autoplay avoids repeating.
+    pub(crate) history: Arc<std::sync::Mutex<VecDeque<String>>>,
 }
 
-#[async_trait]
-impl EventHandler for QueueHandler {
-    async fn act(&self, _: &EventContext<'_>) -> Option<Event> {
-        let mut client_map = self.client_state_map.write().await;
-        let client_state = client_map.get(self.guild_id.as_u64()).cloned().unwrap();
-        let song_queue = client_state.song_queue.as_ref().unwrap();
-
-        debug!("{client_state:?}");
-        debug!("{song_queue:?}");
-
-        if song_queue.len() == 0 {
-            client_map
-                .update(
-                    self.guild_id.as_u64(),
-                    &mut ClientState {
-                        is_playing: false,
-                        current_track: None,
-                        ..client_state.clone()
-                    },
-                )
-                .unwrap();
-
-            return None;
+impl QueueHandler {
+    const MAX_CONSECUTIVE_FAILURES: usize = 3;
+
+    /// How far short of its reported duration a track may end before it is considered failed.
+    const END_TOLERANCE: Duration = Duration::from_secs(5);
+
+    const HISTORY_SIZE: usize = 50;
+
+    /// Registers the queue, preload and segment skip listeners on a newly started track.
+    pub(crate) fn attach(&self, t_handle: &TrackHandle, element: &QueueElement) {
+        {
+            let mut history = self.history.lock().unwrap();
+            history.push_back(element.id.clone());
+            if history.len() > Self::HISTORY_SIZE {
+                history.pop_front();
+            }
         }
 
-        let next = song_queue.first().unwrap();
+        let _ = t_handle
+            .add_event(Event::Track(TrackEvent::End), self.clone())
+            .inspect_err(|err| {
+                error!("Failed to add event listener for track end. Error: {err:?}");
+            });
+
+        let _ = t_handle
+            .add_event(
+                Event::Periodic(PreloadHandler::PERIOD, None),
+                PreloadHandler {
+                    queue_handler: self.clone(),
+                },
+            )
+            .inspect_err(|err| {
+                error!("Failed to add event listener for track preloading. Error: {err:?}");
+            });
 
-        let t_handle = self.handler.lock().await.play_source(
-            songbird::input::Restartable::ytdl(next.url.clone(), true)
-                .await
-                .unwrap()
-                .into(),
-        );
-
-        let mut updated_state = ClientState {
-            is_playing: true,
-            current_track: Some(t_handle.clone()),
-            song_queue: Some(song_queue.clone().into_iter().skip(1).collect()),
-            ..client_state.clone()
+        if !element.live {
+            if let Some(segment_provider) = &self.segment_provider {
+                tokio::spawn(self.clone().attach_segment_skip(
+                    segment_provider.clone(),
+                    t_handle.clone(),
+                    element.id.clone(),
+                ));
+            }
+        }
+    }
+
+    /// Looks up the track's skippable segments and, if there are any, starts skipping them.
+    async fn attach_segment_skip(
+        self,
+        segment_provider: Arc<SegmentProvider>,
+        t_handle: TrackHandle,
+        video_id: String,
+    ) {
+        if !self.settings.get(self.guild_id).segment_skip.enabled {
+            return;
+        }
+
+        let segments = match segment_provider.segments(&video_id).await {
+            Ok(segments) if !segments.is_empty() => segments,
+            Ok(_) => return,
+            Err(err) => {
+                warn!("Could not look up segments of {video_id}. Error: {err}");
+                return;
+            }
         };
 
-        client_map
-            .update(self.guild_id.as_u64(), &mut updated_state)
-            .unwrap();
+        debug!("Found {} skippable segments in {video_id}.", segments.len());
 
         let _ = t_handle
             .add_event(
-                Event::Track(TrackEvent::End),
-                Self {
-                    client_state_map: self.client_state_map.to_owned(),
-                    guild_id: self.guild_id.to_owned(),
-                    handler: self.handler.clone(),
+                Event::Periodic(SegmentSkipHandler::PERIOD, None),
+                SegmentSkipHandler {
+                    guild_id: self.guild_id,
+                    settings: self.settings.clone(),
+                    segments,
                 },
             )
-            .map_err(|err| {
-                error!("Failed to add event listener for track end. Error: {err:?}");
-                err
+            .inspect_err(|err| {
+                error!("Failed to add event listener for segment skipping. Error: {err:?}");
+            });
+    }
+
+    /// Returns the audio source for the given queue element.
+    /// A source preloaded for the same element is used if available,
+    /// otherwise a new source is resolved.
+    async fn input_for(&self, element: &QueueElement) -> Option<Input> {
+        let preloaded = self.preloaded.lock().await.take();
+
+        if let Some(preloaded) = preloaded {
+            if preloaded.id == element.id {
+                debug!("Using preloaded source for {}.", element.id);
+                match preloaded.input.await {
+                    Ok(Some(input)) => return Some(input),
+                    _ => debug!("Preloading {} failed, retrying.", element.id),
+                }
+            } else {
+                preloaded.input.abort();
+            }
+        }
+
+        metrics::YTDLP_SECONDS
+            .time(&["audio"], Restartable::ytdl(element.url.clone(), true))
+            .await
+            .map(Input::from)
+            .inspect_err(|err| {
+                error!(
+                    "Could not resolve source for {}. Error: {err:?}",
+                    element.url
+                );
+            })
+            .ok()
+    }
+
+    /// Queues a track related to the last one from its YouTube mix, if the queue ran out
+    /// and the guild enabled autoplay.
+    async fn autoplay(&self) {
+        if !self.settings.get(self.guild_id).autoplay {
+            return;
+        }
+
+        let seed = self
+            .client_state_map
+            .with(self.guild_id.as_u64(), |client_state| {
+                let empty = client_state
+                    .song_queue
+                    .as_ref()
+                    .is_none_or(|song_queue| song_queue.is_empty());
+
+                empty.then(|| client_state.now_playing.clone()).flatten()
+            })
+            .await;
+
+        let seed = match seed {
+            Ok(Some(seed)) => seed,
+            _ => return,
+        };
+
+        let items = match self
+            .youtube_client
+            .mix(&format!("RD{}", seed.id), Some(&seed.id))
+            .await
+        {
+            Ok(Some((_, items))) => items,
+            Ok(None) => return,
+            Err(err) => {
+                warn!(
+                    "Could not find related tracks for {}. Error: {err}",
+                    seed.id
+                );
+                return;
+            }
+        };
+
+        let next = {
+            let history = self.history.lock().unwrap();
+            items
+                .into_iter()
+                .find(|item| !item.live && !history.contains(&item.id))
+        };
+
+        if let Some(next) = next {
+            debug!("Autoplaying {} after {}.", next.id, seed.id);
+
+            self.client_state_map
+                .with(self.guild_id.as_u64(), |client_state| {
+                    client_state
+                        .song_queue
+                        .get_or_insert_with(Vec::new)
+                        .push(next);
+                })
+                .await
+                .unwrap_or_else(|err| {
+                    error!(
+                        "Could not update the client state for gid: {}. Error: {err:?}",
+                        self.guild_id
+                    );
+                });
+        }
+    }
+
+    /// Removes and returns the element at the head of the queue with its load number, marking
+    /// the client as loading. If the queue is empty, the client is marked as idle. Fails if
+    /// the client is in a state that doesn't advance the queue, such as paused.
+    async fn pop_next(&self) -> Result<Option<(QueueElement, u64)>, ClientStateError> {
+        self.client_state_map
+            .with(self.guild_id.as_u64(), |client_state| {
+                debug!("{client_state:?}");
+
+                let song_queue = client_state.song_queue.get_or_insert_with(Vec::new);
+                let next = (!song_queue.is_empty()).then(|| song_queue[0].clone());
+
+                client_state.transition(match next {
+                    Some(_) => PlaybackState::Loading,
+                    None => PlaybackState::Idle,
+                })?;
+
+                match next {
+                    Some(_) => {
+                        client_state
+                            .song_queue
+                            .as_mut()
+                            .map(|queue| queue.remove(0));
+                    }
+                    None => {
+                        client_state.current_track = None;
+                        client_state.now_playing = None;
+                    }
+                }
+
+                Ok(next.map(|next| (next, client_state.load)))
+            })
+            .await?
+    }
+
+    /// Marks the client as idle, leaving the remaining queue untouched.
+    async fn give_up(&self) {
+        self.client_state_map
+            .with(self.guild_id.as_u64(), |client_state| {
+                client_state.playback = PlaybackState::Idle;
+                client_state.current_track = None;
+                client_state.now_playing = None;
+            })
+            .await
+            .unwrap_or_else(|err| {
+                error!(
+                    "Could not update the client state for gid: {}. Error: {err:?}",
+                    self.guild_id
+                );
             });
+    }
+
+    /// Starts the element at the head of the queue at the given volume,
+    /// returning its handle. Elements that cannot be resolved are skipped until
+    /// too many consecutive failures occur. If the queue is empty, the client is marked as idle.
+    pub(crate) async fn play_next(&self, volume: f32) -> Option<TrackHandle> {
+        loop {
+            if self.failures.load(Ordering::SeqCst) >= Self::MAX_CONSECUTIVE_FAILURES {
+                warn!("Too many consecutive failures in gid: {}.", self.guild_id);
+                self.failures.store(0, Ordering::SeqCst);
+                self.give_up().await;
+                self.announcer.announce(format!(
+                    "{} tracks in a row failed to play. Stopping playback, use `/play` to try again.",
+                    Self::MAX_CONSECUTIVE_FAILURES
+                ))
+                .await;
+                return None;
+            }
+
+            self.autoplay().await;
+            let (next, load) = match self.pop_next().await {
+                Ok(Some(next)) => next,
+                Err(err) => {
+                    warn!(
+                        "Not advancing the queue of gid: {}. Error: {err}",
+                        self.guild_id
+                    );
+                    return None;
+                }
+                Ok(None) => {
+                    self.announcer
+                        .announce("The queue has ended.".to_string())
+                        .await;
+                    return None;
+                }
+            };
+
+            let input = match self.input_for(&next).await {
+                Some(input) => input,
+                None => {
+                    self.failures.fetch_add(1, Ordering::SeqCst);
+                    self.announcer
+                        .announce(format!(
+                            "Could not play {} by {}. Skipping to the next track.",
+                            utils::decode_html_encoded_string(&next.title),
+                            utils::decode_html_encoded_string(&next.channel_name),
+                        ))
+                        .await;
+                    continue;
+                }
+            };
+
+            if let Err(reason) = self
+                .settings
+                .get(self.guild_id)
+                .check_length(input.metadata.duration)
+            {
+                self.announcer
+                    .announce(format!(
+                        "Skipped {} by {}. {reason}",
+                        utils::decode_html_encoded_string(&next.title),
+                        utils::decode_html_encoded_string(&next.channel_name),
+                    ))
+                    .await;
+                continue;
+            }
+
+            let (mut track, t_handle) = create_player(input);
+            track.set_volume(volume);
+            if let Some(start) = next.start {
+                if let Err(err) = track.seek_time(start) {
+                    warn!("Could not start {} at {start:?}. Error: {err:?}", next.url);
+                }
+            }
+
+            // Playback may have been stopped while the element was loading.
+            let (current_track, now_playing) = (t_handle.clone(), next.clone());
+            let started = self
+                .client_state_map
+                .with(self.guild_id.as_u64(), move |client_state| {
+                    client_state.finish_load(load)?;
+                    client_state.current_track = Some(current_track);
+                    client_state.now_playing = Some(now_playing);
+                    Ok::<_, ClientStateError>(())
+                })
+                .await
+                .and_then(|started| started);
+
+            if let Err(err) = started {
+                debug!("Dropping {} after loading. Error: {err}", next.url);
+                return None;
+            }
+            self.handler.lock().await.play(track);
+            metrics::TRACKS_PLAYED.inc(&[]);
+
+            self.attach(&t_handle, &next);
+
+            self.announcer
+                .announce(format!(
+                    "Now playing: {} by {}.\n<{}>",
+                    utils::decode_html_encoded_string(&next.title),
+                    utils::decode_html_encoded_string(&next.channel_name),
+                    next.url
+                ))
+                .await;
+
+            return Some(t_handle);
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for QueueHandler {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        if let EventContext::Track(&[(state, ended)]) = ctx {
+            // A track that was faded out has already been replaced by the next element,
+            // and a stopped track has no successor.
+            let current = self
+                .client_state_map
+                .with(self.guild_id.as_u64(), |client_state| {
+                    client_state
+                        .current_track
+                        .as_ref()
+                        .map(|t_handle| t_handle.uuid())
+                })
+                .await
+                .ok()
+                .flatten();
+
+            if current.is_none_or(|uuid| uuid != ended.uuid()) {
+                return None;
+            }
+
+            // Songbird ends a track whose source errors, rather than stopping it.
+            let metadata = ended.metadata();
+            let failed = state.playing == PlayMode::End
+                && metadata
+                    .duration
+                    .is_some_and(|duration| state.position + Self::END_TOLERANCE < duration);
+
+            if failed {
+                error!(
+                    "Playback of {:?} ended early at {:?}.",
+                    metadata.source_url, state.position
+                );
+                self.failures.fetch_add(1, Ordering::SeqCst);
+                self.announcer
+                    .announce(format!(
+                        "Playback of {} failed. Skipping to the next track.",
+                        metadata.title.as_deref().map_or_else(
+                            || "the current track".into(),
+                            utils::decode_html_encoded_string
+                        )
+                    ))
+                    .await;
+            } else {
+                self.failures.store(0, Ordering::SeqCst);
+            }
+        }
+
+        self.play_next(self.settings.get(self.guild_id).gain())
+            .await;
 
         None
     }
@@ -3,14 +3,13 @@ use serenity::async_trait;
 use songbird::events::{Event, EventContext, EventHandler};
 
 use std::sync::Arc;
-use tokio::sync::RwLock;
 
-use log::info;
+use log::{error, info};
 
-use crate::client_state::{ClientState, ClientStateMap};
+use crate::client_state::{ClientState, ClientStateMap, PlaybackState};
 
 pub(crate) struct ReconnectHandler {
-    pub(crate) client_state_map: Arc<RwLock<ClientStateMap>>,
+    pub(crate) client_state_map: Arc<ClientStateMap>,
     pub(crate) guild: Guild,
 }
 
@@ -18,25 +17,33 @@ pub(crate) struct ReconnectHandler {
 impl EventHandler for ReconnectHandler {
     async fn act(&self, ev: &EventContext<'_>) -> Option<Event> {
         info!("Reconnect Handler fired.");
-        let mut client_state_map = self.client_state_map.write().await;
 
         let ev_data = match ev {
             songbird::events::EventContext::DriverConnect(ev_data) => ev_data,
             _ => return None,
         };
 
-        if client_state_map.get(self.guild.id.as_u64()).is_none() {
-            client_state_map
+        if !self.client_state_map.contains_key(self.guild.id.as_u64()) {
+            self.client_state_map
                 .insert(
                     self.guild.id.as_u64(),
-                    &mut ClientState {
-                        is_playing: false,
+                    ClientState {
+                        playback: PlaybackState::Idle,
                         song_queue: Some(vec![]),
                         current_track: None,
-                        current_channel: ev_data.channel_id.and_then(|cid| Some(cid.0)),
+                        now_playing: None,
+                        current_channel: ev_data.channel_id.map(|cid| cid.0),
+                        text_channel: None,
+                        playlist_imports: vec![],
+                        load: 0,
                     },
                 )
-                .unwrap();
+                .unwrap_or_else(|err| {
+                    error!(
+                        "Could not restore the client state for gid: {}. Error: {err:?}",
+                        self.guild.id
+                    );
+                });
         }
 
         None
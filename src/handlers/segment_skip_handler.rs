@@ -0,0 +1,58 @@
+use log::{debug, error};
+use poise::serenity_prelude::GuildId;
+use serenity::async_trait;
+use songbird::events::{Event, EventContext, EventHandler};
+
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    settings::SettingsStore,
+    utils::segment_skip::{self, Segment},
+};
+
+/// Watches the current track's position and jumps over segments in the categories
+/// the guild chose to skip.
+pub(crate) struct SegmentSkipHandler {
+    pub(crate) guild_id: GuildId,
+    pub(crate) settings: Arc<SettingsStore>,
+    pub(crate) segments: Vec<Segment>,
+}
+
+impl SegmentSkipHandler {
+    pub(crate) const PERIOD: Duration = Duration::from_millis(500);
+
+    /// Segments ending sooner than this after the current position are not worth a seek.
+    const MIN_SKIP: Duration = Duration::from_secs(1);
+}
+
+#[async_trait]
+impl EventHandler for SegmentSkipHandler {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        let (state, t_handle) = match ctx {
+            EventContext::Track(&[(state, t_handle)]) => (state, t_handle),
+            _ => return None,
+        };
+
+        let segment_skip = self.settings.get(self.guild_id).segment_skip;
+
+        if !segment_skip.enabled {
+            return None;
+        }
+
+        let target =
+            segment_skip::skip_target(&self.segments, &segment_skip.categories, state.position)
+                .filter(|target| *target >= state.position + Self::MIN_SKIP)?;
+
+        debug!(
+            "Skipping from {:?} to {target:?} in {:?}.",
+            state.position,
+            t_handle.metadata().source_url
+        );
+
+        let _ = t_handle
+            .seek_time(target)
+            .inspect_err(|err| error!("Could not skip segment. Error: {err:?}"));
+
+        None
+    }
+}
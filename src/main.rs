@@ -1,9 +1,15 @@
+#[cfg(feature = "api")]
+pub(crate) mod api;
 pub(crate) mod checks;
 pub(crate) mod client_state;
 pub(crate) mod commands;
 pub(crate) mod config;
+pub(crate) mod control;
+pub(crate) mod error;
 pub(crate) mod framework;
 pub(crate) mod handlers;
+pub(crate) mod metrics;
+pub(crate) mod settings;
 pub(crate) mod utils;
 
 use ::config::{Config, File, FileFormat};
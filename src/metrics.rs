@@ -0,0 +1,185 @@
+use hyper::{
+    header,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use log::{error, info};
+use songbird::Songbird;
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+
+use crate::client_state::ClientStateMap;
+
+pub(crate) mod registry;
+
+use registry::{Family, Histogram};
+
+pub(crate) static COMMANDS: Family<u64> = Family::new(
+    "luna_commands_total",
+    "Slash commands invoked, by command.",
+    &["command"],
+);
+
+pub(crate) static COMMAND_FAILURES: Family<u64> = Family::new(
+    "luna_command_failures_total",
+    "Slash commands that failed or were rejected by a check, by command.",
+    &["command"],
+);
+
+pub(crate) static TRACKS_PLAYED: Family<u64> =
+    Family::new("luna_tracks_played_total", "Tracks started.", &[]);
+
+pub(crate) static YOUTUBE_API_CALLS: Family<u64> = Family::new(
+    "luna_youtube_api_calls_total",
+    "Requests to the YouTube Data API, by method and outcome.",
+    &["method", "outcome"],
+);
+
+pub(crate) static YOUTUBE_API_SECONDS: Family<Histogram> = Family::new(
+    "luna_youtube_api_request_seconds",
+    "Latency of YouTube Data API requests, by method.",
+    &["method"],
+);
+
+pub(crate) static YTDLP_SECONDS: Family<Histogram> = Family::new(
+    "luna_ytdlp_spawn_seconds",
+    "Time until yt-dlp returned metadata or started streaming audio.",
+    &["purpose"],
+);
+
+/// Times a YouTube Data API request and counts it by outcome.
+pub(crate) async fn youtube_api<T, E>(
+    method: &str,
+    request: impl std::future::Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    let result = YOUTUBE_API_SECONDS.time(&[method], request).await;
+    let outcome = if result.is_ok() { "ok" } else { "error" };
+    YOUTUBE_API_CALLS.inc(&[method, outcome]);
+    result
+}
+
+/// Serves the metrics for Prometheus to scrape at `/metrics`.
+pub(crate) struct Metrics {
+    pub(crate) client_state_map: Arc<ClientStateMap>,
+    pub(crate) songbird: Arc<Songbird>,
+}
+
+impl Metrics {
+    /// Spawns the server. Failing to bind is logged, the bot runs on without metrics.
+    pub(crate) fn serve(self, address: SocketAddr) {
+        let metrics = Arc::new(self);
+
+        let make_service = make_service_fn(move |_| {
+            let metrics = metrics.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let metrics = metrics.clone();
+                    async move { Ok::<_, Infallible>(metrics.handle(request).await) }
+                }))
+            }
+        });
+
+        tokio::spawn(async move {
+            let server = match Server::try_bind(&address) {
+                Ok(server) => server,
+                Err(err) => {
+                    error!("Could not serve metrics on {address}. Error: {err}");
+                    return;
+                }
+            };
+
+            info!("Serving metrics on {address}.");
+            if let Err(err) = server.serve(make_service).await {
+                error!("The metrics server stopped. Error: {err}");
+            }
+        });
+    }
+
+    async fn handle(&self, request: Request<Body>) -> Response<Body> {
+        if request.method() != Method::GET || request.uri().path() != "/metrics" {
+            return Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(Body::empty())
+                .unwrap_or_default();
+        }
+
+        Response::builder()
+            .header(header::CONTENT_TYPE, "text/plain; version=0.0.4")
+            .body(Body::from(self.render().await))
+            .unwrap_or_default()
+    }
+
+    /// The counters and histograms, plus gauges read from the current state.
+    async fn render(&self) -> String {
+        let mut out = String::new();
+        let mut connections = 0;
+        let mut queues = vec![];
+
+        for guild_id in self.client_state_map.ids() {
+            if let Some(call) = self.songbird.get(guild_id) {
+                if call.lock().await.current_connection().is_some() {
+                    connections += 1;
+                }
+            }
+
+            if let Some(client_state) = self.client_state_map.get(&guild_id).await {
+                queues.push((
+                    guild_id,
+                    client_state.song_queue.map_or(0, |queue| queue.len()),
+                ));
+            }
+        }
+        queues.sort();
+
+        registry::header(
+            &mut out,
+            "luna_voice_connections",
+            "Established voice connections.",
+            "gauge",
+        );
+        registry::sample(&mut out, "luna_voice_connections", "", connections);
+
+        registry::header(
+            &mut out,
+            "luna_queue_length",
+            "Tracks waiting in the queue, by guild.",
+            "gauge",
+        );
+        for (guild_id, length) in queues {
+            registry::sample(
+                &mut out,
+                "luna_queue_length",
+                &registry::labels(&["guild"], &[guild_id.to_string()], None),
+                length,
+            );
+        }
+
+        COMMANDS.render(&mut out);
+        COMMAND_FAILURES.render(&mut out);
+        TRACKS_PLAYED.render(&mut out);
+        YOUTUBE_API_CALLS.render(&mut out);
+        YOUTUBE_API_SECONDS.render(&mut out);
+        YTDLP_SECONDS.render(&mut out);
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client_state::EventBus;
+
+    #[tokio::test]
+    async fn gauges_are_read_from_the_current_state() {
+        let metrics = Metrics {
+            client_state_map: Arc::new(ClientStateMap::new(EventBus::new())),
+            songbird: Songbird::serenity(),
+        };
+
+        let out = metrics.render().await;
+
+        assert!(out.contains("# TYPE luna_voice_connections gauge\nluna_voice_connections 0\n"));
+        assert!(out.contains("# TYPE luna_tracks_played_total counter\nluna_tracks_played_total"));
+        assert!(out.contains("# TYPE luna_ytdlp_spawn_seconds histogram\n"));
+    }
+}
@@ -0,0 +1,206 @@
+//! A minimal metrics registry rendering the Prometheus text exposition format.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    future::Future,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Upper bounds of the histogram buckets, in seconds.
+const BUCKETS: [f64; 10] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+/// Observations of a duration, counted into cumulative buckets.
+#[derive(Debug, Default)]
+pub(crate) struct Histogram {
+    buckets: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+/// A metric with one series per combination of label values.
+pub(crate) struct Family<T> {
+    name: &'static str,
+    help: &'static str,
+    labels: &'static [&'static str],
+    series: Mutex<BTreeMap<Vec<String>, T>>,
+}
+
+impl<T: Default> Family<T> {
+    pub(crate) const fn new(
+        name: &'static str,
+        help: &'static str,
+        labels: &'static [&'static str],
+    ) -> Self {
+        Family {
+            name,
+            help,
+            labels,
+            series: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn update(&self, values: &[&str], f: impl FnOnce(&mut T)) {
+        debug_assert_eq!(values.len(), self.labels.len(), "{}", self.name);
+
+        let key = values.iter().map(|value| value.to_string()).collect();
+        if let Ok(mut series) = self.series.lock() {
+            f(series.entry(key).or_default());
+        }
+    }
+}
+
+impl Family<u64> {
+    pub(crate) fn inc(&self, values: &[&str]) {
+        self.update(values, |count| *count += 1);
+    }
+
+    pub(crate) fn render(&self, out: &mut String) {
+        header(out, self.name, self.help, "counter");
+
+        if let Ok(series) = self.series.lock() {
+            // A counter without labels is reported before its first increment.
+            if series.is_empty() && self.labels.is_empty() {
+                sample(out, self.name, "", 0);
+            }
+            for (values, count) in series.iter() {
+                sample(out, self.name, &labels(self.labels, values, None), *count);
+            }
+        }
+    }
+}
+
+impl Family<Histogram> {
+    pub(crate) fn observe(&self, values: &[&str], duration: Duration) {
+        self.update(values, |histogram| {
+            histogram.observe(duration.as_secs_f64())
+        });
+    }
+
+    /// Awaits the future and observes how long it took.
+    pub(crate) async fn time<F: Future>(&self, values: &[&str], future: F) -> F::Output {
+        let start = Instant::now();
+        let output = future.await;
+        self.observe(values, start.elapsed());
+        output
+    }
+
+    pub(crate) fn render(&self, out: &mut String) {
+        header(out, self.name, self.help, "histogram");
+
+        let series = match self.series.lock() {
+            Ok(series) => series,
+            Err(_) => return,
+        };
+
+        for (values, histogram) in series.iter() {
+            let bucket = format!("{}_bucket", self.name);
+            for (count, bound) in histogram.buckets.iter().zip(BUCKETS) {
+                let le = bound.to_string();
+                sample(out, &bucket, &labels(self.labels, values, Some(&le)), count);
+            }
+            sample(
+                out,
+                &bucket,
+                &labels(self.labels, values, Some("+Inf")),
+                histogram.count,
+            );
+
+            let labels = labels(self.labels, values, None);
+            sample(out, &format!("{}_sum", self.name), &labels, histogram.sum);
+            sample(
+                out,
+                &format!("{}_count", self.name),
+                &labels,
+                histogram.count,
+            );
+        }
+    }
+}
+
+pub(crate) fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+pub(crate) fn sample(out: &mut String, name: &str, labels: &str, value: impl std::fmt::Display) {
+    let _ = writeln!(out, "{name}{labels} {value}");
+}
+
+/// Formats label pairs as `{name="value",...}`, or nothing without labels.
+/// Histogram buckets add their upper bound as `le`.
+pub(crate) fn labels<S: AsRef<str>>(names: &[&str], values: &[S], le: Option<&str>) -> String {
+    let pairs = names
+        .iter()
+        .zip(values.iter().map(AsRef::as_ref))
+        .chain(le.map(|le| (&"le", le)))
+        .map(|(name, value)| format!("{name}=\"{}\"", escape(value)))
+        .collect::<Vec<_>>();
+
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters_render_one_series_per_label_set() {
+        let family = Family::<u64>::new("luna_test_total", "A test counter.", &["command"]);
+        family.inc(&["play"]);
+        family.inc(&["play"]);
+        family.inc(&["say \"hi\"\n"]);
+
+        let mut out = String::new();
+        family.render(&mut out);
+
+        assert_eq!(
+            out,
+            "# HELP luna_test_total A test counter.\n\
+             # TYPE luna_test_total counter\n\
+             luna_test_total{command=\"play\"} 2\n\
+             luna_test_total{command=\"say \\\"hi\\\"\\n\"} 1\n"
+        );
+    }
+
+    #[test]
+    fn histograms_count_into_cumulative_buckets() {
+        let family = Family::<Histogram>::new("luna_test_seconds", "A test histogram.", &[]);
+        family.observe(&[], Duration::from_millis(300));
+        family.observe(&[], Duration::from_secs(120));
+
+        let mut out = String::new();
+        family.render(&mut out);
+
+        assert!(out.contains("luna_test_seconds_bucket{le=\"0.25\"} 0\n"));
+        assert!(out.contains("luna_test_seconds_bucket{le=\"0.5\"} 1\n"));
+        assert!(out.contains("luna_test_seconds_bucket{le=\"60\"} 1\n"));
+        assert!(out.contains("luna_test_seconds_bucket{le=\"+Inf\"} 2\n"));
+        assert!(out.contains("luna_test_seconds_sum 120.3\n"));
+        assert!(out.contains("luna_test_seconds_count 2\n"));
+    }
+}
@@ -0,0 +1,5 @@
+pub(crate) mod guild_settings;
+pub(crate) mod settings_store;
+
+pub(crate) use guild_settings::GuildSettings;
+pub(crate) use settings_store::SettingsStore;
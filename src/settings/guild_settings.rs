@@ -0,0 +1,105 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::utils::{segment_skip::SegmentSkip, webhooks::Webhook};
+
+/// The default time the bot stays in a voice channel with nothing to play.
+pub(crate) const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// A guild's preferences, set by its admins with `/settings`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct GuildSettings {
+    /// The volume tracks start at, in percent.
+    pub(crate) volume: u8,
+    /// How long the bot stays in a voice channel with nothing to play. Zero disables leaving.
+    pub(crate) idle_timeout: Duration,
+    /// Whether track transitions, disconnects and playback errors are announced.
+    pub(crate) announcements: bool,
+    /// The channel announcements are posted to. Defaults to where `/play` was last used.
+    pub(crate) announce_channel: Option<u64>,
+    /// If set, controlling playback requires this role or the Manage Server permission.
+    pub(crate) dj_role: Option<u64>,
+    pub(crate) max_queue_size: Option<usize>,
+    pub(crate) max_track_length: Option<Duration>,
+    /// Whether related tracks are queued once the queue runs out.
+    pub(crate) autoplay: bool,
+    /// How long consecutive tracks fade into each other. `None` disables crossfading.
+    pub(crate) crossfade: Option<Duration>,
+    pub(crate) segment_skip: SegmentSkip,
+    /// Endpoints that track, queue and voice channel events are posted to.
+    pub(crate) webhooks: Vec<Webhook>,
+}
+
+impl Default for GuildSettings {
+    fn default() -> Self {
+        GuildSettings {
+            volume: 100,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            announcements: true,
+            announce_channel: None,
+            dj_role: None,
+            max_queue_size: None,
+            max_track_length: None,
+            autoplay: false,
+            crossfade: None,
+            segment_skip: SegmentSkip::default(),
+            webhooks: vec![],
+        }
+    }
+}
+
+impl GuildSettings {
+    /// The volume as a songbird gain.
+    pub(crate) fn gain(&self) -> f32 {
+        f32::from(self.volume) / 100.0
+    }
+
+    /// Returns an error if a track of the given length may not be queued.
+    pub(crate) fn check_length(&self, length: Option<Duration>) -> Result<(), String> {
+        match (self.max_track_length, length) {
+            (Some(max), Some(length)) if length > max => Err(format!(
+                "Tracks may be at most {} long.",
+                crate::utils::format_duration(max)
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_fields_take_defaults() {
+        let settings: GuildSettings = serde_json::from_str(r#"{"volume": 50}"#).unwrap();
+
+        assert_eq!(
+            settings,
+            GuildSettings {
+                volume: 50,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn track_length_is_limited() {
+        let settings = GuildSettings {
+            max_track_length: Some(Duration::from_secs(600)),
+            ..Default::default()
+        };
+
+        assert!(settings
+            .check_length(Some(Duration::from_secs(600)))
+            .is_ok());
+        assert!(settings
+            .check_length(Some(Duration::from_secs(601)))
+            .is_err());
+        assert!(settings.check_length(None).is_ok());
+        assert!(GuildSettings::default()
+            .check_length(Some(Duration::from_secs(36_000)))
+            .is_ok());
+    }
+}
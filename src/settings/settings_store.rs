@@ -0,0 +1,166 @@
+use log::{error, warn};
+use poise::serenity_prelude::GuildId;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+use crate::{settings::GuildSettings, utils};
+
+/// Holds every guild's settings, saving them to a JSON file on each change if a path is given.
+#[derive(Debug)]
+pub(crate) struct SettingsStore {
+    settings: RwLock<HashMap<u64, GuildSettings>>,
+    path: Option<PathBuf>,
+    /// Serialises writes to the settings file.
+    saving: tokio::sync::Mutex<()>,
+}
+
+impl SettingsStore {
+    pub(crate) fn new(path: Option<PathBuf>) -> Self {
+        let settings = path
+            .as_deref()
+            .map(|path| {
+                load(path).unwrap_or_else(|err| {
+                    warn!("Ignoring settings. {err}");
+                    HashMap::new()
+                })
+            })
+            .unwrap_or_default();
+
+        SettingsStore {
+            settings: RwLock::new(settings),
+            path,
+            saving: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Replaces the settings with those in the file, picking up edits made while the bot runs.
+    /// Returns how many guilds have settings.
+    pub(crate) fn reload(&self) -> Result<usize, String> {
+        let path = self
+            .path
+            .as_deref()
+            .ok_or("Settings are kept in memory only.")?;
+        let settings = load(path)?;
+        let guilds = settings.len();
+
+        *self.settings.write().unwrap() = settings;
+        Ok(guilds)
+    }
+
+    /// Returns the guild's settings, or the defaults if it never changed any.
+    pub(crate) fn get(&self, guild_id: GuildId) -> GuildSettings {
+        self.settings
+            .read()
+            .unwrap()
+            .get(guild_id.as_u64())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Applies a change to the guild's settings and saves them. Returns the new settings.
+    pub(crate) async fn update(
+        &self,
+        guild_id: GuildId,
+        change: impl FnOnce(&mut GuildSettings),
+    ) -> GuildSettings {
+        let updated = {
+            let mut settings = self.settings.write().unwrap();
+            let guild_settings = settings.entry(*guild_id.as_u64()).or_default();
+            change(guild_settings);
+            guild_settings.clone()
+        };
+
+        if let Some(path) = &self.path {
+            // Snapshotting under the lock, so an older snapshot never overwrites a newer one.
+            let _saving = self.saving.lock().await;
+            let snapshot = serde_json::to_vec(&*self.settings.read().unwrap());
+            let result = match snapshot {
+                Ok(bytes) => utils::write_replacing(path, bytes)
+                    .await
+                    .map_err(|e| e.to_string()),
+                Err(err) => Err(err.to_string()),
+            };
+
+            if let Err(err) = result {
+                error!("Could not save settings to {path:?}. Error: {err}");
+            }
+        }
+
+        updated
+    }
+}
+
+/// Reads the settings file. A missing file holds no settings yet.
+fn load(path: &Path) -> Result<HashMap<u64, GuildSettings>, String> {
+    match std::fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .map_err(|err| format!("Unreadable settings file {path:?}. Error: {err}")),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+        Err(err) => Err(format!(
+            "Could not read settings file {path:?}. Error: {err}"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn settings_persist_across_restarts() {
+        let path = std::env::temp_dir().join(format!("settings-{}.json", std::process::id()));
+        let guild_id = GuildId(1);
+
+        let store = SettingsStore::new(Some(path.clone()));
+        assert_eq!(store.get(guild_id), GuildSettings::default());
+        store
+            .update(guild_id, |settings| settings.autoplay = true)
+            .await;
+
+        let restored = SettingsStore::new(Some(path.clone()));
+        let _ = std::fs::remove_file(&path);
+
+        assert!(restored.get(guild_id).autoplay);
+        assert_eq!(restored.get(GuildId(2)), GuildSettings::default());
+    }
+
+    #[tokio::test]
+    async fn concurrent_updates_save_the_latest_settings() {
+        let path = std::env::temp_dir().join(format!("concurrent-{}.json", std::process::id()));
+        let store = SettingsStore::new(Some(path.clone()));
+
+        futures::future::join_all(
+            (1..=20).map(|id| store.update(GuildId(id), |settings| settings.volume = 50)),
+        )
+        .await;
+
+        let saved = load(&path);
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(saved.unwrap().len(), 20);
+        assert!(!std::path::Path::new(&format!("{}.tmp", path.display())).exists());
+    }
+
+    #[tokio::test]
+    async fn reloading_picks_up_edits() {
+        let path = std::env::temp_dir().join(format!("reload-{}.json", std::process::id()));
+        let store = SettingsStore::new(Some(path.clone()));
+        store
+            .update(GuildId(1), |settings| settings.volume = 50)
+            .await;
+
+        std::fs::write(&path, r#"{"2": {"autoplay": true}}"#).unwrap();
+        let reloaded = store.reload();
+        std::fs::write(&path, "not json").unwrap();
+        let broken = store.reload();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(reloaded, Ok(1));
+        assert!(broken.is_err());
+        assert_eq!(store.get(GuildId(1)), GuildSettings::default());
+        assert!(store.get(GuildId(2)).autoplay);
+    }
+}
@@ -1,27 +1,138 @@
 use html_escape::decode_html_entities as decode;
+use poise::serenity_prelude::{Guild, GuildId};
 
+use std::{path::Path, time::Duration};
+
+use crate::config::{Context, Error};
+
+pub(crate) mod announcer;
 pub(crate) mod banish;
+pub(crate) mod chapters;
+pub(crate) mod playlist_import;
+pub(crate) mod presence;
+pub(crate) mod segment_skip;
 pub(crate) mod source_retriever;
 pub(crate) mod summon;
+pub(crate) mod webhooks;
 
 pub(crate) use banish::banish;
 pub(crate) use summon::summon;
 
-pub(crate) fn decode_html_encoded_string(s: &String) -> String {
-    decode(&s.clone()).to_string()
+pub(crate) fn decode_html_encoded_string(s: &str) -> String {
+    decode(s).to_string()
+}
+
+/// Returns the id of the guild the command was issued in.
+pub(crate) fn guild_id(ctx: &Context<'_>) -> Result<GuildId, Error> {
+    ctx.guild_id().ok_or_else(|| {
+        Error::UserInput("Sorry, this command can only be used in a server.".to_string())
+    })
+}
+
+/// Returns the cached guild the command was issued in.
+pub(crate) fn guild(ctx: &Context<'_>) -> Result<Guild, Error> {
+    ctx.guild().ok_or_else(|| {
+        Error::UserInput("Sorry, this command can only be used in a server.".to_string())
+    })
 }
 
-pub(crate) fn to_seconds(m: Option<u64>, s: Option<u64>) -> Option<u64> {
-    let s = if m.is_some() {
-        s.and_then(|v| (v < 60).then_some(v))
+/// Parses a timestamp given as `ss`, `mm:ss`, `hh:mm:ss` or with unit suffixes like `1h2m3s`.
+pub(crate) fn parse_timestamp(timestamp: &str) -> Option<Duration> {
+    let timestamp = timestamp.trim();
+    let number = |digits: &str| {
+        (!digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()))
+            .then(|| digits.parse::<u64>().ok())
+            .flatten()
+    };
+
+    let secs = if timestamp.is_empty() {
+        return None;
+    } else if timestamp.contains(':') {
+        let parts = timestamp
+            .split(':')
+            .map(number)
+            .collect::<Option<Vec<_>>>()?;
+
+        match parts[..] {
+            [m, s] if s < 60 => m.checked_mul(60)?.checked_add(s)?,
+            [h, m, s] if m < 60 && s < 60 => h.checked_mul(3600)?.checked_add(m * 60 + s)?,
+            _ => return None,
+        }
+    } else if timestamp.ends_with(|c: char| c.is_ascii_digit()) {
+        number(timestamp)?
     } else {
-        s
+        let mut units = ["h", "m", "s"].iter().zip([3600, 60, 1]);
+        let mut rest = timestamp;
+        let mut secs = 0u64;
+
+        while !rest.is_empty() {
+            let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+            let value = number(&rest[..digits])?;
+            let unit = &rest[digits..digits + 1];
+            let (_, factor) = units.find(|(name, _)| unit.eq_ignore_ascii_case(name))?;
+
+            secs = secs.checked_add(value.checked_mul(factor)?)?;
+            rest = &rest[digits + 1..];
+        }
+
+        secs
     };
 
-    match (m, s) {
-        (Some(m), Some(s)) => Some(m * 60 + s),
-        (Some(_), None) => None,
-        (None, Some(s)) => Some(s),
-        (None, None) => None,
+    Some(Duration::from_secs(secs))
+}
+
+/// Writes the bytes next to the file and renames them over it,
+/// so a crash mid-write leaves the previous contents intact.
+pub(crate) async fn write_replacing(path: &Path, bytes: Vec<u8>) -> std::io::Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+
+    tokio::fs::write(&temp, bytes).await?;
+    tokio::fs::rename(&temp, path).await
+}
+
+/// Formats a duration as `mm:ss`, or `h:mm:ss` if it spans an hour or more.
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+
+    match secs / 3600 {
+        0 => format!("{:02}:{:02}", secs / 60, secs % 60),
+        h => format!("{}:{:02}:{:02}", h, secs / 60 % 60, secs % 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps_are_parsed() {
+        for (timestamp, secs) in [
+            ("42", 42),
+            ("90", 90),
+            ("1:30", 90),
+            ("01:02:03", 3723),
+            ("75:00", 4500),
+            ("1m30s", 90),
+            ("2h", 7200),
+            ("1h2m3s", 3723),
+            ("45S", 45),
+        ] {
+            assert_eq!(
+                parse_timestamp(timestamp),
+                Some(Duration::from_secs(secs)),
+                "{timestamp}"
+            );
+        }
+    }
+
+    #[test]
+    fn malformed_timestamps_are_rejected() {
+        for timestamp in [
+            "", "1:60", "1:60:00", "1:2:3:4", "1:", ":30", "-5", "1m30", "30s1m", "1m1m", "1.5m",
+            "intro", "1x",
+        ] {
+            assert_eq!(parse_timestamp(timestamp), None, "{timestamp}");
+        }
     }
 }
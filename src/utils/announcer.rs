@@ -0,0 +1,59 @@
+use log::error;
+use poise::serenity_prelude::{ChannelId, GuildId, Http};
+use std::sync::Arc;
+
+use crate::{
+    client_state::{ClientState, ClientStateMap},
+    settings::SettingsStore,
+};
+
+/// Posts playback announcements to a guild's announce channel, which defaults
+/// to the text channel the guild last issued `/play` from.
+#[derive(Clone)]
+pub(crate) struct Announcer {
+    pub(crate) guild_id: GuildId,
+    pub(crate) http: Arc<Http>,
+    pub(crate) client_state_map: Arc<ClientStateMap>,
+    pub(crate) settings: Arc<SettingsStore>,
+}
+
+impl Announcer {
+    /// Returns the channel to announce in, or `None` if the guild turned announcements off.
+    /// Takes the client state for callers that already have it at hand.
+    pub(crate) fn channel(&self, client_state: Option<&ClientState>) -> Option<ChannelId> {
+        let settings = self.settings.get(self.guild_id);
+
+        if !settings.announcements {
+            return None;
+        }
+
+        settings
+            .announce_channel
+            .or_else(|| client_state.and_then(|client_state| client_state.text_channel))
+            .map(ChannelId)
+    }
+
+    pub(crate) async fn announce(&self, message: String) {
+        let client_state = self.client_state_map.get(self.guild_id.as_u64()).await;
+        let channel = self.channel(client_state.as_ref());
+
+        self.send(channel, message).await;
+    }
+
+    /// Posts a message without pinging anyone, since it carries titles and channel names
+    /// taken from YouTube.
+    pub(crate) async fn send(&self, channel: Option<ChannelId>, message: String) {
+        if let Some(channel) = channel {
+            let sent = channel
+                .send_message(&self.http, |m| {
+                    m.content(message)
+                        .allowed_mentions(|mentions| mentions.empty_parse())
+                })
+                .await;
+
+            if let Err(err) = sent {
+                error!("Could not announce in channel {channel}. Error: {err:?}");
+            }
+        }
+    }
+}
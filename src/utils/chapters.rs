@@ -0,0 +1,321 @@
+use log::error;
+use poise::serenity_prelude::GuildId;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::{
+    client_state::ClientStateError,
+    config::{Context, Error},
+    utils,
+};
+
+/// How far into a chapter `prev-chapter` restarts it instead of seeking the previous one.
+const RESTART_GRACE: Duration = Duration::from_secs(3);
+
+/// A chapter of a video, as listed in its description.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Chapter {
+    pub(crate) title: String,
+    pub(crate) start: Duration,
+}
+
+/// Parses the chapters listed in a video description.
+///
+/// Follows YouTube's rules: chapters are lines starting or ending with a timestamp, the first one
+/// starts at `0:00`, there are at least three, and they are in ascending order.
+pub(crate) fn parse(description: &str) -> Vec<Chapter> {
+    let chapters = description
+        .lines()
+        .filter_map(parse_line)
+        .collect::<Vec<_>>();
+
+    let valid = chapters.len() >= 3
+        && chapters[0].start.is_zero()
+        && chapters.windows(2).all(|w| w[0].start < w[1].start);
+
+    if valid {
+        chapters
+    } else {
+        vec![]
+    }
+}
+
+/// Returns the chapters of the current track, fetching its description if it wasn't yet.
+pub(crate) async fn of_current_track(
+    ctx: &Context<'_>,
+    guild_id: GuildId,
+) -> Result<Vec<Chapter>, Error> {
+    let now_playing = ctx
+        .data()
+        .client_state_map
+        .get(guild_id.as_u64())
+        .await
+        .ok_or(ClientStateError::NonExistentClientID)?
+        .now_playing
+        .clone()
+        .ok_or_else(|| Error::UserInput("Nothing is currently playing.".to_string()))?;
+
+    if let Some(chapters) = now_playing.chapters {
+        return Ok(chapters);
+    }
+
+    let chapters = ctx
+        .data()
+        .youtube_client
+        .video(&now_playing.id)
+        .await?
+        .and_then(|element| element.chapters)
+        .unwrap_or_default();
+
+    // The track may have changed while the description was fetched.
+    let fetched = chapters.clone();
+    let _ = ctx
+        .data()
+        .client_state_map
+        .with(guild_id.as_u64(), move |client_state| {
+            if let Some(element) = client_state
+                .now_playing
+                .as_mut()
+                .filter(|element| element.id == now_playing.id)
+            {
+                element.chapters = Some(fetched);
+            }
+        })
+        .await;
+
+    Ok(chapters)
+}
+
+/// Seeks the chapter chosen by `pick` from the current track's chapters and playback position.
+pub(crate) async fn jump(
+    ctx: &Context<'_>,
+    pick: impl Fn(&[Chapter], Duration) -> Option<usize>,
+    none_left: &str,
+) -> Result<(), Error> {
+    let guild_id = utils::guild_id(ctx)?;
+    let chapters = of_current_track(ctx, guild_id).await?;
+
+    if chapters.is_empty() {
+        return Err(Error::UserInput(
+            "The current track has no chapters.".to_string(),
+        ));
+    }
+
+    let current_track = ctx
+        .data()
+        .client_state_map
+        .get(guild_id.as_u64())
+        .await
+        .ok_or(ClientStateError::NonExistentClientID)?
+        .current_track
+        .clone()
+        .ok_or_else(|| Error::UserInput("Nothing is currently playing.".to_string()))?;
+
+    let position = current_track.get_info().await?.position;
+    let index = pick(&chapters, position).ok_or_else(|| Error::UserInput(none_left.to_string()))?;
+    let chapter = &chapters[index];
+
+    if current_track
+        .seek_time(chapter.start)
+        .inspect_err(|e| error!("Seek failed: {e}"))
+        .is_ok()
+    {
+        ctx.say(format!(
+            "Playing chapter {}/{}: {} from {}.",
+            index + 1,
+            chapters.len(),
+            chapter.title,
+            utils::format_duration(chapter.start)
+        ))
+        .await?;
+    } else {
+        ctx.say(format!("Seeking chapter {} failed.", chapter.title))
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Parses a line such as `0:00 Intro`, `Intro - 1:02:03` or `(12:34) Outro`.
+fn parse_line(line: &str) -> Option<Chapter> {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+
+    let last = words.len().checked_sub(1)?;
+    let (i, start) = [0, last].into_iter().find_map(|i| {
+        let word = words[i].trim_matches(|c: char| "()[]".contains(c));
+        word.contains(':')
+            .then(|| utils::parse_timestamp(word))
+            .flatten()
+            .map(|start| (i, start))
+    })?;
+
+    let separators = |c: char| c.is_whitespace() || "-–—|:".contains(c);
+    let title = [&words[..i], &words[i + 1..]]
+        .iter()
+        .map(|words| words.join(" "))
+        .map(|part| part.trim_matches(separators).to_string())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    (!title.is_empty()).then_some(Chapter { title, start })
+}
+
+/// Returns the index of the chapter playing at the given position.
+pub(crate) fn current(chapters: &[Chapter], position: Duration) -> Option<usize> {
+    chapters
+        .iter()
+        .rposition(|chapter| chapter.start <= position)
+}
+
+/// Returns the index of the chapter `prev-chapter` seeks to. Like a media player, this
+/// restarts the current chapter unless playback is within its first few seconds.
+pub(crate) fn previous(chapters: &[Chapter], position: Duration) -> Option<usize> {
+    let current = current(chapters, position)?;
+
+    if position.saturating_sub(chapters[current].start) > RESTART_GRACE {
+        Some(current)
+    } else {
+        current.checked_sub(1)
+    }
+}
+
+/// Looks up a chapter by title. Exact matches win over prefix and substring matches,
+/// which win over the closest title within a small edit distance.
+pub(crate) fn find<'a>(chapters: &'a [Chapter], query: &str) -> Option<&'a Chapter> {
+    let query = normalize(query);
+    if query.is_empty() {
+        return None;
+    }
+
+    let titles = chapters
+        .iter()
+        .map(|chapter| (chapter, normalize(&chapter.title)))
+        .collect::<Vec<_>>();
+
+    let matching = |matches: &dyn Fn(&str) -> bool| {
+        titles
+            .iter()
+            .find(|(_, title)| matches(title))
+            .map(|(chapter, _)| *chapter)
+    };
+
+    matching(&|title| title == query)
+        .or_else(|| matching(&|title| title.starts_with(&query)))
+        .or_else(|| matching(&|title| title.contains(&query)))
+        .or_else(|| {
+            titles
+                .iter()
+                .map(|(chapter, title)| {
+                    let distance = levenshtein(title, &query);
+                    (chapter, distance, distance * 3 <= title.chars().count())
+                })
+                .filter(|(_, _, close)| *close)
+                .min_by_key(|(_, distance, _)| *distance)
+                .map(|(chapter, _, _)| *chapter)
+        })
+}
+
+/// Lowercases the text and collapses everything but letters and digits into single spaces.
+fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous + usize::from(ca != *cb);
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(previous + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESCRIPTION: &str = "My new album!\n\
+        \n\
+        Tracklist:\n\
+        0:00 Intro\n\
+        (3:15) The Long Road Home\n\
+        Midnight City - 7:42\n\
+        1:02:03 — Outro\n\
+        \n\
+        Recorded live at 21:00 in Berlin";
+
+    fn chapters() -> Vec<Chapter> {
+        parse(DESCRIPTION)
+    }
+
+    #[test]
+    fn chapters_are_parsed_from_description() {
+        let chapters = chapters();
+
+        assert_eq!(
+            chapters
+                .iter()
+                .map(|chapter| (chapter.title.as_str(), chapter.start.as_secs()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("Intro", 0),
+                ("The Long Road Home", 195),
+                ("Midnight City", 462),
+                ("Outro", 3723),
+            ]
+        );
+    }
+
+    #[test]
+    fn descriptions_without_valid_chapters_yield_none() {
+        assert!(parse("Just a video").is_empty());
+        assert!(parse("0:30 A\n1:00 B\n2:00 C").is_empty());
+        assert!(parse("0:00 A\n2:00 B\n1:00 C").is_empty());
+        assert!(parse("0:00 A\n1:00 B").is_empty());
+    }
+
+    #[test]
+    fn current_chapter_follows_position() {
+        let chapters = chapters();
+
+        assert_eq!(current(&chapters, Duration::from_secs(0)), Some(0));
+        assert_eq!(current(&chapters, Duration::from_secs(200)), Some(1));
+        assert_eq!(current(&chapters, Duration::from_secs(4000)), Some(3));
+    }
+
+    #[test]
+    fn previous_restarts_chapter_after_grace_period() {
+        let chapters = chapters();
+
+        assert_eq!(previous(&chapters, Duration::from_secs(300)), Some(1));
+        assert_eq!(previous(&chapters, Duration::from_secs(197)), Some(0));
+        assert_eq!(previous(&chapters, Duration::from_secs(2)), None);
+    }
+
+    #[test]
+    fn chapters_are_found_by_title() {
+        let chapters = chapters();
+        let title = |query| find(&chapters, query).map(|chapter| chapter.title.as_str());
+
+        assert_eq!(title("outro"), Some("Outro"));
+        assert_eq!(title("the long"), Some("The Long Road Home"));
+        assert_eq!(title("city"), Some("Midnight City"));
+        assert_eq!(title("midnite city"), Some("Midnight City"));
+        assert_eq!(title("the lnog road hme"), Some("The Long Road Home"));
+        assert_eq!(title("something else"), None);
+        assert_eq!(title("   "), None);
+    }
+}
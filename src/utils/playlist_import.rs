@@ -0,0 +1,243 @@
+use log::{debug, error, warn};
+use poise::serenity_prelude::{ChannelId, GuildId, Http, Message};
+use rand::seq::SliceRandom;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::task::AbortHandle;
+
+use crate::{
+    client_state::{ClientStateMap, QueueElement},
+    config::Error,
+    settings::SettingsStore,
+    utils,
+    utils::source_retriever::youtube::{self, client::PlaylistPage, YouTubeClient},
+};
+
+/// The default cap on the number of videos imported from a single playlist.
+pub(crate) const DEFAULT_MAX_IMPORT: usize = 1000;
+
+/// The minimum interval between edits of the progress message.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Streams the remaining pages of a playlist into a guild's queue in the background.
+pub(crate) struct PlaylistImport {
+    pub(crate) guild_id: GuildId,
+    pub(crate) playlist: QueueElement,
+    pub(crate) next_page_token: String,
+    /// The number of videos already queued from the playlist.
+    pub(crate) loaded: usize,
+    /// The number of playlist items before the next page, queued or not.
+    /// Skipped again if the playlist starts over on another client.
+    pub(crate) position: usize,
+    pub(crate) limit: usize,
+    /// Set when the playlist is shuffled on import. Holds the ids of the items queued up front,
+    /// which are shuffled together with the rest once all pages are loaded.
+    pub(crate) shuffle_with: Option<Vec<String>>,
+    pub(crate) youtube_client: Arc<dyn YouTubeClient>,
+    pub(crate) client_state_map: Arc<ClientStateMap>,
+    pub(crate) settings: Arc<SettingsStore>,
+    pub(crate) http: Arc<Http>,
+    /// Where progress is posted. `None` imports silently.
+    pub(crate) channel_id: Option<ChannelId>,
+}
+
+impl PlaylistImport {
+    /// Starts the import. The returned handle cancels it.
+    pub(crate) fn spawn(self) -> AbortHandle {
+        tokio::spawn(self.run()).abort_handle()
+    }
+
+    async fn run(mut self) {
+        let title = utils::decode_html_encoded_string(&self.playlist.title);
+        let mut progress = self.post(self.progress_text(&title)).await;
+        let mut last_update = Instant::now();
+
+        let mut next_page_token = Some(self.next_page_token.clone());
+        let mut truncated = false;
+        let mut shuffled = vec![];
+
+        while let Some(page_token) = next_page_token.take() {
+            if self.loaded >= self.limit {
+                break;
+            }
+
+            let page = match self.page(&page_token).await {
+                Ok(page) => page,
+                Err(err) => {
+                    error!(
+                        "Could not load a page of playlist {}. Error: {err}",
+                        self.playlist.id
+                    );
+                    self.enqueue_shuffled(shuffled).await;
+                    self.finish(&mut progress, format!(
+                        "Stopped loading {title} after {} videos, the rest of the playlist could not be retrieved.",
+                        self.loaded
+                    ))
+                    .await;
+                    return;
+                }
+            };
+
+            self.position += page.items.len();
+
+            let settings = self.settings.get(self.guild_id);
+            let eligible = page
+                .items
+                .into_iter()
+                .filter(|item| settings.check_length(item.duration).is_ok())
+                .collect::<Vec<_>>();
+            let available = eligible.len();
+            let items = eligible
+                .into_iter()
+                .take(self.limit - self.loaded)
+                .collect::<Vec<_>>();
+            let count = items.len();
+            truncated = count < available;
+
+            let enqueued = match self.shuffle_with {
+                Some(_) => {
+                    shuffled.extend(items);
+                    self.client_state_map.contains_key(self.guild_id.as_u64())
+                }
+                None => self.enqueue(items).await,
+            };
+
+            if !enqueued {
+                debug!(
+                    "Guild {} left voice, abandoning import of {}.",
+                    self.guild_id, self.playlist.id
+                );
+                return;
+            }
+
+            self.loaded += count;
+            next_page_token = page.next_page_token;
+
+            if last_update.elapsed() >= PROGRESS_INTERVAL {
+                self.edit(&mut progress, self.progress_text(&title)).await;
+                last_update = Instant::now();
+            }
+        }
+
+        self.enqueue_shuffled(shuffled).await;
+
+        let summary = if truncated || next_page_token.is_some() {
+            format!(
+                "Finished loading {title}: queued {} videos, the import limit.",
+                self.loaded
+            )
+        } else {
+            format!("Finished loading {title}: queued {} videos.", self.loaded)
+        };
+        self.finish(&mut progress, summary).await;
+    }
+
+    /// Fetches the next page. If the playlist started over, the items before
+    /// the position reached so far are skipped.
+    async fn page(&self, page_token: &str) -> Result<PlaylistPage, Error> {
+        let client = self.youtube_client.as_ref();
+        let page = client
+            .playlist_page(&self.playlist.id, Some(page_token))
+            .await?;
+
+        if !page.restarted {
+            return Ok(page);
+        }
+
+        debug!(
+            "Playlist {} started over, skipping the first {} items.",
+            self.playlist.id, self.position
+        );
+        youtube::skip_to(&self.playlist.id, page, self.position + 1, client).await
+    }
+
+    fn progress_text(&self, title: &str) -> String {
+        format!("Loading {title}... {} videos loaded so far.", self.loaded)
+    }
+
+    /// Appends items to the queue. Returns `false` if the guild's client state is gone.
+    async fn enqueue(&self, items: Vec<QueueElement>) -> bool {
+        self.client_state_map
+            .with(self.guild_id.as_u64(), |client_state| {
+                client_state
+                    .song_queue
+                    .get_or_insert_with(Vec::new)
+                    .extend(items);
+            })
+            .await
+            .is_ok()
+    }
+
+    /// Shuffles the loaded items together with the playlist's items that are still waiting
+    /// in the queue, and appends them to the queue.
+    async fn enqueue_shuffled(&self, mut items: Vec<QueueElement>) {
+        let mut pending = match &self.shuffle_with {
+            Some(ids) => ids.clone(),
+            None => return,
+        };
+
+        self.client_state_map
+            .with(self.guild_id.as_u64(), move |client_state| {
+                let queue = client_state.song_queue.get_or_insert_with(Vec::new);
+                queue.retain(
+                    |element| match pending.iter().position(|id| *id == element.id) {
+                        Some(i) => {
+                            pending.swap_remove(i);
+                            items.push(element.clone());
+                            false
+                        }
+                        None => true,
+                    },
+                );
+
+                items.shuffle(&mut rand::thread_rng());
+                queue.extend(items);
+            })
+            .await
+            .unwrap_or_else(|err| {
+                error!(
+                    "Could not queue the shuffled playlist for {}. Error: {err:?}",
+                    self.guild_id
+                );
+            });
+    }
+
+    /// Posts to the progress channel without pinging anyone, as playlist titles
+    /// come from YouTube.
+    async fn post(&self, text: String) -> Option<Message> {
+        self.channel_id?
+            .send_message(&self.http, |m| {
+                m.content(text)
+                    .allowed_mentions(|mentions| mentions.empty_parse())
+            })
+            .await
+            .inspect_err(|err| warn!("Could not post playlist import progress. Error: {err:?}"))
+            .ok()
+    }
+
+    async fn edit(&self, progress: &mut Option<Message>, text: String) {
+        if let Some(message) = progress {
+            let edited = message
+                .edit(&self.http, |m| {
+                    m.content(text)
+                        .allowed_mentions(|mentions| mentions.empty_parse())
+                })
+                .await;
+
+            if let Err(err) = edited {
+                warn!("Could not update playlist import progress. Error: {err:?}");
+            }
+        }
+    }
+
+    async fn finish(&self, progress: &mut Option<Message>, text: String) {
+        match progress {
+            Some(_) => self.edit(progress, text).await,
+            None => {
+                self.post(text).await;
+            }
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use poise::serenity_prelude::{Activity, GuildId, ShardMessenger};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::{
+    client_state::{ClientStateMap, GuildEvent, PlaybackState, StateEvent},
+    utils,
+};
+
+/// Discord cuts activity names off at 128 characters.
+const MAX_ACTIVITY_LENGTH: usize = 128;
+
+/// Shows what is playing as the bot's "Listening to" activity.
+pub(crate) struct Presence {
+    shard: ShardMessenger,
+    client_state_map: Arc<ClientStateMap>,
+    /// The guild whose track is shown whenever it is playing, regardless of other guilds.
+    primary_guild: Option<GuildId>,
+    rotation: AtomicUsize,
+    shown: std::sync::Mutex<Option<String>>,
+}
+
+impl Presence {
+    /// How long each entry is shown while several guilds are playing.
+    pub(crate) const ROTATION_PERIOD: Duration = Duration::from_secs(30);
+
+    pub(crate) fn new(
+        shard: ShardMessenger,
+        client_state_map: Arc<ClientStateMap>,
+        primary_guild: Option<GuildId>,
+    ) -> Self {
+        Presence {
+            shard,
+            client_state_map,
+            primary_guild,
+            rotation: AtomicUsize::new(0),
+            shown: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// Updates the activity to the current playback.
+    pub(crate) async fn refresh(&self) {
+        let mut playing = vec![];
+        for gid in self.client_state_map.ids() {
+            let title = self
+                .client_state_map
+                .with(&gid, |client_state| {
+                    match (client_state.playback, &client_state.now_playing) {
+                        (PlaybackState::Playing, Some(element)) => Some(element.title.clone()),
+                        _ => None,
+                    }
+                })
+                .await;
+
+            if let Ok(Some(title)) = title {
+                playing.push((GuildId(gid), utils::decode_html_encoded_string(&title)));
+            }
+        }
+        playing.sort_by_key(|(gid, _)| *gid);
+
+        let activity = status(
+            &playing,
+            self.primary_guild,
+            self.rotation.load(Ordering::SeqCst),
+        );
+
+        // Presence updates are rate limited, so only changes are sent.
+        {
+            let mut shown = self.shown.lock().unwrap();
+            if *shown == activity {
+                return;
+            }
+            shown.clone_from(&activity);
+        }
+
+        self.shard.set_activity(activity.map(Activity::listening));
+    }
+
+    /// Refreshes the activity whenever playback starts or stops in a guild, and periodically
+    /// to move on to the next entry of the rotation.
+    pub(crate) fn start(self: Arc<Self>, mut events: broadcast::Receiver<GuildEvent>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Self::ROTATION_PERIOD);
+
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {
+                        self.rotation.fetch_add(1, Ordering::SeqCst);
+                    }
+                    event = events.recv() => match event {
+                        Ok(GuildEvent { event: StateEvent::QueueChanged { .. } | StateEvent::Joined { .. }, .. }) => continue,
+                        Ok(_) | Err(RecvError::Lagged(_)) => {}
+                        Err(RecvError::Closed) => return,
+                    },
+                }
+
+                self.refresh().await;
+            }
+        });
+    }
+}
+
+/// Picks the activity for the given playing guilds and their titles. The primary guild
+/// and a single playing guild show their title; several guilds rotate between a summary
+/// and each of their titles.
+fn status(
+    playing: &[(GuildId, String)],
+    primary_guild: Option<GuildId>,
+    rotation: usize,
+) -> Option<String> {
+    let primary = primary_guild.and_then(|primary| playing.iter().find(|(gid, _)| *gid == primary));
+
+    let status = match (primary, playing) {
+        (Some((_, title)), _) | (None, [(_, title)]) => title.clone(),
+        (None, []) => return None,
+        (None, _) => match rotation % (playing.len() + 1) {
+            0 => format!("music in {} servers", playing.len()),
+            i => playing[i - 1].1.clone(),
+        },
+    };
+
+    Some(status.chars().take(MAX_ACTIVITY_LENGTH).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn playing(guilds: &[u64]) -> Vec<(GuildId, String)> {
+        guilds
+            .iter()
+            .map(|gid| (GuildId(*gid), format!("Track {gid}")))
+            .collect()
+    }
+
+    #[test]
+    fn single_guild_shows_its_title() {
+        assert_eq!(status(&playing(&[]), None, 0), None);
+        assert_eq!(status(&playing(&[1]), None, 3), Some("Track 1".into()));
+    }
+
+    #[test]
+    fn several_guilds_rotate_with_a_summary() {
+        let playing = playing(&[1, 2]);
+        let shown = (0..4)
+            .map(|rotation| status(&playing, None, rotation).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            shown,
+            [
+                "music in 2 servers",
+                "Track 1",
+                "Track 2",
+                "music in 2 servers"
+            ]
+        );
+    }
+
+    #[test]
+    fn primary_guild_takes_precedence() {
+        let playing = playing(&[1, 2, 3]);
+
+        assert_eq!(
+            status(&playing, Some(GuildId(2)), 0),
+            Some("Track 2".into())
+        );
+        assert_eq!(
+            status(&playing, Some(GuildId(4)), 0),
+            Some("music in 3 servers".into())
+        );
+    }
+
+    #[test]
+    fn long_titles_are_truncated() {
+        let playing = vec![(GuildId(1), "a".repeat(200))];
+
+        assert_eq!(
+            status(&playing, None, 0).map(|s| s.len()),
+            Some(MAX_ACTIVITY_LENGTH)
+        );
+    }
+}
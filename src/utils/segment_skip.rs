@@ -0,0 +1,303 @@
+use hyper::{body, client::connect::HttpConnector, StatusCode};
+use hyper_rustls::HttpsConnector;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use url::Url;
+
+use crate::config::Error;
+
+/// The public SponsorBlock instance.
+pub(crate) const DEFAULT_PROVIDER_URL: &str = "https://sponsor.ajay.app";
+
+/// A category of segment, as named by the SponsorBlock API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, poise::ChoiceParameter)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Category {
+    #[name = "Sponsor"]
+    Sponsor,
+    #[name = "Self promotion"]
+    SelfPromo,
+    #[name = "Interaction reminder"]
+    Interaction,
+    #[name = "Intro"]
+    Intro,
+    #[name = "Outro"]
+    Outro,
+    #[name = "Preview"]
+    Preview,
+    #[name = "Non-music section"]
+    MusicOfftopic,
+    #[name = "Filler"]
+    Filler,
+}
+
+impl Category {
+    pub(crate) const ALL: [Category; 8] = [
+        Category::Sponsor,
+        Category::SelfPromo,
+        Category::Interaction,
+        Category::Intro,
+        Category::Outro,
+        Category::Preview,
+        Category::MusicOfftopic,
+        Category::Filler,
+    ];
+
+    fn api_name(self) -> &'static str {
+        match self {
+            Category::Sponsor => "sponsor",
+            Category::SelfPromo => "selfpromo",
+            Category::Interaction => "interaction",
+            Category::Intro => "intro",
+            Category::Outro => "outro",
+            Category::Preview => "preview",
+            Category::MusicOfftopic => "music_offtopic",
+            Category::Filler => "filler",
+        }
+    }
+
+    fn from_api_name(name: &str) -> Option<Category> {
+        Category::ALL
+            .into_iter()
+            .find(|category| category.api_name() == name)
+    }
+}
+
+/// A guild's segment skipping preferences.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct SegmentSkip {
+    pub(crate) enabled: bool,
+    pub(crate) categories: Vec<Category>,
+}
+
+impl Default for SegmentSkip {
+    fn default() -> Self {
+        SegmentSkip {
+            enabled: true,
+            categories: vec![
+                Category::Sponsor,
+                Category::SelfPromo,
+                Category::Interaction,
+                Category::MusicOfftopic,
+            ],
+        }
+    }
+}
+
+/// A section of a video that can be skipped.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Segment {
+    pub(crate) category: Category,
+    pub(crate) start: Duration,
+    pub(crate) end: Duration,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiSegment {
+    category: String,
+    action_type: Option<String>,
+    segment: [f64; 2],
+}
+
+/// Parses a `skipSegments` response, keeping the skippable segments of known categories
+/// in order of their start.
+fn parse_segments(json: &[u8]) -> Result<Vec<Segment>, Error> {
+    let segments = serde_json::from_slice::<Vec<ApiSegment>>(json).map_err(|err| {
+        Error::SourceResolution(format!("Unexpected segment provider response: {err}"))
+    })?;
+
+    let mut segments = segments
+        .into_iter()
+        .filter(|segment| segment.action_type.as_deref().unwrap_or("skip") == "skip")
+        .filter(|segment| segment.segment[0] >= 0.0 && segment.segment[0] < segment.segment[1])
+        .filter_map(|segment| {
+            Some(Segment {
+                category: Category::from_api_name(&segment.category)?,
+                start: Duration::try_from_secs_f64(segment.segment[0]).ok()?,
+                end: Duration::try_from_secs_f64(segment.segment[1]).ok()?,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    segments.sort_by_key(|segment| segment.start);
+    Ok(segments)
+}
+
+/// Returns where playback should jump to, if the position lies in a segment of one of
+/// the given categories. Back-to-back and overlapping segments are skipped in one jump.
+pub(crate) fn skip_target(
+    segments: &[Segment],
+    categories: &[Category],
+    position: Duration,
+) -> Option<Duration> {
+    let mut target: Option<Duration> = None;
+
+    for segment in segments
+        .iter()
+        .filter(|segment| categories.contains(&segment.category))
+    {
+        let from = target.unwrap_or(position);
+
+        if segment.start <= from && from < segment.end {
+            target = Some(segment.end);
+        }
+    }
+
+    target
+}
+
+/// Looks up skippable segments from a SponsorBlock-compatible API.
+pub(crate) struct SegmentProvider {
+    base_url: String,
+    http: hyper::Client<HttpsConnector<HttpConnector>>,
+}
+
+impl SegmentProvider {
+    pub(crate) fn new(base_url: String) -> Self {
+        let https = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_native_roots()
+            .https_or_http()
+            .enable_http1()
+            .build();
+
+        SegmentProvider {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            http: hyper::Client::builder().build(https),
+        }
+    }
+
+    /// Fetches the segments of a video in all categories.
+    pub(crate) async fn segments(&self, video_id: &str) -> Result<Vec<Segment>, Error> {
+        let categories = format!(
+            "[{}]",
+            Category::ALL
+                .iter()
+                .map(|category| format!("\"{}\"", category.api_name()))
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+
+        let url = Url::parse_with_params(
+            &format!("{}/api/skipSegments", self.base_url),
+            [("videoID", video_id), ("categories", &categories)],
+        )
+        .map_err(|err| Error::SourceResolution(format!("Invalid segment provider URL: {err}")))?;
+
+        let unreachable =
+            |err: hyper::Error| Error::SourceResolution(format!("Segment provider error: {err}"));
+
+        let response = self
+            .http
+            .get(url.as_str().parse().map_err(|err| {
+                Error::SourceResolution(format!("Invalid segment provider URL: {err}"))
+            })?)
+            .await
+            .map_err(unreachable)?;
+
+        match response.status() {
+            // The API answers 404 when a video has no segments.
+            StatusCode::NOT_FOUND => Ok(vec![]),
+            status if status.is_success() => parse_segments(
+                &body::to_bytes(response.into_body())
+                    .await
+                    .map_err(unreachable)?,
+            ),
+            status => Err(Error::SourceResolution(format!(
+                "Segment provider answered {status} for {video_id}."
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(category: Category, start: u64, end: u64) -> Segment {
+        Segment {
+            category,
+            start: Duration::from_secs(start),
+            end: Duration::from_secs(end),
+        }
+    }
+
+    #[test]
+    fn response_maps_to_segments() {
+        let segments = parse_segments(
+            br#"[
+                {"category": "outro", "actionType": "skip", "segment": [200.5, 230.0], "UUID": "b"},
+                {"category": "sponsor", "actionType": "skip", "segment": [10.0, 40.0], "UUID": "a"},
+                {"category": "sponsor", "actionType": "mute", "segment": [50.0, 60.0], "UUID": "c"},
+                {"category": "chapter", "actionType": "chapter", "segment": [0.0, 100.0], "UUID": "d"},
+                {"category": "newcategory", "actionType": "skip", "segment": [70.0, 80.0], "UUID": "e"}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            segments,
+            vec![
+                segment(Category::Sponsor, 10, 40),
+                Segment {
+                    category: Category::Outro,
+                    start: Duration::from_secs_f64(200.5),
+                    end: Duration::from_secs(230),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn out_of_range_segments_are_dropped() {
+        let segments = parse_segments(
+            br#"[
+                {"category": "sponsor", "segment": [0, 1e20]},
+                {"category": "sponsor", "segment": [10.0, 40.0]}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(segments, vec![segment(Category::Sponsor, 10, 40)]);
+    }
+
+    #[test]
+    fn malformed_response_is_an_error() {
+        assert!(parse_segments(b"Not Found").is_err());
+    }
+
+    #[test]
+    fn only_selected_categories_are_skipped() {
+        let segments = [
+            segment(Category::Intro, 0, 15),
+            segment(Category::Sponsor, 60, 90),
+        ];
+        let at = Duration::from_secs;
+
+        assert_eq!(skip_target(&segments, &[Category::Sponsor], at(5)), None);
+        assert_eq!(
+            skip_target(&segments, &[Category::Sponsor], at(60)),
+            Some(at(90))
+        );
+        assert_eq!(skip_target(&segments, &[Category::Sponsor], at(90)), None);
+        assert_eq!(
+            skip_target(&segments, &[Category::Intro, Category::Sponsor], at(5)),
+            Some(at(15))
+        );
+    }
+
+    #[test]
+    fn adjacent_segments_are_skipped_at_once() {
+        let segments = [
+            segment(Category::Sponsor, 10, 40),
+            segment(Category::SelfPromo, 35, 50),
+            segment(Category::Interaction, 50, 55),
+        ];
+
+        assert_eq!(
+            skip_target(&segments, &Category::ALL, Duration::from_secs(12)),
+            Some(Duration::from_secs(55))
+        );
+    }
+}
@@ -2,4 +2,4 @@
 pub(crate) mod source;
 pub(crate) mod youtube;
 
-pub(crate) use source::SourceType;
+pub(crate) use source::{Playlist, PlaylistKind, SourceType};
@@ -1,7 +1,26 @@
-use crate::client_state::QueueElement;
+use crate::{client_state::QueueElement, utils::source_retriever::youtube::client::PlaylistPage};
 
 #[derive(Debug, Clone)]
 pub(crate) enum SourceType {
     Single(QueueElement),
-    Playlist((QueueElement, Vec<QueueElement>)),
+    Playlist(Playlist),
+}
+
+/// A playlist with its first page of items.
+#[derive(Debug, Clone)]
+pub(crate) struct Playlist {
+    pub(crate) metadata: QueueElement,
+    pub(crate) page: PlaylistPage,
+    /// The 1-based position to start playing from, as given by a link's `index` parameter.
+    pub(crate) index: Option<usize>,
+    pub(crate) kind: PlaylistKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PlaylistKind {
+    Playlist,
+    /// A channel's uploads, newest first.
+    Uploads,
+    /// An auto-generated mix. Mixes are fetched whole, they have no further pages.
+    Mix,
 }
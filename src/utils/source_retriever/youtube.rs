@@ -1,292 +1,648 @@
-use crate::{client_state::QueueElement, config::ServerState, utils::source_retriever::SourceType};
-use futures::join;
+pub(crate) mod cache;
+pub(crate) mod client;
+#[cfg(test)]
+pub(crate) mod fake;
+pub(crate) mod fallback;
+pub(crate) mod link;
+pub(crate) mod quota;
+pub(crate) mod ytdlp;
+
+use crate::{
+    client_state::QueueElement,
+    config::Error,
+    utils::source_retriever::{Playlist, PlaylistKind, SourceType},
+};
+use futures::try_join;
 
 use url::Url;
 
 use log::debug;
 
-const SINGLE_URI: &str = "https://youtube.com/watch?v=";
-const PLAYLIST_URI: &str = "https://youtube.com/playlist?list=";
+pub(crate) use cache::CachedClient;
+pub(crate) use client::{DataApiClient, YouTubeClient};
+use client::{PlaylistPage, SearchMatch};
+pub(crate) use fallback::FallbackClient;
+use link::ChannelLink;
+pub(crate) use quota::QuotaTracker;
+pub(crate) use ytdlp::YtDlpClient;
 
+/// Fetches a playlist and its first page of items.
+/// Further pages are left to the caller, see [`crate::utils::playlist_import`].
 pub(crate) async fn fetch_playlist(
     playlist_id: String,
-    server_state: &ServerState,
-) -> Option<(QueueElement, Vec<QueueElement>)> {
-    let query_builder = || {
-        server_state
-            .youtube_client
-            .playlist_items()
-            .list(&vec!["snippet".to_string()])
-            .playlist_id(&playlist_id)
-            .param("key", &server_state.youtube_api_key.as_str())
-            .max_results(50)
+    client: &dyn YouTubeClient,
+) -> Result<Option<Playlist>, Error> {
+    let (playlist_data, page) = try_join!(
+        client.playlist(&playlist_id),
+        client.playlist_page(&playlist_id, None)
+    )?;
+
+    Ok(playlist_data.map(|metadata| Playlist {
+        metadata,
+        page,
+        index: None,
+        kind: PlaylistKind::Playlist,
+    }))
+}
+
+/// Fetches an auto-generated mix, seeded by `video_id` if given.
+pub(crate) async fn fetch_mix(
+    mix_id: &str,
+    video_id: Option<&str>,
+    client: &dyn YouTubeClient,
+) -> Result<Option<Playlist>, Error> {
+    Ok(client
+        .mix(mix_id, video_id)
+        .await?
+        .map(|(metadata, items)| Playlist {
+            metadata,
+            page: PlaylistPage {
+                items,
+                ..Default::default()
+            },
+            index: None,
+            kind: PlaylistKind::Mix,
+        }))
+}
+
+/// Fetches the uploads playlist of a channel.
+pub(crate) async fn fetch_channel(
+    channel: &ChannelLink,
+    client: &dyn YouTubeClient,
+) -> Result<Option<Playlist>, Error> {
+    let uploads = match client.uploads_playlist(channel).await? {
+        Some(uploads) => uploads,
+        None => return Ok(None),
     };
 
-    let p_query = server_state
-        .youtube_client
-        .playlists()
-        .list(&vec!["snippet".to_string()])
-        .add_id(&playlist_id)
-        .param("key", &server_state.youtube_api_key.as_str())
-        .max_results(1);
-
-    if let (Ok((_, mut p_items_res)), Ok((_, p_res))) =
-        join!(query_builder().doit(), p_query.doit())
-    {
-        let best_match = p_res
-            .items
-            .as_ref()
-            .unwrap()
-            .first()
-            .as_ref()
-            .unwrap()
-            .snippet
-            .as_ref()
-            .unwrap();
-        let playlist_data = QueueElement {
-            title: best_match.title.as_ref().unwrap().clone(),
-            channel_name: best_match
-                .channel_title
-                .as_ref()
-                .unwrap_or(&"None".to_string())
-                .clone(),
-            url: format!("{}{}", PLAYLIST_URI, playlist_id),
-            id: playlist_id.clone(),
-        };
+    Ok(fetch_playlist(uploads, client)
+        .await?
+        .map(|playlist| Playlist {
+            kind: PlaylistKind::Uploads,
+            ..playlist
+        }))
+}
+
+/// Pages through a playlist until the page holding the item at the 1-based position `from`.
+/// Returns that page, without the items before `from`. Also used to resume a playlist
+/// that started over on another client.
+pub(crate) async fn skip_to(
+    playlist_id: &str,
+    mut page: PlaylistPage,
+    from: usize,
+    client: &dyn YouTubeClient,
+) -> Result<PlaylistPage, Error> {
+    let mut skip = from.saturating_sub(1);
 
-        let mut playlist_elems = vec![];
-
-        loop {
-            playlist_elems = playlist_elems
-                .into_iter()
-                .chain(
-                    p_items_res
-                        .clone()
-                        .items
-                        .unwrap()
-                        .into_iter()
-                        .map(|playlist_item| QueueElement {
-                            title: playlist_item.snippet.clone().unwrap().title.unwrap(),
-                            channel_name: playlist_item
-                                .snippet
-                                .as_ref()
-                                .unwrap()
-                                .channel_title
-                                .as_ref()
-                                .unwrap()
-                                .clone(),
-                            url: format!(
-                                "{}{}",
-                                SINGLE_URI,
-                                playlist_item
-                                    .snippet
-                                    .as_ref()
-                                    .unwrap()
-                                    .resource_id
-                                    .as_ref()
-                                    .unwrap()
-                                    .video_id
-                                    .as_ref()
-                                    .unwrap()
-                                    .clone()
-                            ),
-                            id: playlist_item
-                                .snippet
-                                .as_ref()
-                                .unwrap()
-                                .resource_id
-                                .as_ref()
-                                .unwrap()
-                                .video_id
-                                .as_ref()
-                                .unwrap()
-                                .clone(),
-                        }),
-                )
-                .collect();
-
-            if let Some(next_token) = p_items_res.clone().next_page_token {
-                p_items_res = query_builder()
-                    .page_token(&next_token)
-                    .doit()
-                    .await
-                    .unwrap()
-                    .1;
-            } else {
-                break;
+    while skip >= page.items.len() {
+        skip -= page.items.len();
+
+        match page.next_page_token {
+            Some(next_token) => {
+                page = client.playlist_page(playlist_id, Some(&next_token)).await?;
+                if page.restarted {
+                    skip = from.saturating_sub(1);
+                }
+            }
+            None => {
+                page.items.clear();
+                return Ok(page);
             }
         }
-        Some((playlist_data, playlist_elems))
-    } else {
-        None
     }
+
+    page.items.drain(..skip);
+    page.restarted = false;
+    Ok(page)
 }
 
 pub(crate) async fn fetch_video(
     video_id: String,
-    server_state: &ServerState,
-) -> Option<QueueElement> {
-    let (_, response) = server_state
-        .youtube_client
-        .videos()
-        .list(&vec!["snippet".to_string()])
-        .add_id(&video_id)
-        .param("key", &server_state.youtube_api_key.as_str())
-        .doit()
-        .await
-        .unwrap();
-
-    if let Some(video_data) = response.items.unwrap().first() {
-        Some(QueueElement {
-            title: video_data
-                .snippet
-                .as_ref()
-                .unwrap()
-                .title
-                .as_ref()
-                .unwrap()
-                .clone(),
-            channel_name: video_data
-                .snippet
-                .as_ref()
-                .unwrap()
-                .channel_title
-                .as_ref()
-                .unwrap()
-                .clone(),
-            url: format!(
-                "{}{}",
-                SINGLE_URI,
-                video_data.id.as_ref().unwrap().to_string()
-            ),
-            id: video_data.id.as_ref().unwrap().to_string(),
-        })
-    } else {
-        None
-    }
+    client: &dyn YouTubeClient,
+) -> Result<Option<QueueElement>, Error> {
+    client.video(&video_id).await
 }
 
 pub(crate) async fn handle_search_query(
     query: String,
-    server_state: &ServerState,
-) -> Option<SourceType> {
-    let (_, result) = server_state
-        .youtube_client
-        .search()
-        .list(&vec!["snippet".to_string()])
-        .q(&query.as_str())
-        .param("key", &server_state.youtube_api_key.as_str())
-        .max_results(1)
-        .doit()
-        .await
-        .unwrap();
+    client: &dyn YouTubeClient,
+) -> Result<Option<SourceType>, Error> {
+    let best_match = client.search(&query).await?;
 
-    if let Some(best_match) = result.items.clone().unwrap().first().cloned() {
-        debug!("{:?}", best_match);
+    debug!("{:?}", best_match);
 
-        if best_match
-            .id
-            .as_ref()
-            .and_then(|id| id.video_id.clone())
-            .is_some()
-        {
-            Some(SourceType::Single(QueueElement {
-                title: best_match
-                    .snippet
-                    .as_ref()
-                    .unwrap()
-                    .title
-                    .as_ref()
-                    .unwrap()
-                    .clone(),
-                channel_name: best_match
-                    .snippet
-                    .as_ref()
-                    .unwrap()
-                    .channel_title
-                    .as_ref()
-                    .unwrap()
-                    .clone(),
-                url: format!(
-                    "{}{}",
-                    SINGLE_URI,
-                    best_match
-                        .id
-                        .as_ref()
-                        .unwrap()
-                        .video_id
-                        .as_ref()
-                        .unwrap()
-                        .clone()
-                ),
-                id: best_match
-                    .id
-                    .as_ref()
-                    .unwrap()
-                    .video_id
-                    .as_ref()
-                    .unwrap()
-                    .clone(),
-            }))
+    match best_match {
+        Some(SearchMatch::Video(element)) => Ok(Some(SourceType::Single(element))),
+        Some(SearchMatch::Playlist(p_id)) => Ok(fetch_playlist(p_id, client)
+            .await?
+            .map(SourceType::Playlist)),
+        None => Ok(None),
+    }
+}
+
+/// Resolves a YouTube link. Links to a video within a playlist resolve to the playlist,
+/// and links to a channel resolve to its uploads.
+/// A requested start offset is attached to the linked video.
+pub(crate) async fn process(
+    source: &Url,
+    client: &dyn YouTubeClient,
+) -> Result<Option<SourceType>, Error> {
+    let link = match link::parse(source) {
+        Some(link) => link,
+        None => return Ok(None),
+    };
+
+    let with_start = |mut element: QueueElement| {
+        if link.video_id.as_ref() == Some(&element.id) {
+            element.start = link.start;
+        }
+        element
+    };
+
+    if let Some(channel) = &link.channel {
+        return Ok(fetch_channel(channel, client)
+            .await?
+            .map(SourceType::Playlist));
+    }
+
+    if let Some(p_id) = link.playlist_id.clone() {
+        let playlist = if link::is_mix(&p_id) {
+            fetch_mix(&p_id, link.video_id.as_deref(), client).await?
         } else {
-            let p_id = best_match
-                .id
-                .as_ref()
-                .unwrap()
-                .playlist_id
-                .as_ref()
-                .unwrap()
-                .clone();
-
-            if let Some(playlist) = fetch_playlist(p_id, server_state).await {
-                Some(SourceType::Playlist(playlist))
-            } else {
-                None
-            }
+            fetch_playlist(p_id, client).await?
+        };
+
+        if let Some(mut playlist) = playlist {
+            playlist.page.items = playlist.page.items.into_iter().map(with_start).collect();
+            playlist.index = link.index;
+            return Ok(Some(SourceType::Playlist(playlist)));
         }
-    } else {
-        None
+    }
+
+    match link.video_id.clone() {
+        Some(video_id) => Ok(fetch_video(video_id, client)
+            .await?
+            .map(with_start)
+            .map(SourceType::Single)),
+        None => Ok(None),
     }
 }
 
-pub(crate) async fn process(source: &Url, server_state: &ServerState) -> Option<SourceType> {
-    match source.path() {
-        "/playlist" => {
-            let playlist_id = match source
-                .query_pairs()
-                .skip_while(|(key, _)| key != "list")
-                .next()
-            {
-                Some((_, p)) => p,
-                None => return None,
-            };
-            if let Some(queue) = fetch_playlist(playlist_id.to_string(), server_state).await {
-                Some(SourceType::Playlist(queue))
-            } else {
-                None
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::source_retriever::youtube::{
+        client::{add_details, parse_iso8601_duration, search_match, video_element},
+        fake::{video, FakeClient},
+    };
+    use google_youtube3::api::{
+        ResourceId, SearchResult, SearchResultSnippet, Video, VideoContentDetails,
+        VideoLiveStreamingDetails, VideoSnippet,
+    };
+    use std::time::Duration;
+
+    fn ids(elements: &[QueueElement]) -> Vec<&str> {
+        elements.iter().map(|element| element.id.as_str()).collect()
+    }
+
+    async fn process_url(url: &str, client: &FakeClient) -> Option<SourceType> {
+        process(&Url::parse(url).unwrap(), client).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn watch_url_resolves_video() {
+        let client = FakeClient::default().with_video("abc");
+
+        match process_url("https://www.youtube.com/watch?v=abc", &client).await {
+            Some(SourceType::Single(element)) => assert_eq!(element.id, "abc"),
+            other => panic!("expected a single video, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn watch_url_with_list_prefers_playlist() {
+        let client = FakeClient::default()
+            .with_video("abc")
+            .with_playlist("PL1", vec![vec!["a", "b"]]);
+
+        match process_url("https://www.youtube.com/watch?v=abc&list=PL1", &client).await {
+            Some(SourceType::Playlist(playlist)) => {
+                assert_eq!(playlist.metadata.id, "PL1");
+                assert_eq!(ids(&playlist.page.items), vec!["a", "b"]);
             }
+            other => panic!("expected a playlist, got {other:?}"),
         }
-        "/watch" => {
-            let params = source.query_pairs();
-            if params.count() > 1 {
-                if let Some((_, p_id)) = params.skip_while(|(key, _)| key != "list").next() {
-                    if let Some(res) = fetch_playlist(p_id.to_string(), server_state).await {
-                        return Some(SourceType::Playlist(res));
-                    }
-                }
+    }
+
+    #[tokio::test]
+    async fn watch_url_with_list_before_video_prefers_playlist() {
+        let client = FakeClient::default()
+            .with_video("abc")
+            .with_playlist("PL1", vec![vec!["a"]]);
+
+        assert!(matches!(
+            process_url("https://www.youtube.com/watch?list=PL1&v=abc", &client).await,
+            Some(SourceType::Playlist(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn watch_url_with_unknown_list_falls_back_to_video() {
+        let client = FakeClient::default().with_video("abc");
+
+        match process_url("https://www.youtube.com/watch?v=abc&list=missing", &client).await {
+            Some(SourceType::Single(element)) => assert_eq!(element.id, "abc"),
+            other => panic!("expected a single video, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn watch_url_without_video_is_rejected() {
+        let client = FakeClient::default();
+
+        assert!(process_url("https://www.youtube.com/watch?t=10", &client)
+            .await
+            .is_none());
+        assert!(client.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn playlist_url_resolves_playlist() {
+        let client = FakeClient::default().with_playlist("PL1", vec![vec!["a"]]);
+
+        match process_url("https://www.youtube.com/playlist?list=PL1", &client).await {
+            Some(SourceType::Playlist(playlist)) => {
+                assert_eq!(playlist.metadata.title, "Playlist PL1");
+                assert_eq!(ids(&playlist.page.items), vec!["a"]);
+            }
+            other => panic!("expected a playlist, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn playlist_url_without_list_is_rejected() {
+        let client = FakeClient::default();
+
+        assert!(process_url("https://www.youtube.com/playlist", &client)
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn short_link_with_timestamp_starts_at_offset() {
+        let client = FakeClient::default().with_video("abc");
+
+        match process_url("https://youtu.be/abc?t=1m5s", &client).await {
+            Some(SourceType::Single(element)) => {
+                assert_eq!(element.id, "abc");
+                assert_eq!(element.start, Some(std::time::Duration::from_secs(65)));
+            }
+            other => panic!("expected a single video, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn unknown_path_is_rejected() {
+        let client = FakeClient::default().with_video("abc");
+
+        assert!(
+            process_url("https://www.youtube.com/feed/trending", &client)
+                .await
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn only_the_first_playlist_page_is_fetched() {
+        let client =
+            FakeClient::default().with_playlist("PL1", vec![vec!["a", "b"], vec!["c"], vec!["d"]]);
+
+        let page = fetch_playlist("PL1".to_string(), &client)
+            .await
+            .unwrap()
+            .unwrap()
+            .page;
+
+        assert_eq!(ids(&page.items), vec!["a", "b"]);
+        assert_eq!(page.next_page_token.as_deref(), Some("1"));
+        assert_eq!(
+            client
+                .requests()
+                .iter()
+                .filter(|request| request.starts_with("playlist_page"))
+                .count(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn index_parameter_is_kept() {
+        let client = FakeClient::default().with_playlist("PL1", vec![vec!["a", "b"]]);
+
+        match process_url(
+            "https://www.youtube.com/watch?v=b&list=PL1&index=2",
+            &client,
+        )
+        .await
+        {
+            Some(SourceType::Playlist(playlist)) => assert_eq!(playlist.index, Some(2)),
+            other => panic!("expected a playlist, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn skip_to_pages_forward() {
+        let client = FakeClient::default()
+            .with_playlist("PL1", vec![vec!["a", "b"], vec!["c", "d"], vec!["e"]]);
+        let first = fetch_playlist("PL1".to_string(), &client)
+            .await
+            .unwrap()
+            .unwrap()
+            .page;
+
+        let page = skip_to("PL1", first.clone(), 1, &client).await.unwrap();
+        assert_eq!(ids(&page.items), vec!["a", "b"]);
+
+        let page = skip_to("PL1", first.clone(), 4, &client).await.unwrap();
+        assert_eq!(ids(&page.items), vec!["d"]);
+        assert_eq!(page.next_page_token.as_deref(), Some("2"));
+
+        let page = skip_to("PL1", first, 9, &client).await.unwrap();
+        assert!(page.items.is_empty());
+        assert!(page.next_page_token.is_none());
+    }
+
+    #[tokio::test]
+    async fn skip_to_resumes_a_restarted_playlist() {
+        let client = FallbackClient::new(
+            Box::new(FakeClient::default().exhausted()),
+            Box::new(
+                FakeClient::default()
+                    .with_playlist("PL1", vec![vec!["a", "b"], vec!["c", "d"], vec!["e"]]),
+            ),
+        );
+
+        // The primary ran out after the first four items were queued.
+        let page = client.playlist_page("PL1", Some("CAQQAA")).await.unwrap();
+        assert!(page.restarted);
+
+        let page = skip_to("PL1", page, 5, &client).await.unwrap();
+        assert_eq!(ids(&page.items), vec!["e"]);
+        assert!(!page.restarted);
+    }
+
+    #[tokio::test]
+    async fn channel_url_resolves_uploads() {
+        let client = FakeClient::default()
+            .with_channel(ChannelLink::Handle("creator".to_string()), "UU1")
+            .with_playlist("UU1", vec![vec!["new", "old"]]);
+
+        match process_url("https://www.youtube.com/@creator/videos", &client).await {
+            Some(SourceType::Playlist(playlist)) => {
+                assert_eq!(playlist.kind, PlaylistKind::Uploads);
+                assert_eq!(ids(&playlist.page.items), vec!["new", "old"]);
+            }
+            other => panic!("expected the uploads playlist, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn unknown_channel_resolves_to_none() {
+        let client = FakeClient::default();
+
+        assert!(process_url("https://www.youtube.com/@nobody", &client)
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn mix_list_is_fetched_whole() {
+        let client = FakeClient::default()
+            .with_video("abc")
+            .with_mix("RDabc", vec!["abc", "def", "ghi"]);
+
+        match process_url("https://www.youtube.com/watch?v=abc&list=RDabc", &client).await {
+            Some(SourceType::Playlist(playlist)) => {
+                assert_eq!(playlist.kind, PlaylistKind::Mix);
+                assert_eq!(ids(&playlist.page.items), vec!["abc", "def", "ghi"]);
+                assert!(playlist.page.next_page_token.is_none());
+            }
+            other => panic!("expected a mix, got {other:?}"),
+        }
+        assert_eq!(client.requests(), vec!["mix:RDabc"]);
+    }
+
+    #[tokio::test]
+    async fn missing_playlist_resolves_to_none() {
+        let client = FakeClient::default();
+
+        assert!(fetch_playlist("PL1".to_string(), &client)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn search_for_video_resolves_single() {
+        let client = FakeClient::default().with_search("song", SearchMatch::Video(video("abc")));
+
+        match handle_search_query("song".to_string(), &client)
+            .await
+            .unwrap()
+        {
+            Some(SourceType::Single(element)) => assert_eq!(element.id, "abc"),
+            other => panic!("expected a single video, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn search_for_playlist_fetches_items() {
+        let client = FakeClient::default()
+            .with_search("mix", SearchMatch::Playlist("PL1".to_string()))
+            .with_playlist("PL1", vec![vec!["a"], vec!["b"]]);
+
+        match handle_search_query("mix".to_string(), &client)
+            .await
+            .unwrap()
+        {
+            Some(SourceType::Playlist(playlist)) => {
+                assert_eq!(ids(&playlist.page.items), vec!["a"])
             }
+            other => panic!("expected a playlist, got {other:?}"),
+        }
+    }
 
-            let video_id = match params.skip_while(|(key, _)| key != "v").next() {
-                Some((_, v)) => v,
-                None => return None,
-            };
+    #[tokio::test]
+    async fn search_without_results_resolves_to_none() {
+        let client = FakeClient::default();
+
+        assert!(handle_search_query("nothing".to_string(), &client)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn search_result_maps_to_video() {
+        let result = SearchResult {
+            id: Some(ResourceId {
+                video_id: Some("abc".to_string()),
+                ..Default::default()
+            }),
+            snippet: Some(SearchResultSnippet {
+                title: Some("Title".to_string()),
+                channel_title: Some("Channel".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
 
-            if let Some(element) = fetch_video(video_id.to_string(), server_state).await {
-                Some(SourceType::Single(element))
-            } else {
-                None
+        match search_match(&result) {
+            Some(SearchMatch::Video(element)) => {
+                assert_eq!(element.id, "abc");
+                assert_eq!(element.title, "Title");
+                assert_eq!(element.channel_name, "Channel");
+                assert_eq!(element.url, "https://youtube.com/watch?v=abc");
             }
+            other => panic!("expected a video, got {other:?}"),
         }
-        _ => None,
+    }
+
+    #[test]
+    fn search_result_maps_to_playlist() {
+        let result = SearchResult {
+            id: Some(ResourceId {
+                playlist_id: Some("PL1".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            search_match(&result),
+            Some(SearchMatch::Playlist(id)) if id == "PL1"
+        ));
+    }
+
+    #[test]
+    fn search_result_for_channel_is_ignored() {
+        let result = SearchResult {
+            id: Some(ResourceId {
+                channel_id: Some("UC1".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        assert!(search_match(&result).is_none());
+    }
+
+    #[test]
+    fn live_video_is_marked_live() {
+        let video = Video {
+            id: Some("abc".to_string()),
+            snippet: Some(VideoSnippet {
+                title: Some("Stream".to_string()),
+                live_broadcast_content: Some("live".to_string()),
+                ..Default::default()
+            }),
+            content_details: Some(VideoContentDetails {
+                duration: Some("P0D".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let element = video_element(&video).unwrap();
+        assert!(element.live);
+        assert_eq!(element.duration, None);
+    }
+
+    #[test]
+    fn video_duration_is_parsed() {
+        let video = Video {
+            id: Some("abc".to_string()),
+            snippet: Some(VideoSnippet {
+                title: Some("Title".to_string()),
+                live_broadcast_content: Some("none".to_string()),
+                ..Default::default()
+            }),
+            content_details: Some(VideoContentDetails {
+                duration: Some("PT1H2M3S".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let element = video_element(&video).unwrap();
+        assert!(!element.live);
+        assert_eq!(element.duration, Some(Duration::from_secs(3723)));
+    }
+
+    #[test]
+    fn playlist_items_get_durations_and_live_status() {
+        let mut items = vec![video("abc"), video("live"), video("gone")];
+        let details = |id: &str, duration: &str, live: Option<VideoLiveStreamingDetails>| Video {
+            id: Some(id.to_string()),
+            content_details: Some(VideoContentDetails {
+                duration: Some(duration.to_string()),
+                ..Default::default()
+            }),
+            live_streaming_details: live,
+            ..Default::default()
+        };
+
+        add_details(
+            &mut items,
+            &[
+                details("abc", "PT4M13S", None),
+                details(
+                    "live",
+                    "P0D",
+                    Some(VideoLiveStreamingDetails {
+                        actual_start_time: Some(chrono::Utc::now()),
+                        ..Default::default()
+                    }),
+                ),
+            ],
+        );
+
+        assert_eq!(items[0].duration, Some(Duration::from_secs(253)));
+        assert!(!items[0].live);
+        assert!(items[1].live);
+        assert_eq!(items[1].duration, None);
+        assert_eq!(items[2].duration, None);
+    }
+
+    #[test]
+    fn iso8601_durations_are_parsed() {
+        for (duration, secs) in [
+            ("PT15S", 15),
+            ("PT4M13S", 253),
+            ("PT1H", 3600),
+            ("P1DT2H", 93_600),
+            ("P0D", 0),
+        ] {
+            assert_eq!(
+                parse_iso8601_duration(duration),
+                Some(Duration::from_secs(secs)),
+                "{duration}"
+            );
+        }
+
+        assert_eq!(parse_iso8601_duration("1H"), None);
+        assert_eq!(parse_iso8601_duration("PT1X"), None);
+        assert_eq!(parse_iso8601_duration("PT12"), None);
+    }
+
+    #[test]
+    fn video_without_title_is_ignored() {
+        let video = Video {
+            id: Some("abc".to_string()),
+            snippet: Some(VideoSnippet::default()),
+            ..Default::default()
+        };
+
+        assert!(video_element(&video).is_none());
     }
 }
@@ -0,0 +1,447 @@
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use serenity::async_trait;
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    client_state::QueueElement,
+    config::Error,
+    utils,
+    utils::source_retriever::youtube::{
+        client::{PlaylistPage, SearchMatch, YouTubeClient},
+        link::ChannelLink,
+    },
+};
+
+pub(crate) const DEFAULT_TTL: Duration = Duration::from_secs(6 * 60 * 60);
+pub(crate) const DEFAULT_CAPACITY: usize = 1000;
+
+/// How often a changed cache is saved.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Cached {
+    Video(QueueElement),
+    Playlist(QueueElement),
+    Page(PlaylistPage),
+    Search(SearchMatch),
+    Uploads(String),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    value: Cached,
+    /// Seconds since the Unix epoch, so entries keep their age across restarts.
+    stored_at: u64,
+    #[serde(skip)]
+    last_used: u64,
+}
+
+/// A metadata cache whose entries expire after a TTL. Once full, the least recently used
+/// entry is evicted.
+#[derive(Debug)]
+struct MetadataCache {
+    entries: HashMap<String, Entry>,
+    ttl: Duration,
+    capacity: usize,
+    /// A logical clock ordering entry accesses.
+    uses: u64,
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+impl MetadataCache {
+    fn new(ttl: Duration, capacity: usize) -> Self {
+        MetadataCache {
+            entries: HashMap::new(),
+            ttl,
+            capacity,
+            uses: 0,
+        }
+    }
+
+    fn get(&mut self, key: &str, now: SystemTime) -> Option<Cached> {
+        let expired =
+            unix_secs(now).saturating_sub(self.entries.get(key)?.stored_at) >= self.ttl.as_secs();
+
+        if expired {
+            self.entries.remove(key);
+            return None;
+        }
+
+        self.uses += 1;
+        let entry = self.entries.get_mut(key)?;
+        entry.last_used = self.uses;
+        Some(entry.value.clone())
+    }
+
+    fn insert(&mut self, key: String, value: Cached, now: SystemTime) {
+        self.insert_entry(
+            key,
+            Entry {
+                value,
+                stored_at: unix_secs(now),
+                last_used: 0,
+            },
+        );
+    }
+
+    /// Restores persisted entries, dropping those that have expired since.
+    fn restore(&mut self, entries: HashMap<String, Entry>, now: SystemTime) {
+        let mut entries = entries.into_iter().collect::<Vec<_>>();
+        entries.sort_by_key(|(_, entry)| entry.stored_at);
+
+        for (key, entry) in entries {
+            if unix_secs(now).saturating_sub(entry.stored_at) < self.ttl.as_secs() {
+                self.insert_entry(key, entry);
+            }
+        }
+    }
+
+    fn insert_entry(&mut self, key: String, mut entry: Entry) {
+        if !self.entries.contains_key(&key) && self.entries.len() >= self.capacity {
+            let lru = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+
+            if let Some(lru) = lru {
+                self.entries.remove(&lru);
+            }
+        }
+
+        self.uses += 1;
+        entry.last_used = self.uses;
+        self.entries.insert(key, entry);
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+/// Serves repeated lookups from a metadata cache instead of the wrapped client.
+/// Only successful lookups are cached. If a path is given, the cache is loaded from
+/// that file and saved to it periodically while it changes.
+pub(crate) struct CachedClient {
+    inner: Box<dyn YouTubeClient>,
+    cache: Mutex<MetadataCache>,
+    path: Option<PathBuf>,
+    /// Set when entries were added since the last save.
+    dirty: AtomicBool,
+    /// Serialises writes to the cache file.
+    saving: tokio::sync::Mutex<()>,
+}
+
+impl CachedClient {
+    pub(crate) fn new(
+        inner: Box<dyn YouTubeClient>,
+        ttl: Duration,
+        capacity: usize,
+        path: Option<PathBuf>,
+    ) -> Self {
+        let mut cache = MetadataCache::new(ttl, capacity);
+
+        if let Some(path) = &path {
+            match std::fs::read(path) {
+                Ok(bytes) => match serde_json::from_slice(&bytes) {
+                    Ok(entries) => cache.restore(entries, SystemTime::now()),
+                    Err(err) => warn!("Ignoring unreadable metadata cache {path:?}. Error: {err}"),
+                },
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => warn!("Could not read metadata cache {path:?}. Error: {err}"),
+            }
+        }
+
+        CachedClient {
+            inner,
+            cache: Mutex::new(cache),
+            path,
+            dirty: AtomicBool::new(false),
+            saving: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Saves the cache every [`FLUSH_INTERVAL`] if it changed. Does nothing without a path.
+    pub(crate) fn flush_periodically(self: &Arc<Self>) {
+        if self.path.is_none() {
+            return;
+        }
+
+        let client = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+            loop {
+                interval.tick().await;
+                match client.upgrade() {
+                    Some(client) => client.flush().await,
+                    None => return,
+                }
+            }
+        });
+    }
+
+    /// Saves the cache if entries were added since the last save.
+    async fn flush(&self) {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+
+        // Snapshotting under the lock, so an older snapshot never overwrites a newer one.
+        let _saving = self.saving.lock().await;
+        if !self.dirty.swap(false, Ordering::AcqRel) {
+            return;
+        }
+
+        let snapshot = serde_json::to_vec(&self.cache.lock().unwrap().entries);
+        let result = match snapshot {
+            Ok(bytes) => utils::write_replacing(path, bytes)
+                .await
+                .map_err(|e| e.to_string()),
+            Err(err) => Err(err.to_string()),
+        };
+
+        if let Err(err) = result {
+            self.dirty.store(true, Ordering::Release);
+            error!("Could not save metadata cache to {path:?}. Error: {err}");
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<Cached> {
+        self.cache.lock().unwrap().get(key, SystemTime::now())
+    }
+
+    async fn put(&self, key: String, value: Cached) {
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(key, value, SystemTime::now());
+        self.dirty.store(true, Ordering::Release);
+    }
+}
+
+#[async_trait]
+impl YouTubeClient for CachedClient {
+    async fn video(&self, video_id: &str) -> Result<Option<QueueElement>, Error> {
+        let key = format!("video:{video_id}");
+        if let Some(Cached::Video(element)) = self.get(&key) {
+            return Ok(Some(element));
+        }
+
+        let element = self.inner.video(video_id).await?;
+        if let Some(element) = &element {
+            self.put(key, Cached::Video(element.clone())).await;
+        }
+
+        Ok(element)
+    }
+
+    async fn playlist(&self, playlist_id: &str) -> Result<Option<QueueElement>, Error> {
+        let key = format!("playlist:{playlist_id}");
+        if let Some(Cached::Playlist(element)) = self.get(&key) {
+            return Ok(Some(element));
+        }
+
+        let element = self.inner.playlist(playlist_id).await?;
+        if let Some(element) = &element {
+            self.put(key, Cached::Playlist(element.clone())).await;
+        }
+
+        Ok(element)
+    }
+
+    async fn playlist_page(
+        &self,
+        playlist_id: &str,
+        page_token: Option<&str>,
+    ) -> Result<PlaylistPage, Error> {
+        let key = format!("playlist_page:{playlist_id}:{}", page_token.unwrap_or(""));
+        if let Some(Cached::Page(page)) = self.get(&key) {
+            return Ok(page);
+        }
+
+        let page = self.inner.playlist_page(playlist_id, page_token).await?;
+        self.put(key, Cached::Page(page.clone())).await;
+
+        Ok(page)
+    }
+
+    async fn search(&self, query: &str) -> Result<Option<SearchMatch>, Error> {
+        let key = format!("search:{}", query.trim().to_lowercase());
+        if let Some(Cached::Search(best_match)) = self.get(&key) {
+            return Ok(Some(best_match));
+        }
+
+        let best_match = self.inner.search(query).await?;
+        if let Some(best_match) = &best_match {
+            self.put(key, Cached::Search(best_match.clone())).await;
+        }
+
+        Ok(best_match)
+    }
+
+    async fn uploads_playlist(&self, channel: &ChannelLink) -> Result<Option<String>, Error> {
+        let key = format!("uploads:{}", channel.url());
+        if let Some(Cached::Uploads(playlist_id)) = self.get(&key) {
+            return Ok(Some(playlist_id));
+        }
+
+        let playlist_id = self.inner.uploads_playlist(channel).await?;
+        if let Some(playlist_id) = &playlist_id {
+            self.put(key, Cached::Uploads(playlist_id.clone())).await;
+        }
+
+        Ok(playlist_id)
+    }
+
+    /// Mixes are generated on request, so they are never cached.
+    async fn mix(
+        &self,
+        mix_id: &str,
+        video_id: Option<&str>,
+    ) -> Result<Option<(QueueElement, Vec<QueueElement>)>, Error> {
+        self.inner.mix(mix_id, video_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::source_retriever::youtube::fake::{video, FakeClient};
+
+    fn cached(client: &Arc<FakeClient>) -> CachedClient {
+        CachedClient::new(
+            Box::new(client.clone()),
+            DEFAULT_TTL,
+            DEFAULT_CAPACITY,
+            None,
+        )
+    }
+
+    #[tokio::test]
+    async fn repeated_lookups_hit_the_cache() {
+        let fake = Arc::new(
+            FakeClient::default()
+                .with_video("abc")
+                .with_search("song", SearchMatch::Video(video("abc"))),
+        );
+        let client = cached(&fake);
+
+        for _ in 0..3 {
+            assert!(client.video("abc").await.unwrap().is_some());
+            assert!(client.search("song").await.unwrap().is_some());
+        }
+
+        assert_eq!(fake.requests(), vec!["video:abc", "search:song"]);
+    }
+
+    #[tokio::test]
+    async fn missing_resources_are_not_cached() {
+        let fake = Arc::new(FakeClient::default());
+        let client = cached(&fake);
+
+        assert!(client.video("abc").await.unwrap().is_none());
+        assert!(client.video("abc").await.unwrap().is_none());
+
+        assert_eq!(fake.requests().len(), 2);
+        assert_eq!(client.cache.lock().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn entries_expire_after_ttl() {
+        let mut cache = MetadataCache::new(Duration::from_secs(60), 10);
+        let now = SystemTime::now();
+
+        cache.insert("video:a".to_string(), Cached::Video(video("a")), now);
+
+        assert!(cache
+            .get("video:a", now + Duration::from_secs(59))
+            .is_some());
+        assert!(cache
+            .get("video:a", now + Duration::from_secs(60))
+            .is_none());
+        assert_eq!(cache.len(), 0);
+    }
+
+    #[test]
+    fn least_recently_used_entry_is_evicted() {
+        let mut cache = MetadataCache::new(DEFAULT_TTL, 2);
+        let now = SystemTime::now();
+
+        cache.insert("video:a".to_string(), Cached::Video(video("a")), now);
+        cache.insert("video:b".to_string(), Cached::Video(video("b")), now);
+        cache.get("video:a", now);
+        cache.insert("video:c".to_string(), Cached::Video(video("c")), now);
+
+        assert!(cache.get("video:a", now).is_some());
+        assert!(cache.get("video:b", now).is_none());
+        assert!(cache.get("video:c", now).is_some());
+    }
+
+    #[test]
+    fn restored_entries_keep_their_age() {
+        let mut cache = MetadataCache::new(Duration::from_secs(60), 10);
+        let now = SystemTime::now();
+
+        cache.insert("video:old".to_string(), Cached::Video(video("old")), now);
+        cache.insert(
+            "video:new".to_string(),
+            Cached::Video(video("new")),
+            now + Duration::from_secs(30),
+        );
+
+        let persisted = serde_json::to_vec(&cache.entries).unwrap();
+        let mut restored = MetadataCache::new(Duration::from_secs(60), 10);
+        restored.restore(
+            serde_json::from_slice(&persisted).unwrap(),
+            now + Duration::from_secs(70),
+        );
+
+        assert_eq!(restored.len(), 1);
+        assert!(restored
+            .get("video:new", now + Duration::from_secs(70))
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn changes_are_saved_on_flush() {
+        let path = std::env::temp_dir().join(format!("metadata-{}.json", std::process::id()));
+        let fake = Arc::new(FakeClient::default().with_video("abc"));
+        let client = CachedClient::new(
+            Box::new(fake.clone()),
+            DEFAULT_TTL,
+            DEFAULT_CAPACITY,
+            Some(path.clone()),
+        );
+
+        client.video("abc").await.unwrap();
+        assert!(!path.exists());
+
+        client.flush().await;
+        let restored = CachedClient::new(
+            Box::new(fake.clone()),
+            DEFAULT_TTL,
+            DEFAULT_CAPACITY,
+            Some(path.clone()),
+        );
+        let _ = std::fs::remove_file(&path);
+
+        assert!(restored.video("abc").await.unwrap().is_some());
+        assert_eq!(fake.requests(), vec!["video:abc"]);
+    }
+}
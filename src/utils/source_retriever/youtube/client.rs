@@ -0,0 +1,429 @@
+use google_youtube3::{
+    api::{PlaylistItem, SearchResult, Video},
+    client::auth::NoToken,
+    YouTube,
+};
+use hyper::client::connect::HttpConnector;
+use hyper_rustls::HttpsConnector;
+use serde::{Deserialize, Serialize};
+use serenity::async_trait;
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    client_state::QueueElement,
+    config::Error,
+    metrics,
+    utils::{
+        chapters,
+        source_retriever::youtube::{
+            link::{self, ChannelLink},
+            quota::{QuotaTracker, LIST_COST, SEARCH_COST},
+        },
+    },
+};
+
+pub(crate) const SINGLE_URI: &str = "https://youtube.com/watch?v=";
+pub(crate) const PLAYLIST_URI: &str = "https://youtube.com/playlist?list=";
+
+/// A single page of a playlist's items.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct PlaylistPage {
+    pub(crate) items: Vec<QueueElement>,
+    pub(crate) next_page_token: Option<String>,
+    /// Set when the page token could not be continued and the playlist started over,
+    /// so the caller has to skip the items it already has.
+    #[serde(default)]
+    pub(crate) restarted: bool,
+}
+
+/// The best match for a search query.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) enum SearchMatch {
+    Video(QueueElement),
+    Playlist(String),
+}
+
+/// The YouTube metadata lookups needed to resolve a request into queue elements.
+#[async_trait]
+pub(crate) trait YouTubeClient: Send + Sync {
+    async fn video(&self, video_id: &str) -> Result<Option<QueueElement>, Error>;
+
+    /// Returns the playlist itself as a queue element, without its items.
+    async fn playlist(&self, playlist_id: &str) -> Result<Option<QueueElement>, Error>;
+
+    async fn playlist_page(
+        &self,
+        playlist_id: &str,
+        page_token: Option<&str>,
+    ) -> Result<PlaylistPage, Error>;
+
+    async fn search(&self, query: &str) -> Result<Option<SearchMatch>, Error>;
+
+    /// Returns the id of the playlist holding a channel's uploads, newest first.
+    async fn uploads_playlist(&self, channel: &ChannelLink) -> Result<Option<String>, Error>;
+
+    /// Returns an auto-generated mix with all of its items, as mixes cannot be paged.
+    async fn mix(
+        &self,
+        mix_id: &str,
+        video_id: Option<&str>,
+    ) -> Result<Option<(QueueElement, Vec<QueueElement>)>, Error>;
+}
+
+#[async_trait]
+impl<T: YouTubeClient + ?Sized> YouTubeClient for Arc<T> {
+    async fn video(&self, video_id: &str) -> Result<Option<QueueElement>, Error> {
+        self.as_ref().video(video_id).await
+    }
+
+    async fn playlist(&self, playlist_id: &str) -> Result<Option<QueueElement>, Error> {
+        self.as_ref().playlist(playlist_id).await
+    }
+
+    async fn playlist_page(
+        &self,
+        playlist_id: &str,
+        page_token: Option<&str>,
+    ) -> Result<PlaylistPage, Error> {
+        self.as_ref().playlist_page(playlist_id, page_token).await
+    }
+
+    async fn search(&self, query: &str) -> Result<Option<SearchMatch>, Error> {
+        self.as_ref().search(query).await
+    }
+
+    async fn uploads_playlist(&self, channel: &ChannelLink) -> Result<Option<String>, Error> {
+        self.as_ref().uploads_playlist(channel).await
+    }
+
+    async fn mix(
+        &self,
+        mix_id: &str,
+        video_id: Option<&str>,
+    ) -> Result<Option<(QueueElement, Vec<QueueElement>)>, Error> {
+        self.as_ref().mix(mix_id, video_id).await
+    }
+}
+
+/// A client backed by the YouTube Data API.
+pub(crate) struct DataApiClient {
+    hub: YouTube<HttpsConnector<HttpConnector>>,
+    api_key: String,
+    quota: Arc<QuotaTracker>,
+}
+
+impl DataApiClient {
+    pub(crate) fn new(api_key: String, quota: Arc<QuotaTracker>) -> Self {
+        let https = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_native_roots()
+            .https_or_http()
+            .enable_http1()
+            .build();
+
+        DataApiClient {
+            hub: YouTube::new(hyper::Client::builder().build(https), NoToken),
+            api_key,
+            quota,
+        }
+    }
+}
+
+impl DataApiClient {
+    /// Playlist items carry neither a duration nor whether they are live,
+    /// those are looked up for a whole page at once.
+    async fn add_details(&self, items: &mut [QueueElement]) -> Result<(), Error> {
+        if items.is_empty() {
+            return Ok(());
+        }
+
+        let ids = items
+            .iter()
+            .map(|item| item.id.as_str())
+            .collect::<Vec<_>>()
+            .join(",");
+        let query = self
+            .hub
+            .videos()
+            .list(&vec![
+                "contentDetails".to_string(),
+                "liveStreamingDetails".to_string(),
+            ])
+            .add_id(&ids)
+            .param("key", self.api_key.as_str())
+            .max_results(50);
+
+        self.quota.record(LIST_COST);
+        let (_, response) = metrics::youtube_api("videos.list", query.doit()).await?;
+
+        add_details(items, response.items.as_deref().unwrap_or_default());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl YouTubeClient for DataApiClient {
+    async fn video(&self, video_id: &str) -> Result<Option<QueueElement>, Error> {
+        self.quota.record(LIST_COST);
+        let query = self
+            .hub
+            .videos()
+            .list(&vec!["snippet".to_string(), "contentDetails".to_string()])
+            .add_id(video_id)
+            .param("key", self.api_key.as_str());
+        let (_, response) = metrics::youtube_api("videos.list", query.doit()).await?;
+
+        Ok(response
+            .items
+            .as_ref()
+            .and_then(|items| items.first())
+            .and_then(video_element))
+    }
+
+    async fn playlist(&self, playlist_id: &str) -> Result<Option<QueueElement>, Error> {
+        self.quota.record(LIST_COST);
+        let query = self
+            .hub
+            .playlists()
+            .list(&vec!["snippet".to_string()])
+            .add_id(playlist_id)
+            .param("key", self.api_key.as_str())
+            .max_results(1);
+        let (_, response) = metrics::youtube_api("playlists.list", query.doit()).await?;
+
+        Ok(response
+            .items
+            .as_ref()
+            .and_then(|items| items.first())
+            .and_then(|playlist| playlist.snippet.as_ref())
+            .map(|snippet| QueueElement {
+                title: snippet.title.clone().unwrap_or_default(),
+                channel_name: snippet
+                    .channel_title
+                    .clone()
+                    .unwrap_or_else(|| "None".to_string()),
+                url: format!("{}{}", PLAYLIST_URI, playlist_id),
+                id: playlist_id.to_string(),
+                ..Default::default()
+            }))
+    }
+
+    async fn playlist_page(
+        &self,
+        playlist_id: &str,
+        page_token: Option<&str>,
+    ) -> Result<PlaylistPage, Error> {
+        let mut query = self
+            .hub
+            .playlist_items()
+            .list(&vec!["snippet".to_string()])
+            .playlist_id(playlist_id)
+            .param("key", self.api_key.as_str())
+            .max_results(50);
+
+        if let Some(page_token) = page_token {
+            query = query.page_token(page_token);
+        }
+
+        self.quota.record(LIST_COST);
+        let (_, response) = metrics::youtube_api("playlistItems.list", query.doit()).await?;
+
+        let mut items = response
+            .items
+            .iter()
+            .flatten()
+            .filter_map(playlist_item_element)
+            .collect::<Vec<_>>();
+        self.add_details(&mut items).await?;
+
+        Ok(PlaylistPage {
+            items,
+            next_page_token: response.next_page_token,
+            ..Default::default()
+        })
+    }
+
+    async fn search(&self, query: &str) -> Result<Option<SearchMatch>, Error> {
+        self.quota.record(SEARCH_COST);
+        let query = self
+            .hub
+            .search()
+            .list(&vec!["snippet".to_string()])
+            .q(query)
+            .param("key", self.api_key.as_str())
+            .max_results(1);
+        let (_, response) = metrics::youtube_api("search.list", query.doit()).await?;
+
+        Ok(response
+            .items
+            .as_ref()
+            .and_then(|items| items.first())
+            .and_then(search_match))
+    }
+
+    /// Custom URLs cannot be looked up through the Data API.
+    async fn uploads_playlist(&self, channel: &ChannelLink) -> Result<Option<String>, Error> {
+        let query = self
+            .hub
+            .channels()
+            .list(&vec!["contentDetails".to_string()])
+            .param("key", self.api_key.as_str());
+
+        let query = match channel {
+            ChannelLink::Id(id) => return Ok(link::uploads_playlist_id(id)),
+            ChannelLink::Handle(handle) => query.param("forHandle", handle),
+            ChannelLink::Username(name) => query.for_username(name),
+            ChannelLink::CustomUrl(_) => {
+                return Err(Error::Unsupported(
+                    "The YouTube Data API can't resolve custom channel URLs.".to_string(),
+                ))
+            }
+        };
+
+        self.quota.record(LIST_COST);
+        let (_, response) = metrics::youtube_api("channels.list", query.doit()).await?;
+
+        Ok(response
+            .items
+            .as_ref()
+            .and_then(|items| items.first())
+            .and_then(|channel| channel.content_details.as_ref())
+            .and_then(|details| details.related_playlists.as_ref())
+            .and_then(|playlists| playlists.uploads.clone()))
+    }
+
+    /// The Data API cannot read mixes.
+    async fn mix(
+        &self,
+        _mix_id: &str,
+        _video_id: Option<&str>,
+    ) -> Result<Option<(QueueElement, Vec<QueueElement>)>, Error> {
+        Err(Error::Unsupported(
+            "The YouTube Data API can't read mixes.".to_string(),
+        ))
+    }
+}
+
+pub(crate) fn video_element(video: &Video) -> Option<QueueElement> {
+    let snippet = video.snippet.as_ref()?;
+    let id = video.id.clone()?;
+    let live = is_live(snippet.live_broadcast_content.as_deref());
+
+    Some(QueueElement {
+        title: snippet.title.clone()?,
+        channel_name: snippet.channel_title.clone().unwrap_or_default(),
+        url: format!("{}{}", SINGLE_URI, id),
+        id,
+        duration: duration(video, live),
+        live,
+        chapters: Some(chapters::parse(
+            snippet.description.as_deref().unwrap_or_default(),
+        )),
+        ..Default::default()
+    })
+}
+
+/// The duration of a video, `None` while it is live or if YouTube reports none.
+fn duration(video: &Video, live: bool) -> Option<Duration> {
+    video
+        .content_details
+        .as_ref()
+        .and_then(|details| details.duration.as_deref())
+        .and_then(parse_iso8601_duration)
+        .filter(|duration| !live && !duration.is_zero())
+}
+
+/// Fills in the duration and live status of playlist items from their videos. A video is
+/// live while its stream has started and not ended.
+pub(crate) fn add_details(items: &mut [QueueElement], videos: &[Video]) {
+    for video in videos {
+        let item = match items
+            .iter_mut()
+            .find(|item| video.id.as_ref() == Some(&item.id))
+        {
+            Some(item) => item,
+            None => continue,
+        };
+
+        item.live = video
+            .live_streaming_details
+            .as_ref()
+            .is_some_and(|details| {
+                details.actual_start_time.is_some() && details.actual_end_time.is_none()
+            });
+        item.duration = duration(video, item.live);
+    }
+}
+
+pub(crate) fn playlist_item_element(playlist_item: &PlaylistItem) -> Option<QueueElement> {
+    let snippet = playlist_item.snippet.as_ref()?;
+    let id = snippet.resource_id.as_ref()?.video_id.clone()?;
+
+    Some(QueueElement {
+        title: snippet.title.clone()?,
+        channel_name: snippet
+            .video_owner_channel_title
+            .clone()
+            .or_else(|| snippet.channel_title.clone())
+            .unwrap_or_default(),
+        url: format!("{}{}", SINGLE_URI, id),
+        id,
+        ..Default::default()
+    })
+}
+
+pub(crate) fn search_match(search_result: &SearchResult) -> Option<SearchMatch> {
+    let id = search_result.id.as_ref()?;
+
+    if let Some(video_id) = id.video_id.clone() {
+        let snippet = search_result.snippet.as_ref()?;
+
+        Some(SearchMatch::Video(QueueElement {
+            title: snippet.title.clone()?,
+            channel_name: snippet.channel_title.clone().unwrap_or_default(),
+            url: format!("{}{}", SINGLE_URI, video_id),
+            id: video_id,
+            live: is_live(snippet.live_broadcast_content.as_deref()),
+            ..Default::default()
+        }))
+    } else {
+        id.playlist_id.clone().map(SearchMatch::Playlist)
+    }
+}
+
+/// `liveBroadcastContent` is `live` while a stream is broadcasting,
+/// and `upcoming` or `none` otherwise.
+fn is_live(live_broadcast_content: Option<&str>) -> bool {
+    live_broadcast_content == Some("live")
+}
+
+/// Parses the ISO 8601 durations used by the Data API, e.g. `PT1H2M3S` or `P1DT2H`.
+pub(crate) fn parse_iso8601_duration(duration: &str) -> Option<Duration> {
+    let duration = duration.strip_prefix('P')?;
+    let (date, time) = duration.split_once('T').unwrap_or((duration, ""));
+
+    let mut secs = 0;
+
+    let date_units: &[(char, u64)] = &[('W', 604_800), ('D', 86_400)];
+    let time_units: &[(char, u64)] = &[('H', 3600), ('M', 60), ('S', 1)];
+
+    for (part, units) in [(date, date_units), (time, time_units)] {
+        let mut digits = String::new();
+
+        for c in part.chars() {
+            if c.is_ascii_digit() {
+                digits.push(c);
+                continue;
+            }
+
+            let (_, unit) = units.iter().find(|(designator, _)| *designator == c)?;
+            secs += digits.parse::<u64>().ok()? * unit;
+            digits.clear();
+        }
+
+        if !digits.is_empty() {
+            return None;
+        }
+    }
+
+    Some(Duration::from_secs(secs))
+}
@@ -0,0 +1,180 @@
+use serenity::async_trait;
+use std::{collections::HashMap, sync::Mutex};
+
+use crate::{
+    client_state::QueueElement,
+    config::Error,
+    utils::source_retriever::youtube::{
+        client::{PlaylistPage, SearchMatch, YouTubeClient, PLAYLIST_URI, SINGLE_URI},
+        link::ChannelLink,
+    },
+};
+
+/// An in-memory stand-in for the YouTube Data API.
+/// Playlist pages are addressed by their index, which doubles as the page token.
+#[derive(Default)]
+pub(crate) struct FakeClient {
+    videos: HashMap<String, QueueElement>,
+    playlists: HashMap<String, (QueueElement, Vec<Vec<QueueElement>>)>,
+    searches: HashMap<String, SearchMatch>,
+    channels: HashMap<ChannelLink, String>,
+    mixes: HashMap<String, Vec<QueueElement>>,
+    requests: Mutex<Vec<String>>,
+    quota_exceeded: bool,
+    mixes_unsupported: bool,
+}
+
+pub(crate) fn video(id: &str) -> QueueElement {
+    QueueElement {
+        title: format!("Video {id}"),
+        channel_name: "Channel".to_string(),
+        url: format!("{SINGLE_URI}{id}"),
+        id: id.to_string(),
+        ..Default::default()
+    }
+}
+
+impl FakeClient {
+    pub(crate) fn with_video(mut self, id: &str) -> Self {
+        self.videos.insert(id.to_string(), video(id));
+        self
+    }
+
+    pub(crate) fn with_playlist(mut self, id: &str, pages: Vec<Vec<&str>>) -> Self {
+        let playlist = QueueElement {
+            title: format!("Playlist {id}"),
+            channel_name: "Channel".to_string(),
+            url: format!("{PLAYLIST_URI}{id}"),
+            id: id.to_string(),
+            ..Default::default()
+        };
+
+        let pages = pages
+            .into_iter()
+            .map(|page| page.into_iter().map(video).collect())
+            .collect();
+
+        self.playlists.insert(id.to_string(), (playlist, pages));
+        self
+    }
+
+    pub(crate) fn with_search(mut self, query: &str, best_match: SearchMatch) -> Self {
+        self.searches.insert(query.to_string(), best_match);
+        self
+    }
+
+    pub(crate) fn with_channel(mut self, channel: ChannelLink, uploads: &str) -> Self {
+        self.channels.insert(channel, uploads.to_string());
+        self
+    }
+
+    pub(crate) fn with_mix(mut self, id: &str, items: Vec<&str>) -> Self {
+        self.mixes
+            .insert(id.to_string(), items.into_iter().map(video).collect());
+        self
+    }
+
+    /// Makes every lookup fail as if the API quota were spent.
+    pub(crate) fn exhausted(mut self) -> Self {
+        self.quota_exceeded = true;
+        self
+    }
+
+    /// Makes mix lookups fail like they do on the Data API.
+    pub(crate) fn without_mixes(mut self) -> Self {
+        self.mixes_unsupported = true;
+        self
+    }
+
+    pub(crate) fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    fn record(&self, request: String) -> Result<(), Error> {
+        self.requests.lock().unwrap().push(request);
+
+        if self.quota_exceeded {
+            return Err(google_youtube3::Error::BadRequest(serde_json::json!({
+                "error": { "code": 403, "errors": [{ "reason": "quotaExceeded" }] }
+            }))
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl YouTubeClient for FakeClient {
+    async fn video(&self, video_id: &str) -> Result<Option<QueueElement>, Error> {
+        self.record(format!("video:{video_id}"))?;
+        Ok(self.videos.get(video_id).cloned())
+    }
+
+    async fn playlist(&self, playlist_id: &str) -> Result<Option<QueueElement>, Error> {
+        self.record(format!("playlist:{playlist_id}"))?;
+        Ok(self
+            .playlists
+            .get(playlist_id)
+            .map(|(playlist, _)| playlist.clone()))
+    }
+
+    async fn playlist_page(
+        &self,
+        playlist_id: &str,
+        page_token: Option<&str>,
+    ) -> Result<PlaylistPage, Error> {
+        self.record(format!(
+            "playlist_page:{playlist_id}:{}",
+            page_token.unwrap_or("0")
+        ))?;
+
+        let index = page_token
+            .and_then(|t| t.parse::<usize>().ok())
+            .unwrap_or(0);
+        let pages = self
+            .playlists
+            .get(playlist_id)
+            .map(|(_, pages)| pages.as_slice())
+            .unwrap_or_default();
+
+        Ok(PlaylistPage {
+            items: pages.get(index).cloned().unwrap_or_default(),
+            next_page_token: (index + 1 < pages.len()).then(|| (index + 1).to_string()),
+            ..Default::default()
+        })
+    }
+
+    async fn search(&self, query: &str) -> Result<Option<SearchMatch>, Error> {
+        self.record(format!("search:{query}"))?;
+        Ok(self.searches.get(query).cloned())
+    }
+
+    async fn uploads_playlist(&self, channel: &ChannelLink) -> Result<Option<String>, Error> {
+        self.record(format!("uploads_playlist:{}", channel.url()))?;
+        Ok(self.channels.get(channel).cloned())
+    }
+
+    async fn mix(
+        &self,
+        mix_id: &str,
+        _video_id: Option<&str>,
+    ) -> Result<Option<(QueueElement, Vec<QueueElement>)>, Error> {
+        self.record(format!("mix:{mix_id}"))?;
+        if self.mixes_unsupported {
+            return Err(Error::Unsupported("No mixes.".to_string()));
+        }
+        Ok(self.mixes.get(mix_id).map(|items| {
+            (
+                QueueElement {
+                    title: format!("Mix {mix_id}"),
+                    channel_name: "YouTube".to_string(),
+                    url: format!("{PLAYLIST_URI}{mix_id}"),
+                    id: mix_id.to_string(),
+                    ..Default::default()
+                },
+                items.clone(),
+            )
+        }))
+    }
+}
@@ -0,0 +1,317 @@
+use log::warn;
+use serenity::async_trait;
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    client_state::QueueElement,
+    config::Error,
+    utils::source_retriever::youtube::{
+        client::{PlaylistPage, SearchMatch, YouTubeClient},
+        link::ChannelLink,
+    },
+};
+
+/// How long lookups bypass the primary client after it reports an exhausted quota.
+const QUOTA_COOLDOWN: Duration = Duration::from_secs(60 * 60);
+
+/// Prefixes the page tokens of the fallback client, which only it can continue.
+const FALLBACK_TOKEN: &str = "fallback:";
+
+/// Routes lookups to a primary client, switching to a fallback client
+/// for a while once the primary reports an exhausted quota.
+/// Lookups the primary doesn't support always go to the fallback.
+pub(crate) struct FallbackClient {
+    primary: Box<dyn YouTubeClient>,
+    fallback: Box<dyn YouTubeClient>,
+    exhausted_until: Mutex<Option<Instant>>,
+}
+
+impl FallbackClient {
+    pub(crate) fn new(primary: Box<dyn YouTubeClient>, fallback: Box<dyn YouTubeClient>) -> Self {
+        FallbackClient {
+            primary,
+            fallback,
+            exhausted_until: Mutex::new(None),
+        }
+    }
+
+    fn primary_exhausted(&self) -> bool {
+        let mut exhausted_until = self.exhausted_until.lock().unwrap();
+
+        match *exhausted_until {
+            Some(until) if Instant::now() < until => true,
+            Some(_) => {
+                *exhausted_until = None;
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the result unless it reports an exhausted quota, in which case
+    /// the fallback client is used from now on, or an unsupported lookup.
+    fn check<T>(&self, result: Result<T, Error>) -> Option<Result<T, Error>> {
+        match result {
+            Err(Error::Unsupported(_)) => None,
+            Err(err) if err.is_quota_exceeded() => {
+                warn!("YouTube API quota exhausted, falling back to yt-dlp. Error: {err}");
+                *self.exhausted_until.lock().unwrap() = Some(Instant::now() + QUOTA_COOLDOWN);
+                None
+            }
+            result => Some(result),
+        }
+    }
+}
+
+#[async_trait]
+impl YouTubeClient for FallbackClient {
+    async fn video(&self, video_id: &str) -> Result<Option<QueueElement>, Error> {
+        if !self.primary_exhausted() {
+            if let Some(result) = self.check(self.primary.video(video_id).await) {
+                return result;
+            }
+        }
+
+        self.fallback.video(video_id).await
+    }
+
+    async fn playlist(&self, playlist_id: &str) -> Result<Option<QueueElement>, Error> {
+        if !self.primary_exhausted() {
+            if let Some(result) = self.check(self.primary.playlist(playlist_id).await) {
+                return result;
+            }
+        }
+
+        self.fallback.playlist(playlist_id).await
+    }
+
+    /// Page tokens are specific to a client. Tokens of the fallback are marked and keep paging
+    /// on the fallback. A playlist paged on the primary starts over on the fallback if the
+    /// primary runs out mid-way, which the page reports as `restarted`.
+    async fn playlist_page(
+        &self,
+        playlist_id: &str,
+        page_token: Option<&str>,
+    ) -> Result<PlaylistPage, Error> {
+        let fallback_token = page_token.and_then(|token| token.strip_prefix(FALLBACK_TOKEN));
+
+        if fallback_token.is_none() && !self.primary_exhausted() {
+            if let Some(result) =
+                self.check(self.primary.playlist_page(playlist_id, page_token).await)
+            {
+                return result;
+            }
+        }
+
+        let mut page = self
+            .fallback
+            .playlist_page(playlist_id, fallback_token)
+            .await?;
+        page.next_page_token = page
+            .next_page_token
+            .map(|token| format!("{FALLBACK_TOKEN}{token}"));
+        page.restarted |= page_token.is_some() && fallback_token.is_none();
+
+        Ok(page)
+    }
+
+    async fn search(&self, query: &str) -> Result<Option<SearchMatch>, Error> {
+        if !self.primary_exhausted() {
+            if let Some(result) = self.check(self.primary.search(query).await) {
+                return result;
+            }
+        }
+
+        self.fallback.search(query).await
+    }
+
+    async fn uploads_playlist(&self, channel: &ChannelLink) -> Result<Option<String>, Error> {
+        if !self.primary_exhausted() {
+            if let Some(result) = self.check(self.primary.uploads_playlist(channel).await) {
+                return result;
+            }
+        }
+
+        self.fallback.uploads_playlist(channel).await
+    }
+
+    async fn mix(
+        &self,
+        mix_id: &str,
+        video_id: Option<&str>,
+    ) -> Result<Option<(QueueElement, Vec<QueueElement>)>, Error> {
+        if !self.primary_exhausted() {
+            if let Some(result) = self.check(self.primary.mix(mix_id, video_id).await) {
+                return result;
+            }
+        }
+
+        self.fallback.mix(mix_id, video_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::source_retriever::youtube::fake::FakeClient;
+    use std::sync::Arc;
+
+    fn clients(
+        primary: FakeClient,
+        fallback: FakeClient,
+    ) -> (FallbackClient, Arc<FakeClient>, Arc<FakeClient>) {
+        let (primary, fallback) = (Arc::new(primary), Arc::new(fallback));
+
+        (
+            FallbackClient::new(Box::new(primary.clone()), Box::new(fallback.clone())),
+            primary,
+            fallback,
+        )
+    }
+
+    #[tokio::test]
+    async fn uses_primary_while_it_has_quota() {
+        let (client, _, fallback) = clients(
+            FakeClient::default().with_video("abc"),
+            FakeClient::default().with_video("abc"),
+        );
+
+        assert!(client.video("abc").await.unwrap().is_some());
+        assert!(fallback.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn switches_to_fallback_once_quota_is_exhausted() {
+        let (client, primary, fallback) = clients(
+            FakeClient::default().exhausted(),
+            FakeClient::default().with_video("abc").with_video("def"),
+        );
+
+        assert!(client.video("abc").await.unwrap().is_some());
+        assert!(client.video("def").await.unwrap().is_some());
+        assert_eq!(primary.requests(), vec!["video:abc"]);
+        assert_eq!(fallback.requests(), vec!["video:abc", "video:def"]);
+    }
+
+    #[tokio::test]
+    async fn other_errors_are_not_retried() {
+        struct Broken;
+
+        #[async_trait]
+        impl YouTubeClient for Broken {
+            async fn video(&self, _: &str) -> Result<Option<QueueElement>, Error> {
+                Err(Error::SourceResolution("broken".to_string()))
+            }
+
+            async fn playlist(&self, _: &str) -> Result<Option<QueueElement>, Error> {
+                unimplemented!()
+            }
+
+            async fn playlist_page(&self, _: &str, _: Option<&str>) -> Result<PlaylistPage, Error> {
+                unimplemented!()
+            }
+
+            async fn search(&self, _: &str) -> Result<Option<SearchMatch>, Error> {
+                unimplemented!()
+            }
+
+            async fn uploads_playlist(&self, _: &ChannelLink) -> Result<Option<String>, Error> {
+                unimplemented!()
+            }
+
+            async fn mix(
+                &self,
+                _: &str,
+                _: Option<&str>,
+            ) -> Result<Option<(QueueElement, Vec<QueueElement>)>, Error> {
+                unimplemented!()
+            }
+        }
+
+        let fallback = Arc::new(FakeClient::default().with_video("abc"));
+        let client = FallbackClient::new(Box::new(Broken), Box::new(fallback.clone()));
+
+        assert!(client.video("abc").await.is_err());
+        assert!(fallback.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn unsupported_lookups_go_to_the_fallback() {
+        let (client, primary, fallback) = clients(
+            FakeClient::default().without_mixes().with_video("abc"),
+            FakeClient::default().with_mix("RDabc", vec!["abc"]),
+        );
+
+        assert!(client.mix("RDabc", None).await.unwrap().is_some());
+        assert!(client.video("abc").await.unwrap().is_some());
+        assert_eq!(primary.requests(), vec!["mix:RDabc", "video:abc"]);
+        assert_eq!(fallback.requests(), vec!["mix:RDabc"]);
+    }
+
+    fn pages(count: usize) -> Vec<Vec<String>> {
+        (0..count)
+            .map(|i| format!("v{i}"))
+            .collect::<Vec<_>>()
+            .chunks(50)
+            .map(<[String]>::to_vec)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn pages_through_a_playlist_on_the_fallback() {
+        let pages = pages(120);
+        let (client, _, fallback) = clients(
+            FakeClient::default().exhausted(),
+            FakeClient::default().with_playlist(
+                "PL1",
+                pages
+                    .iter()
+                    .map(|page| page.iter().map(String::as_str).collect())
+                    .collect(),
+            ),
+        );
+
+        let mut ids = vec![];
+        let mut page_token = None;
+        loop {
+            let page = client
+                .playlist_page("PL1", page_token.as_deref())
+                .await
+                .unwrap();
+            assert!(!page.restarted);
+            ids.extend(page.items.into_iter().map(|item| item.id));
+
+            match page.next_page_token {
+                Some(token) => page_token = Some(token),
+                None => break,
+            }
+        }
+
+        assert_eq!(ids, pages.concat());
+        assert_eq!(
+            fallback.requests(),
+            vec![
+                "playlist_page:PL1:0",
+                "playlist_page:PL1:1",
+                "playlist_page:PL1:2"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn primary_page_tokens_restart_on_the_fallback() {
+        let (client, _, _) = clients(
+            FakeClient::default().exhausted(),
+            FakeClient::default().with_playlist("PL1", vec![vec!["a"], vec!["b"]]),
+        );
+
+        let page = client.playlist_page("PL1", Some("CDIQAA")).await.unwrap();
+
+        assert!(page.restarted);
+        assert_eq!(page.items[0].id, "a");
+        assert_eq!(page.next_page_token.as_deref(), Some("fallback:1"));
+    }
+}
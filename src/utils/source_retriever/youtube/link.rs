@@ -0,0 +1,364 @@
+use std::time::Duration;
+use url::Url;
+
+use crate::utils;
+
+const HOSTS: [&str; 5] = [
+    "youtube.com",
+    "youtu.be",
+    "youtube-nocookie.com",
+    "m.youtube.com",
+    "music.youtube.com",
+];
+
+/// The resources referenced by a YouTube link, in any of its common shapes:
+/// `/watch?v=`, `youtu.be/<id>`, `/shorts/<id>`, `/embed/<id>`, `/live/<id>`, `/v/<id>`,
+/// `/playlist?list=` and channel pages, on the desktop, mobile and music domains.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct YouTubeLink {
+    pub(crate) video_id: Option<String>,
+    pub(crate) playlist_id: Option<String>,
+    pub(crate) channel: Option<ChannelLink>,
+    /// The offset requested with a `t` or `start` parameter.
+    pub(crate) start: Option<Duration>,
+    /// The 1-based playlist position requested with an `index` parameter.
+    pub(crate) index: Option<usize>,
+}
+
+/// The ways a channel page can identify its channel.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum ChannelLink {
+    /// `/channel/<id>`
+    Id(String),
+    /// `/@<handle>`
+    Handle(String),
+    /// `/c/<name>`
+    CustomUrl(String),
+    /// `/user/<name>`
+    Username(String),
+}
+
+impl ChannelLink {
+    pub(crate) fn url(&self) -> String {
+        match self {
+            ChannelLink::Id(id) => format!("https://www.youtube.com/channel/{id}"),
+            ChannelLink::Handle(handle) => format!("https://www.youtube.com/@{handle}"),
+            ChannelLink::CustomUrl(name) => format!("https://www.youtube.com/c/{name}"),
+            ChannelLink::Username(name) => format!("https://www.youtube.com/user/{name}"),
+        }
+    }
+}
+
+/// The uploads playlist of a channel shares its id, with a `UU` prefix instead of `UC`.
+pub(crate) fn uploads_playlist_id(channel_id: &str) -> Option<String> {
+    channel_id
+        .strip_prefix("UC")
+        .filter(|id| !id.is_empty())
+        .map(|id| format!("UU{id}"))
+}
+
+/// Auto-generated mixes have ids prefixed with `RD`. They cannot be paged through the Data API.
+pub(crate) fn is_mix(playlist_id: &str) -> bool {
+    playlist_id.starts_with("RD")
+}
+
+/// Checks whether a domain belongs to YouTube.
+pub(crate) fn is_youtube_domain(domain: &str) -> bool {
+    let domain = domain.trim_start_matches("www.");
+    HOSTS.contains(&domain)
+}
+
+/// Normalises a YouTube URL. Returns `None` if the URL does not reference a video or playlist.
+pub(crate) fn parse(source: &Url) -> Option<YouTubeLink> {
+    let domain = source.domain()?.to_lowercase();
+    if !is_youtube_domain(&domain) {
+        return None;
+    }
+
+    let param = |name: &str| {
+        source
+            .query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    let segments = source
+        .path_segments()
+        .map(|segments| segments.filter(|s| !s.is_empty()).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let channel = if domain.ends_with("youtu.be") {
+        None
+    } else {
+        match segments.as_slice() {
+            [handle, ..] if handle.starts_with('@') => {
+                Some(ChannelLink::Handle(handle[1..].to_string()))
+            }
+            ["channel", id, ..] => Some(ChannelLink::Id(id.to_string())),
+            ["c", name, ..] => Some(ChannelLink::CustomUrl(name.to_string())),
+            ["user", name, ..] => Some(ChannelLink::Username(name.to_string())),
+            _ => None,
+        }
+    }
+    .filter(|channel| match channel {
+        ChannelLink::Id(id) => is_valid_id(id),
+        ChannelLink::Handle(name) | ChannelLink::CustomUrl(name) | ChannelLink::Username(name) => {
+            is_valid_id(&name.replace('.', ""))
+        }
+    });
+
+    if channel.is_some() {
+        return Some(YouTubeLink {
+            channel,
+            ..Default::default()
+        });
+    }
+
+    let video_id = if domain.ends_with("youtu.be") {
+        segments.first().map(|id| id.to_string())
+    } else {
+        match segments.as_slice() {
+            ["watch"] => param("v"),
+            ["shorts" | "embed" | "live" | "v", id, ..] => Some(id.to_string()),
+            _ => None,
+        }
+    }
+    .filter(|id| is_valid_id(id));
+
+    let playlist_id = match segments.as_slice() {
+        [_] | ["embed", ..] => param("list"),
+        _ => None,
+    }
+    .filter(|id| is_valid_id(id));
+
+    if video_id.is_none() && playlist_id.is_none() {
+        return None;
+    }
+
+    let start = param("t")
+        .or_else(|| param("start"))
+        .or_else(|| {
+            source
+                .fragment()
+                .and_then(|fragment| fragment.strip_prefix("t="))
+                .map(|t| t.to_string())
+        })
+        .and_then(|t| utils::parse_timestamp(&t));
+
+    let index = param("index")
+        .and_then(|index| index.parse::<usize>().ok())
+        .filter(|index| *index > 0);
+
+    Some(YouTubeLink {
+        video_id,
+        playlist_id,
+        channel: None,
+        start,
+        index,
+    })
+}
+
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(url: &str) -> Option<YouTubeLink> {
+        parse(&Url::parse(url).unwrap())
+    }
+
+    fn video(id: &str) -> Option<YouTubeLink> {
+        Some(YouTubeLink {
+            video_id: Some(id.to_string()),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn parses_video_links() {
+        for url in [
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            "https://youtube.com/watch?feature=share&v=dQw4w9WgXcQ",
+            "https://m.youtube.com/watch?v=dQw4w9WgXcQ",
+            "https://music.youtube.com/watch?v=dQw4w9WgXcQ",
+            "https://youtu.be/dQw4w9WgXcQ",
+            "https://youtu.be/dQw4w9WgXcQ?si=tracking",
+            "https://www.youtube.com/shorts/dQw4w9WgXcQ",
+            "https://www.youtube.com/embed/dQw4w9WgXcQ",
+            "https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ",
+            "https://www.youtube.com/live/dQw4w9WgXcQ?feature=share",
+            "https://www.youtube.com/v/dQw4w9WgXcQ",
+        ] {
+            assert_eq!(link(url), video("dQw4w9WgXcQ"), "{url}");
+        }
+    }
+
+    #[test]
+    fn parses_playlist_links() {
+        for url in [
+            "https://www.youtube.com/playlist?list=PL123",
+            "https://music.youtube.com/playlist?list=PL123",
+            "https://m.youtube.com/playlist?list=PL123",
+        ] {
+            assert_eq!(
+                link(url),
+                Some(YouTubeLink {
+                    playlist_id: Some("PL123".to_string()),
+                    ..Default::default()
+                }),
+                "{url}"
+            );
+        }
+    }
+
+    #[test]
+    fn parses_video_in_playlist_links() {
+        for url in [
+            "https://www.youtube.com/watch?v=abc&list=PL123",
+            "https://youtu.be/abc?list=PL123",
+            "https://www.youtube.com/embed/abc?list=PL123",
+        ] {
+            assert_eq!(
+                link(url),
+                Some(YouTubeLink {
+                    video_id: Some("abc".to_string()),
+                    playlist_id: Some("PL123".to_string()),
+                    ..Default::default()
+                }),
+                "{url}"
+            );
+        }
+    }
+
+    #[test]
+    fn parses_timestamps() {
+        for (url, secs) in [
+            ("https://youtu.be/abc?t=90", 90),
+            ("https://youtu.be/abc?t=90s", 90),
+            ("https://www.youtube.com/watch?v=abc&t=1m30s", 90),
+            ("https://www.youtube.com/watch?v=abc&t=1h2m3s", 3723),
+            ("https://www.youtube.com/embed/abc?start=42", 42),
+            ("https://www.youtube.com/watch?v=abc#t=15", 15),
+        ] {
+            assert_eq!(
+                link(url).and_then(|link| link.start),
+                Some(Duration::from_secs(secs)),
+                "{url}"
+            );
+        }
+    }
+
+    #[test]
+    fn parses_playlist_index() {
+        let index = |url| link(url).and_then(|link| link.index);
+
+        assert_eq!(
+            index("https://www.youtube.com/watch?v=abc&list=PL123&index=7"),
+            Some(7)
+        );
+        assert_eq!(
+            index("https://www.youtube.com/watch?v=abc&list=PL123&index=0"),
+            None
+        );
+        assert_eq!(
+            index("https://www.youtube.com/watch?v=abc&list=PL123&index=x"),
+            None
+        );
+    }
+
+    #[test]
+    fn parses_channel_links() {
+        for (url, channel) in [
+            (
+                "https://www.youtube.com/@some.creator",
+                ChannelLink::Handle("some.creator".to_string()),
+            ),
+            (
+                "https://m.youtube.com/@creator/videos",
+                ChannelLink::Handle("creator".to_string()),
+            ),
+            (
+                "https://www.youtube.com/channel/UC123abc",
+                ChannelLink::Id("UC123abc".to_string()),
+            ),
+            (
+                "https://www.youtube.com/c/Creator",
+                ChannelLink::CustomUrl("Creator".to_string()),
+            ),
+            (
+                "https://www.youtube.com/user/creator",
+                ChannelLink::Username("creator".to_string()),
+            ),
+        ] {
+            assert_eq!(
+                link(url).and_then(|link| link.channel),
+                Some(channel),
+                "{url}"
+            );
+        }
+
+        assert_eq!(link("https://www.youtube.com/@"), None);
+        assert_eq!(link("https://www.youtube.com/channel/"), None);
+    }
+
+    #[test]
+    fn derives_uploads_playlist() {
+        assert_eq!(uploads_playlist_id("UC123abc").as_deref(), Some("UU123abc"));
+        assert_eq!(uploads_playlist_id("HC123abc"), None);
+        assert_eq!(uploads_playlist_id("UC"), None);
+    }
+
+    #[test]
+    fn recognises_mixes() {
+        assert!(is_mix("RDdQw4w9WgXcQ"));
+        assert!(is_mix("RDCLAK5uy_kmPRjHDECIcuVwnKsx2Ng7fyNgFKWNJFs"));
+        assert!(!is_mix("PL123"));
+    }
+
+    #[test]
+    fn ignores_malformed_timestamps() {
+        assert_eq!(
+            link("https://youtu.be/abc?t=soon").and_then(|link| link.start),
+            None
+        );
+        assert_eq!(
+            link("https://youtu.be/abc?t=1m30").and_then(|link| link.start),
+            None
+        );
+        assert_eq!(
+            link("https://youtu.be/abc?t=99999999999999999h").and_then(|link| link.start),
+            None
+        );
+    }
+
+    #[test]
+    fn rejects_links_without_resources() {
+        for url in [
+            "https://www.youtube.com/",
+            "https://www.youtube.com/watch",
+            "https://www.youtube.com/watch?v=",
+            "https://www.youtube.com/feed/trending",
+            "https://youtu.be/",
+            "https://www.youtube.com/watch?v=not%20an%20id",
+            "https://notyoutube.com/watch?v=abc",
+        ] {
+            assert_eq!(link(url), None, "{url}");
+        }
+    }
+
+    #[test]
+    fn recognises_youtube_domains() {
+        assert!(is_youtube_domain("www.youtube.com"));
+        assert!(is_youtube_domain("music.youtube.com"));
+        assert!(is_youtube_domain("youtu.be"));
+        assert!(!is_youtube_domain("youtube.com.example.org"));
+        assert!(!is_youtube_domain("soundcloud.com"));
+    }
+}
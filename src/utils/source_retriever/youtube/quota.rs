@@ -0,0 +1,160 @@
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, TimeZone, Utc, Weekday};
+use std::sync::Mutex;
+
+/// The daily quota granted to a YouTube Data API project by default.
+pub(crate) const DEFAULT_DAILY_QUOTA: u32 = 10_000;
+
+/// The unit cost of the Data API calls used by the bot.
+pub(crate) const LIST_COST: u32 = 1;
+pub(crate) const SEARCH_COST: u32 = 100;
+
+/// The Data API quota resets at midnight Pacific Time. Returns the Pacific offset at `now`,
+/// UTC-7 from 2 am on the second Sunday of March to 2 am on the first Sunday of November,
+/// UTC-8 otherwise.
+fn pacific(now: DateTime<Utc>) -> FixedOffset {
+    let transition = |month, n, utc_hour| {
+        let day = NaiveDate::from_weekday_of_month_opt(now.year(), month, Weekday::Sun, n).unwrap();
+        Utc.from_utc_datetime(&day.and_hms_opt(utc_hour, 0, 0).unwrap())
+    };
+    let daylight_saving = transition(3, 2, 10) <= now && now < transition(11, 1, 9);
+
+    FixedOffset::west_opt(if daylight_saving { 7 } else { 8 } * 3600).unwrap()
+}
+
+/// The Pacific date at `now`.
+fn pacific_date(now: DateTime<Utc>) -> NaiveDate {
+    now.with_timezone(&pacific(now)).date_naive()
+}
+
+#[derive(Debug)]
+struct Usage {
+    day: NaiveDate,
+    used: u32,
+}
+
+/// Tracks the Data API units spent today.
+#[derive(Debug)]
+pub(crate) struct QuotaTracker {
+    daily_limit: u32,
+    usage: Mutex<Usage>,
+}
+
+impl QuotaTracker {
+    pub(crate) fn new(daily_limit: u32) -> Self {
+        QuotaTracker {
+            daily_limit,
+            usage: Mutex::new(Usage {
+                day: pacific_date(Utc::now()),
+                used: 0,
+            }),
+        }
+    }
+
+    pub(crate) fn record(&self, cost: u32) {
+        self.record_at(cost, Utc::now());
+    }
+
+    pub(crate) fn used(&self) -> u32 {
+        self.used_at(Utc::now())
+    }
+
+    pub(crate) fn remaining(&self) -> u32 {
+        self.daily_limit.saturating_sub(self.used())
+    }
+
+    pub(crate) fn daily_limit(&self) -> u32 {
+        self.daily_limit
+    }
+
+    /// The next time the quota resets.
+    pub(crate) fn resets_at(&self) -> DateTime<Utc> {
+        next_reset(Utc::now())
+    }
+
+    fn record_at(&self, cost: u32, now: DateTime<Utc>) {
+        let mut usage = self.usage.lock().unwrap();
+        Self::roll_over(&mut usage, now);
+        usage.used = usage.used.saturating_add(cost);
+    }
+
+    fn used_at(&self, now: DateTime<Utc>) -> u32 {
+        let mut usage = self.usage.lock().unwrap();
+        Self::roll_over(&mut usage, now);
+        usage.used
+    }
+
+    fn roll_over(usage: &mut Usage, now: DateTime<Utc>) {
+        let today = pacific_date(now);
+
+        if usage.day != today {
+            *usage = Usage {
+                day: today,
+                used: 0,
+            };
+        }
+    }
+}
+
+/// The next Pacific midnight after `now`. Clocks change at 2 am, so midnight
+/// has the offset of 8 am UTC on the same date.
+fn next_reset(now: DateTime<Utc>) -> DateTime<Utc> {
+    let midnight = (pacific_date(now) + Duration::days(1))
+        .and_hms_opt(0, 0, 0)
+        .unwrap();
+    let offset = pacific(Utc.from_utc_datetime(&(midnight + Duration::hours(8))));
+
+    offset
+        .from_local_datetime(&midnight)
+        .unwrap()
+        .with_timezone(&Utc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(rfc3339: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(rfc3339)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn counts_units_within_a_day() {
+        let quota = QuotaTracker::new(DEFAULT_DAILY_QUOTA);
+        let now = Utc::now();
+
+        quota.record_at(LIST_COST, now);
+        quota.record_at(SEARCH_COST, now);
+
+        assert_eq!(quota.used_at(now), 101);
+    }
+
+    #[test]
+    fn resets_at_pacific_midnight() {
+        let quota = QuotaTracker::new(DEFAULT_DAILY_QUOTA);
+
+        quota.record_at(SEARCH_COST, at("2023-06-01T06:59:00Z"));
+        assert_eq!(quota.used_at(at("2023-06-01T06:59:59Z")), SEARCH_COST);
+        assert_eq!(quota.used_at(at("2023-06-01T07:00:00Z")), 0);
+
+        quota.record_at(SEARCH_COST, at("2023-12-01T07:59:00Z"));
+        assert_eq!(quota.used_at(at("2023-12-01T07:59:59Z")), SEARCH_COST);
+        assert_eq!(quota.used_at(at("2023-12-01T08:00:00Z")), 0);
+    }
+
+    #[test]
+    fn reset_follows_daylight_saving_time() {
+        for (now, reset) in [
+            ("2023-01-15T12:00:00Z", "2023-01-16T08:00:00Z"),
+            ("2023-07-15T12:00:00Z", "2023-07-16T07:00:00Z"),
+            // Clocks go forward on March 12 and back on November 5, 2023.
+            ("2023-03-11T12:00:00Z", "2023-03-12T08:00:00Z"),
+            ("2023-03-12T12:00:00Z", "2023-03-13T07:00:00Z"),
+            ("2023-11-04T12:00:00Z", "2023-11-05T07:00:00Z"),
+            ("2023-11-05T12:00:00Z", "2023-11-06T08:00:00Z"),
+        ] {
+            assert_eq!(next_reset(at(now)), at(reset), "{now}");
+        }
+    }
+}
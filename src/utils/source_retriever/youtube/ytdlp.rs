@@ -0,0 +1,363 @@
+use log::warn;
+use serde::Deserialize;
+use serenity::async_trait;
+use std::time::Duration;
+use tokio::process::Command;
+
+use crate::{
+    client_state::QueueElement,
+    config::Error,
+    metrics,
+    utils::{
+        chapters::{self, Chapter},
+        source_retriever::youtube::{
+            client::{PlaylistPage, SearchMatch, YouTubeClient, PLAYLIST_URI, SINGLE_URI},
+            link::{self, ChannelLink},
+        },
+    },
+};
+
+const PAGE_SIZE: usize = 50;
+
+/// The subset of yt-dlp's info JSON used to build queue elements.
+#[derive(Debug, Default, Deserialize)]
+struct Info {
+    id: Option<String>,
+    title: Option<String>,
+    channel: Option<String>,
+    channel_id: Option<String>,
+    uploader: Option<String>,
+    duration: Option<f64>,
+    live_status: Option<String>,
+    description: Option<String>,
+    chapters: Option<Vec<ChapterInfo>>,
+    #[serde(default)]
+    entries: Vec<Info>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChapterInfo {
+    start_time: f64,
+    title: String,
+}
+
+impl Info {
+    /// Prefers the chapters yt-dlp extracted, which include YouTube's automatic chapters.
+    /// Flat playlist entries carry neither chapters nor a description.
+    fn chapters(&self) -> Option<Vec<Chapter>> {
+        match &self.chapters {
+            Some(chapters) if !chapters.is_empty() => Some(
+                chapters
+                    .iter()
+                    .filter_map(|chapter| {
+                        Some(Chapter {
+                            title: chapter.title.clone(),
+                            start: Duration::try_from_secs_f64(chapter.start_time.max(0.0)).ok()?,
+                        })
+                    })
+                    .collect(),
+            ),
+            _ => self.description.as_deref().map(chapters::parse),
+        }
+    }
+
+    fn channel_name(&self) -> String {
+        self.channel
+            .clone()
+            .or_else(|| self.uploader.clone())
+            .unwrap_or_default()
+    }
+
+    fn mix(&self, mix_id: &str, url: &str) -> (QueueElement, Vec<QueueElement>) {
+        (
+            QueueElement {
+                title: self.title.clone().unwrap_or_else(|| "Mix".to_string()),
+                channel_name: "YouTube".to_string(),
+                url: url.to_string(),
+                id: mix_id.to_string(),
+                ..Default::default()
+            },
+            self.entries
+                .iter()
+                .filter_map(Info::video_element)
+                .collect(),
+        )
+    }
+
+    fn video_element(&self) -> Option<QueueElement> {
+        let id = self.id.clone()?;
+
+        let live = self.live_status.as_deref() == Some("is_live");
+
+        Some(QueueElement {
+            title: self.title.clone()?,
+            channel_name: self.channel_name(),
+            url: format!("{}{}", SINGLE_URI, id),
+            id,
+            duration: self
+                .duration
+                .filter(|duration| !live && *duration > 0.0)
+                .and_then(|duration| Duration::try_from_secs_f64(duration).ok()),
+            live,
+            chapters: self.chapters(),
+            ..Default::default()
+        })
+    }
+}
+
+/// A client that scrapes metadata with yt-dlp. It needs no API key and consumes no quota,
+/// at the cost of slower lookups.
+#[derive(Default)]
+pub(crate) struct YtDlpClient;
+
+impl YtDlpClient {
+    /// Runs yt-dlp and parses its JSON output. Returns `None` if yt-dlp could not resolve the target.
+    async fn dump(&self, args: &[&str], target: &str) -> Result<Option<Info>, Error> {
+        let mut command = Command::new("yt-dlp");
+        command
+            .args(["--dump-single-json", "--no-warnings", "--skip-download"])
+            .args(args)
+            .arg("--")
+            .arg(target);
+
+        let output = metrics::YTDLP_SECONDS
+            .time(&["metadata"], command.output())
+            .await
+            .map_err(|err| Error::SourceResolution(format!("Could not run yt-dlp: {err}")))?;
+
+        if !output.status.success() {
+            warn!(
+                "yt-dlp could not resolve {target}: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            return Ok(None);
+        }
+
+        parse_info(&output.stdout).map(Some)
+    }
+}
+
+fn parse_info(json: &[u8]) -> Result<Info, Error> {
+    serde_json::from_slice(json)
+        .map_err(|err| Error::SourceResolution(format!("Unexpected yt-dlp output: {err}")))
+}
+
+#[async_trait]
+impl YouTubeClient for YtDlpClient {
+    async fn video(&self, video_id: &str) -> Result<Option<QueueElement>, Error> {
+        Ok(self
+            .dump(&["--no-playlist"], &format!("{SINGLE_URI}{video_id}"))
+            .await?
+            .and_then(|info| info.video_element()))
+    }
+
+    async fn playlist(&self, playlist_id: &str) -> Result<Option<QueueElement>, Error> {
+        Ok(self
+            .dump(
+                &["--flat-playlist", "--playlist-items", "1"],
+                &format!("{PLAYLIST_URI}{playlist_id}"),
+            )
+            .await?
+            .map(|info| QueueElement {
+                title: info.title.clone().unwrap_or_default(),
+                channel_name: info.channel_name(),
+                url: format!("{}{}", PLAYLIST_URI, playlist_id),
+                id: playlist_id.to_string(),
+                ..Default::default()
+            }))
+    }
+
+    /// Pages are addressed by the 1-based index of their first item.
+    async fn playlist_page(
+        &self,
+        playlist_id: &str,
+        page_token: Option<&str>,
+    ) -> Result<PlaylistPage, Error> {
+        let first = page_token
+            .and_then(|token| token.parse::<usize>().ok())
+            .unwrap_or(1);
+        let range = format!("{}:{}", first, first + PAGE_SIZE - 1);
+
+        let info = self
+            .dump(
+                &["--flat-playlist", "--playlist-items", &range],
+                &format!("{PLAYLIST_URI}{playlist_id}"),
+            )
+            .await?
+            .unwrap_or_default();
+
+        Ok(PlaylistPage {
+            next_page_token: (info.entries.len() == PAGE_SIZE)
+                .then(|| (first + PAGE_SIZE).to_string()),
+            items: info
+                .entries
+                .iter()
+                .filter_map(Info::video_element)
+                .collect(),
+            ..Default::default()
+        })
+    }
+
+    async fn search(&self, query: &str) -> Result<Option<SearchMatch>, Error> {
+        Ok(self
+            .dump(&["--flat-playlist"], &format!("ytsearch1:{query}"))
+            .await?
+            .and_then(|info| info.entries.first().and_then(Info::video_element))
+            .map(SearchMatch::Video))
+    }
+
+    async fn uploads_playlist(&self, channel: &ChannelLink) -> Result<Option<String>, Error> {
+        let channel_id = match channel {
+            ChannelLink::Id(id) => Some(id.clone()),
+            _ => self
+                .dump(
+                    &["--flat-playlist", "--playlist-items", "1"],
+                    &channel.url(),
+                )
+                .await?
+                .and_then(|info| info.channel_id),
+        };
+
+        Ok(channel_id.and_then(|id| link::uploads_playlist_id(&id)))
+    }
+
+    async fn mix(
+        &self,
+        mix_id: &str,
+        video_id: Option<&str>,
+    ) -> Result<Option<(QueueElement, Vec<QueueElement>)>, Error> {
+        let url = match video_id {
+            Some(video_id) => format!("{SINGLE_URI}{video_id}&list={mix_id}"),
+            None => format!("{PLAYLIST_URI}{mix_id}"),
+        };
+
+        Ok(self
+            .dump(&["--flat-playlist"], &url)
+            .await?
+            .map(|info| info.mix(mix_id, &url)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn video_info_maps_to_element() {
+        let info = parse_info(
+            br#"{"id": "abc", "title": "Title", "channel": "Channel", "uploader": "Uploader", "duration": 212}"#,
+        )
+        .unwrap();
+
+        let element = info.video_element().unwrap();
+        assert_eq!(element.id, "abc");
+        assert_eq!(element.title, "Title");
+        assert_eq!(element.channel_name, "Channel");
+        assert_eq!(element.url, "https://youtube.com/watch?v=abc");
+        assert_eq!(element.duration, Some(Duration::from_secs(212)));
+        assert!(!element.live);
+    }
+
+    #[test]
+    fn live_info_is_marked_live() {
+        let info = parse_info(
+            br#"{"id": "abc", "title": "Stream", "channel": "Channel", "live_status": "is_live"}"#,
+        )
+        .unwrap();
+
+        let element = info.video_element().unwrap();
+        assert!(element.live);
+        assert_eq!(element.duration, None);
+    }
+
+    #[test]
+    fn out_of_range_times_are_dropped() {
+        let info = parse_info(
+            br#"{"id": "abc", "title": "Title", "duration": 1e20,
+                "chapters": [{"start_time": 0.0, "title": "First"},
+                             {"start_time": 1e20, "title": "Never"}]}"#,
+        )
+        .unwrap();
+
+        let element = info.video_element().unwrap();
+        assert_eq!(element.duration, None);
+        assert_eq!(element.chapters.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn chapters_prefer_extracted_ones() {
+        let info = parse_info(
+            br#"{"id": "abc", "title": "Title", "description": "0:00 A\n1:00 B\n2:00 C",
+                "chapters": [{"start_time": 0.0, "end_time": 90.0, "title": "First"},
+                             {"start_time": 90.0, "end_time": 180.0, "title": "Second"}]}"#,
+        )
+        .unwrap();
+
+        let chapters = info.video_element().unwrap().chapters.unwrap();
+        assert_eq!(chapters.len(), 2);
+        assert_eq!(chapters[1].title, "Second");
+        assert_eq!(chapters[1].start, Duration::from_secs(90));
+
+        let info = parse_info(
+            br#"{"id": "abc", "title": "Title", "description": "0:00 A\n1:00 B\n2:00 C"}"#,
+        )
+        .unwrap();
+        assert_eq!(info.video_element().unwrap().chapters.unwrap().len(), 3);
+
+        let info = parse_info(br#"{"id": "abc", "title": "Title"}"#).unwrap();
+        assert_eq!(info.video_element().unwrap().chapters, None);
+    }
+
+    #[test]
+    fn flat_playlist_entries_map_to_elements() {
+        let info = parse_info(
+            br#"{"_type": "playlist", "id": "PL1", "title": "Mix", "uploader": "Someone", "entries": [
+                {"_type": "url", "id": "a", "title": "A", "channel": "One"},
+                {"_type": "url", "id": "b", "title": null},
+                {"_type": "url", "id": "c", "title": "C", "uploader": "Three"}
+            ]}"#,
+        )
+        .unwrap();
+
+        let items = info
+            .entries
+            .iter()
+            .filter_map(Info::video_element)
+            .collect::<Vec<_>>();
+
+        assert_eq!(info.channel_name(), "Someone");
+        assert_eq!(
+            items
+                .iter()
+                .map(|item| item.id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["a", "c"]
+        );
+        assert_eq!(items[1].channel_name, "Three");
+    }
+
+    #[test]
+    fn mix_entries_map_to_elements() {
+        let info = parse_info(
+            br#"{"_type": "playlist", "id": "RDabc", "title": "Mix - Title", "entries": [
+                {"_type": "url", "id": "abc", "title": "Title", "channel": "One"},
+                {"_type": "url", "id": "def", "title": "Other", "channel": "Two"}
+            ]}"#,
+        )
+        .unwrap();
+
+        let (mix, items) = info.mix("RDabc", "https://youtube.com/watch?v=abc&list=RDabc");
+
+        assert_eq!(mix.title, "Mix - Title");
+        assert_eq!(mix.id, "RDabc");
+        assert_eq!(items.len(), 2);
+    }
+
+    #[test]
+    fn malformed_output_is_an_error() {
+        assert!(matches!(
+            parse_info(b"ERROR: not json"),
+            Err(Error::SourceResolution(_))
+        ));
+    }
+}
@@ -1,33 +1,41 @@
 use songbird::Event;
+use std::sync::Mutex;
 
 use crate::{
-    client_state::ClientState,
+    client_state::{ClientState, PlaybackState},
     config::{Context, Error},
-    handlers::{DisconnectHandler, InactivityHandler, ReconnectHandler},
+    handlers::{DisconnectHandler, IdleHandler, InactivityHandler, ReconnectHandler},
+    utils::{self, announcer::Announcer},
 };
 
 /// This function uses songbird to connect the bot to the command author's voice channel.
 /// The implementation assumes that the author is already in a voice channel.
 pub async fn summon(context: &Context<'_>) -> Result<(), Error> {
-    let guild = context.guild().unwrap();
-    let guild_id = *context.guild_id().unwrap().as_u64();
+    let guild = utils::guild(context)?;
+    let guild_id = *guild.id.as_u64();
 
-    let r_lock = context.data().client_state_map.read().await;
-    if r_lock.contains_key(&guild_id) {
+    if context.data().client_state_map.contains_key(&guild_id) {
         return Ok(());
     }
-    drop(r_lock);
 
     let channel_id = guild
         .voice_states
         .get(&context.author().id)
         .and_then(|v_state| v_state.channel_id)
-        .unwrap();
+        .ok_or_else(|| {
+            Error::UserInput("Whoops. Looks like you're not in a voice channel.".to_string())
+        })?;
 
     if let Some(manager) = songbird::get(context.serenity_context()).await {
         match manager.join(guild_id, channel_id).await {
             (call, Ok(_)) => {
                 let mut call = call.lock().await;
+                let announcer = Announcer {
+                    guild_id: guild.id,
+                    http: context.serenity_context().http.clone(),
+                    client_state_map: context.data().client_state_map.clone(),
+                    settings: context.data().settings.clone(),
+                };
 
                 call.add_global_event(
                     Event::Core(songbird::CoreEvent::ClientDisconnect),
@@ -36,6 +44,19 @@ pub async fn summon(context: &Context<'_>) -> Result<(), Error> {
                         manager: manager.clone(),
                         cache: context.serenity_context().cache.clone(),
                         guild: guild.clone(),
+                        announcer: announcer.clone(),
+                    },
+                );
+
+                call.add_global_event(
+                    Event::Periodic(IdleHandler::PERIOD, None),
+                    IdleHandler {
+                        client_state_map: context.data().client_state_map.clone(),
+                        manager: manager.clone(),
+                        settings: context.data().settings.clone(),
+                        guild_id: guild.id,
+                        idle_since: Mutex::new(None),
+                        announcer: announcer.clone(),
                     },
                 );
 
@@ -45,6 +66,7 @@ pub async fn summon(context: &Context<'_>) -> Result<(), Error> {
                         client_state_map: context.data().client_state_map.clone(),
                         manager: manager.clone(),
                         guild: guild.clone(),
+                        announcer: announcer.clone(),
                     },
                 );
 
@@ -56,34 +78,31 @@ pub async fn summon(context: &Context<'_>) -> Result<(), Error> {
                     },
                 );
             }
-            _ => {
-                context.say("Sorry. Something went wrong.").await?;
-                return Ok(());
+            (_, Err(err)) => {
+                return Err(Error::VoiceConnection(format!(
+                    "Could not join channel {channel_id}. Error: {err:?}"
+                )));
             }
         };
     } else {
-        context
-            .say(format!(
-                "Sorry {}. I couldn't join your voice channel.\
-            Please ensure that I have the permission needed to join.",
-                context.author().name
-            ))
-            .await?;
-        return Ok(());
+        return Err(Error::VoiceConnection(
+            "Songbird is not initialized.".to_string(),
+        ));
     }
 
-    let mut w_lock = context.data().client_state_map.write().await;
-    w_lock
-        .insert(
-            &guild_id,
-            &mut ClientState {
-                current_channel: Some(*channel_id.as_u64()),
-                current_track: None,
-                song_queue: Some(vec![]),
-                is_playing: false,
-            },
-        )
-        .expect("ClientState insertion failed.");
+    context.data().client_state_map.insert(
+        &guild_id,
+        ClientState {
+            current_channel: Some(*channel_id.as_u64()),
+            text_channel: Some(*context.channel_id().as_u64()),
+            current_track: None,
+            now_playing: None,
+            song_queue: Some(vec![]),
+            playback: PlaybackState::Idle,
+            playlist_imports: vec![],
+            load: 0,
+        },
+    )?;
 
     Ok(())
 }
@@ -0,0 +1,627 @@
+use chrono::{DateTime, Utc};
+use hyper::{
+    client::connect::{
+        dns::{GaiResolver, Name},
+        HttpConnector,
+    },
+    header,
+    service::Service,
+    Body, Method, Request, StatusCode,
+};
+use hyper_rustls::HttpsConnector;
+use log::{debug, warn};
+use poise::serenity_prelude::GuildId;
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::sync::{
+    broadcast::{self, error::RecvError},
+    mpsc::{self, error::TrySendError},
+};
+use url::{Host, Url};
+
+use crate::{
+    client_state::{GuildEvent, StateEvent},
+    settings::SettingsStore,
+};
+
+/// The most webhooks a guild may register.
+pub(crate) const MAX_WEBHOOKS: usize = 5;
+
+/// The header carrying the payload's HMAC-SHA256 signature, for webhooks with a secret.
+pub(crate) const SIGNATURE_HEADER: &str = "X-Luna-Signature";
+
+/// The header naming the event, which is also part of the payload.
+pub(crate) const EVENT_HEADER: &str = "X-Luna-Event";
+
+/// An HTTP endpoint a guild's playback events are posted to.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Webhook {
+    pub(crate) url: String,
+    /// Signs payloads when set, so the receiver can check they came from the bot.
+    pub(crate) secret: Option<String>,
+}
+
+// Keeps secrets out of the logs.
+impl fmt::Debug for Webhook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Webhook")
+            .field("url", &self.url)
+            .field("secret", &self.secret.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+/// Returns the name and JSON payload posted for an event, or `None` if webhooks don't
+/// receive this kind of event.
+fn payload(event: &GuildEvent, at: DateTime<Utc>) -> Option<(&'static str, Value)> {
+    let (name, data) = match &event.event {
+        StateEvent::TrackStarted(element) => {
+            ("track_started", json!({ "track": element.to_json() }))
+        }
+        StateEvent::TrackEnded(element) => ("track_ended", json!({ "track": element.to_json() })),
+        StateEvent::QueueChanged { length } => ("queue_changed", json!({ "length": length })),
+        StateEvent::Joined { channel } => (
+            "joined",
+            json!({ "channel_id": channel.map(|channel| channel.to_string()) }),
+        ),
+        StateEvent::Left => ("left", json!({})),
+        StateEvent::Paused | StateEvent::Resumed | StateEvent::Idle => return None,
+    };
+
+    Some((
+        name,
+        json!({
+            "event": name,
+            // Discord ids exceed the integers JSON parsers reliably handle.
+            "guild_id": event.guild_id.to_string(),
+            "timestamp": at.to_rfc3339(),
+            "data": data,
+        }),
+    ))
+}
+
+/// Signs a payload with HMAC-SHA256, formatted as `sha256=<hex digest>`.
+pub(crate) fn signature(secret: &str, body: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    format!("sha256={}", hex::encode(hmac::sign(&key, body)))
+}
+
+/// Whether a failed delivery is worth retrying.
+fn is_transient(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::REQUEST_TIMEOUT
+}
+
+/// Whether an address is reachable from the internet at large. Posting to loopback, private,
+/// link-local or other special-purpose addresses would reach the bot's own host or network.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || first == 0
+                // Carrier-grade NAT, 100.64.0.0/10.
+                || (first == 100 && second & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => match embedded_ipv4(ip) {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // Unique local, fc00::/7.
+                    || first & 0xfe00 == 0xfc00
+                    // Link-local, fe80::/10.
+                    || first & 0xffc0 == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Returns the IPv4 address an IPv4-compatible, IPv4-mapped, 6to4 or NAT64 address
+/// is routed to, so it is judged by the IPv4 rules.
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let octets = ip.octets();
+    let at = |i: usize| Ipv4Addr::new(octets[i], octets[i + 1], octets[i + 2], octets[i + 3]);
+
+    match ip.segments() {
+        [0, 0, 0, 0, 0, 0 | 0xffff, ..] => Some(at(12)),
+        // 6to4, 2002::/16.
+        [0x2002, ..] => Some(at(2)),
+        // NAT64, 64:ff9b::/96.
+        [0x64, 0xff9b, 0, 0, 0, 0, ..] => Some(at(12)),
+        _ => None,
+    }
+}
+
+/// Checks that a webhook URL is an http or https URL whose host only resolves to public
+/// addresses, unless private ones are allowed. Returns the reason it was rejected.
+pub(crate) async fn check_target(url: &str, allow_private: bool) -> Result<(), String> {
+    let not_http = || format!("<{url}> is not an http or https URL.");
+    let parsed = Url::parse(url).map_err(|_| not_http())?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(not_http());
+    }
+
+    let port = parsed.port_or_known_default().unwrap_or(80);
+    let addresses = match parsed.host() {
+        Some(Host::Domain(domain)) => tokio::net::lookup_host((domain, port))
+            .await
+            .map_err(|_| format!("Could not resolve {domain}."))?
+            .map(|address| address.ip())
+            .collect::<Vec<_>>(),
+        Some(Host::Ipv4(ip)) => vec![IpAddr::V4(ip)],
+        Some(Host::Ipv6(ip)) => vec![IpAddr::V6(ip)],
+        None => return Err(not_http()),
+    };
+
+    if addresses.is_empty() {
+        return Err(format!("Could not resolve <{url}>."));
+    }
+    if !allow_private && !addresses.into_iter().all(is_public) {
+        return Err(format!("<{url}> points to a private or local address."));
+    }
+
+    Ok(())
+}
+
+/// Resolves webhook hosts, dropping the addresses deliveries may not go to. Checking
+/// again on every connection keeps a host from switching to a private address later.
+#[derive(Clone)]
+struct TargetResolver {
+    inner: GaiResolver,
+    allow_private: bool,
+}
+
+impl Service<Name> for TargetResolver {
+    type Response = std::vec::IntoIter<SocketAddr>;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, io::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        let resolving = self.inner.call(name.clone());
+        let allow_private = self.allow_private;
+
+        Box::pin(async move {
+            let addresses = resolving
+                .await?
+                .filter(|address| allow_private || is_public(address.ip()))
+                .collect::<Vec<_>>();
+
+            if addresses.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("{name} only resolves to private or local addresses."),
+                ));
+            }
+
+            Ok(addresses.into_iter())
+        })
+    }
+}
+
+/// An event waiting to be posted to a webhook: the webhook, the event's name and its payload.
+type Delivery = (Webhook, &'static str, String);
+
+/// Posts the events of each guild to the webhooks it registered.
+pub(crate) struct WebhookDispatcher {
+    settings: Arc<SettingsStore>,
+    http: hyper::Client<HttpsConnector<HttpConnector<TargetResolver>>>,
+    /// Lets webhooks post to loopback, private and link-local addresses.
+    allow_private: bool,
+    /// The wait before the first retry, doubled for every further one.
+    backoff: Duration,
+}
+
+impl WebhookDispatcher {
+    const MAX_ATTEMPTS: u32 = 5;
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// The most events waiting for a webhook. Further events are dropped until it catches up.
+    const QUEUE_SIZE: usize = 32;
+
+    pub(crate) fn new(settings: Arc<SettingsStore>, allow_private: bool) -> Self {
+        let mut http = HttpConnector::new_with_resolver(TargetResolver {
+            inner: GaiResolver::new(),
+            allow_private,
+        });
+        http.enforce_http(false);
+
+        let https = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_native_roots()
+            .https_or_http()
+            .enable_http1()
+            .wrap_connector(http);
+
+        WebhookDispatcher {
+            settings,
+            http: hyper::Client::builder().build(https),
+            allow_private,
+            backoff: Duration::from_secs(1),
+        }
+    }
+
+    /// Delivers events until the event bus closes. Each webhook has its own task delivering
+    /// its events in order, so a slow or failing endpoint doesn't hold up the others.
+    pub(crate) fn start(self, mut events: broadcast::Receiver<GuildEvent>) {
+        let dispatcher = Arc::new(self);
+
+        tokio::spawn(async move {
+            let mut queues = HashMap::<(GuildId, String), mpsc::Sender<Delivery>>::new();
+
+            loop {
+                let event = match events.recv().await {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!("Webhooks missed {missed} events.");
+                        continue;
+                    }
+                    Err(RecvError::Closed) => return,
+                };
+
+                let webhooks = dispatcher.settings.get(event.guild_id).webhooks;
+                // Dropping the queue of a removed webhook ends its task once it drained.
+                queues.retain(|(guild_id, url), _| {
+                    *guild_id != event.guild_id
+                        || webhooks.iter().any(|webhook| webhook.url == *url)
+                });
+                if webhooks.is_empty() {
+                    continue;
+                }
+
+                let (name, payload) = match payload(&event, Utc::now()) {
+                    Some(payload) => payload,
+                    None => continue,
+                };
+                let body = payload.to_string();
+
+                for webhook in webhooks {
+                    let key = (event.guild_id, webhook.url.clone());
+                    let queue = queues
+                        .entry(key.clone())
+                        .or_insert_with(|| dispatcher.clone().worker());
+
+                    match queue.try_send((webhook, name, body.clone())) {
+                        Ok(()) => {}
+                        Err(TrySendError::Full(_)) => {
+                            warn!("Webhook {} is falling behind, dropping {name}.", key.1);
+                        }
+                        Err(TrySendError::Closed(_)) => {
+                            queues.remove(&key);
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    /// Spawns the task delivering a webhook's events one at a time. It ends when the
+    /// returned queue is dropped.
+    fn worker(self: Arc<Self>) -> mpsc::Sender<Delivery> {
+        let (sender, mut receiver) = mpsc::channel::<Delivery>(Self::QUEUE_SIZE);
+
+        tokio::spawn(async move {
+            while let Some((webhook, event, body)) = receiver.recv().await {
+                self.deliver(&webhook, event, body).await;
+            }
+        });
+
+        sender
+    }
+
+    /// Posts a payload, retrying with exponential backoff while the endpoint is unreachable
+    /// or reports a transient error. Returns whether the endpoint accepted it.
+    async fn deliver(&self, webhook: &Webhook, event: &str, body: String) -> bool {
+        if let Err(reason) = check_target(&webhook.url, self.allow_private).await {
+            warn!(
+                "Not delivering {event} to webhook {}. {reason}",
+                webhook.url
+            );
+            return false;
+        }
+
+        let mut backoff = self.backoff;
+
+        for attempt in 1..=Self::MAX_ATTEMPTS {
+            let mut request = Request::builder()
+                .method(Method::POST)
+                .uri(&webhook.url)
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::USER_AGENT, "luna-rs")
+                .header(EVENT_HEADER, event);
+            if let Some(secret) = &webhook.secret {
+                request = request.header(SIGNATURE_HEADER, signature(secret, body.as_bytes()));
+            }
+
+            let request = match request.body(Body::from(body.clone())) {
+                Ok(request) => request,
+                Err(err) => {
+                    warn!("Invalid webhook {}. Error: {err}", webhook.url);
+                    return false;
+                }
+            };
+
+            let retry = match tokio::time::timeout(Self::TIMEOUT, self.http.request(request)).await
+            {
+                Ok(Ok(response)) if response.status().is_success() => return true,
+                Ok(Ok(response)) => {
+                    debug!(
+                        "Webhook {} answered {} to {event}.",
+                        webhook.url,
+                        response.status()
+                    );
+                    is_transient(response.status())
+                }
+                Ok(Err(err)) => {
+                    debug!("Could not reach webhook {}. Error: {err}", webhook.url);
+                    true
+                }
+                Err(_) => {
+                    debug!("Webhook {} timed out.", webhook.url);
+                    true
+                }
+            };
+
+            if !retry || attempt == Self::MAX_ATTEMPTS {
+                break;
+            }
+
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+
+        warn!("Giving up delivering {event} to webhook {}.", webhook.url);
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client_state::QueueElement;
+    use std::sync::Mutex;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    /// A local HTTP receiver that answers with the given statuses in turn and records
+    /// the requests it received.
+    async fn receiver(statuses: Vec<u16>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(vec![]));
+
+        let received = requests.clone();
+        tokio::spawn(async move {
+            for status in statuses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+
+                let mut request = vec![];
+                let mut buffer = [0; 4096];
+                loop {
+                    let read = stream.read(&mut buffer).await.unwrap();
+                    request.extend_from_slice(&buffer[..read]);
+
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                        let length = head
+                            .lines()
+                            .find_map(|line| {
+                                line.to_lowercase()
+                                    .strip_prefix("content-length: ")
+                                    .map(str::to_string)
+                            })
+                            .and_then(|length| length.parse::<usize>().ok())
+                            .unwrap_or_default();
+                        if body.len() >= length {
+                            received.lock().unwrap().push(text);
+                            break;
+                        }
+                    }
+                }
+
+                let response = format!(
+                    "HTTP/1.1 {status} Status\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        (url, requests)
+    }
+
+    /// The test receivers listen on loopback, so private addresses are allowed.
+    fn dispatcher() -> WebhookDispatcher {
+        WebhookDispatcher {
+            backoff: Duration::from_millis(10),
+            ..WebhookDispatcher::new(Arc::new(SettingsStore::new(None)), true)
+        }
+    }
+
+    #[test]
+    fn payloads_describe_the_event() {
+        let event = GuildEvent {
+            guild_id: GuildId(1234),
+            event: StateEvent::TrackStarted(QueueElement {
+                id: "abc".to_string(),
+                title: "Title".to_string(),
+                duration: Some(Duration::from_secs(90)),
+                ..Default::default()
+            }),
+        };
+        let at = DateTime::parse_from_rfc3339("2024-01-02T03:04:05Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let (name, payload) = payload(&event, at).unwrap();
+
+        assert_eq!(name, "track_started");
+        assert_eq!(payload["guild_id"], "1234");
+        assert_eq!(payload["timestamp"], "2024-01-02T03:04:05+00:00");
+        assert_eq!(payload["data"]["track"]["id"], "abc");
+        assert_eq!(payload["data"]["track"]["duration"], 90);
+    }
+
+    #[test]
+    fn signatures_match_known_digest() {
+        // From RFC 4231, test case 2.
+        assert_eq!(
+            signature("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[tokio::test]
+    async fn deliveries_are_signed_and_retried() {
+        let (url, requests) = receiver(vec![503, 200]).await;
+        let webhook = Webhook {
+            url,
+            secret: Some("secret".to_string()),
+        };
+
+        let delivered = dispatcher()
+            .deliver(&webhook, "left", r#"{"event":"left"}"#.to_string())
+            .await;
+
+        assert!(delivered);
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].to_lowercase().contains(&format!(
+            "x-luna-signature: {}",
+            signature("secret", br#"{"event":"left"}"#)
+        )));
+        assert!(requests[1].ends_with(r#"{"event":"left"}"#));
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_not_retried() {
+        let (url, requests) = receiver(vec![404, 200]).await;
+        let webhook = Webhook { url, secret: None };
+
+        let delivered = dispatcher()
+            .deliver(&webhook, "left", "{}".to_string())
+            .await;
+
+        assert!(!delivered);
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn private_targets_need_the_opt_in() {
+        for url in [
+            "http://127.0.0.1:8080/hook",
+            "http://localhost/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://10.0.0.5/hook",
+            "http://192.168.1.1/hook",
+            "http://[::1]:8080/hook",
+            "http://[fd00::1]/hook",
+            "http://[::ffff:127.0.0.1]/hook",
+            "http://[::127.0.0.1]/hook",
+            "http://[::a9fe:a9fe]/hook",
+            "http://[2002:a9fe:a9fe::1]/hook",
+            "http://[2002:7f00:1::]/hook",
+            "http://[64:ff9b::10.0.0.1]/hook",
+            "http://[64:ff9b::a9fe:a9fe]/hook",
+        ] {
+            assert!(check_target(url, false).await.is_err(), "{url}");
+            assert!(check_target(url, true).await.is_ok(), "{url}");
+        }
+
+        assert!(check_target("https://93.184.216.34/hook", false)
+            .await
+            .is_ok());
+        assert!(check_target("http://[2002:5db8:d822::1]/hook", false)
+            .await
+            .is_ok());
+        assert!(check_target("http://[64:ff9b::5db8:d822]/hook", false)
+            .await
+            .is_ok());
+        assert!(check_target("http://[2606:4700::1111]/hook", false)
+            .await
+            .is_ok());
+        assert!(check_target("ftp://93.184.216.34", true).await.is_err());
+        assert!(check_target("file:///etc/passwd", true).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn private_targets_are_not_delivered_to() {
+        let (url, requests) = receiver(vec![200]).await;
+        let webhook = Webhook { url, secret: None };
+
+        let delivered = WebhookDispatcher::new(Arc::new(SettingsStore::new(None)), false)
+            .deliver(&webhook, "left", "{}".to_string())
+            .await;
+
+        assert!(!delivered);
+        assert!(requests.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn events_are_delivered_in_order() {
+        let (url, requests) = receiver(vec![503, 200, 200]).await;
+        let settings = Arc::new(SettingsStore::new(None));
+        settings
+            .update(GuildId(1), |settings| {
+                settings.webhooks.push(Webhook { url, secret: None })
+            })
+            .await;
+        let (events, subscriber) = broadcast::channel(8);
+
+        WebhookDispatcher {
+            backoff: Duration::from_millis(50),
+            ..WebhookDispatcher::new(settings, true)
+        }
+        .start(subscriber);
+
+        for event in [StateEvent::Joined { channel: None }, StateEvent::Left] {
+            events
+                .send(GuildEvent {
+                    guild_id: GuildId(1),
+                    event,
+                })
+                .unwrap();
+        }
+
+        for _ in 0..100 {
+            if requests.lock().unwrap().len() == 3 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        let events = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|request| request.contains(r#""event":"joined""#))
+            .collect::<Vec<_>>();
+        assert_eq!(events, [true, true, false]);
+    }
+}